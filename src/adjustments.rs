@@ -0,0 +1,459 @@
+use crate::color_space::{self, ColorSpace};
+use crate::lut3d::Lut3D;
+use serde::Deserialize;
+use std::io::{self, Error};
+
+/// Rec.709 luma weights used for saturation.
+const LUMA: [f32; 3] = [0.2126, 0.7152, 0.0722];
+/// Linear mid-grey value contrast pivots around.
+const MID_GREY: f32 = 0.18;
+/// Correlated color temperature of the white the temperature shift starts from.
+const NEUTRAL_CCT: f64 = 6504.0;
+/// Distance from the daylight locus in CIE 1960 uv per unit of tint.
+const TINT_SCALE: f64 = 0.0002;
+
+/// Parametric color adjustments a LUT can be generated from.
+///
+/// All fields default to leaving colors unchanged. Adjustments are applied in a
+/// fixed order: white balance, exposure, contrast, and saturation in linear light,
+/// then hue-vs-hue and the tone curves on sRGB-encoded values.
+#[derive(Clone, Debug, PartialEq, Deserialize)]
+#[serde(default)]
+pub struct Adjustments {
+    /// Exposure change in stops
+    pub exposure: f32,
+    /// Contrast around 18% grey as a power of linear light (1 leaves colors unchanged)
+    pub contrast: f32,
+    /// White balance shift in mireds from -100 (cooler) to 100 (warmer)
+    pub temperature: f32,
+    /// White balance shift from -100 (green) to 100 (magenta)
+    pub tint: f32,
+    /// Saturation around Rec.709 luma (1 leaves colors unchanged, 0 is greyscale)
+    pub saturation: f32,
+    /// Hue shifts as `[hue, shift]` points in degrees, interpolated around the hue
+    /// circle
+    pub hue_vs_hue: Vec<[f32; 2]>,
+    /// Tone curve applied to all channels as `[input, output]` points in [0, 1]
+    pub curve: Vec<[f32; 2]>,
+    /// Tone curve of the red channel, applied after `curve`
+    pub red_curve: Vec<[f32; 2]>,
+    /// Tone curve of the green channel, applied after `curve`
+    pub green_curve: Vec<[f32; 2]>,
+    /// Tone curve of the blue channel, applied after `curve`
+    pub blue_curve: Vec<[f32; 2]>,
+}
+
+impl Default for Adjustments {
+    /// Adjustments leaving every color unchanged.
+    fn default() -> Self {
+        Adjustments {
+            exposure: 0.0,
+            contrast: 1.0,
+            temperature: 0.0,
+            tint: 0.0,
+            saturation: 1.0,
+            hue_vs_hue: Vec::new(),
+            curve: Vec::new(),
+            red_curve: Vec::new(),
+            green_curve: Vec::new(),
+            blue_curve: Vec::new(),
+        }
+    }
+}
+
+/// Adjustments prepared for evaluation, see [`Adjustments::prepare`].
+struct Prepared {
+    /// White balance in linear Rec.709, with the exposure gain folded in
+    matrix: [[f32; 3]; 3],
+    contrast: f32,
+    saturation: f32,
+    hue_vs_hue: Option<Curve>,
+    curve: Option<Curve>,
+    channel_curves: [Option<Curve>; 3],
+}
+
+impl Adjustments {
+    /// Checks the values and precomputes the white balance and curves.
+    fn prepare(&self) -> Result<Prepared, Error> {
+        let finite = [
+            self.exposure,
+            self.contrast,
+            self.temperature,
+            self.tint,
+            self.saturation,
+        ]
+        .iter()
+        .all(|v| v.is_finite());
+        if !finite {
+            return Err(invalid("Adjustments must be finite numbers."));
+        }
+        if self.contrast <= 0.0 {
+            return Err(invalid("The contrast must be greater than 0."));
+        }
+        if self.saturation < 0.0 {
+            return Err(invalid("The saturation must not be negative."));
+        }
+        if self.temperature.abs() > 100.0 || self.tint.abs() > 100.0 {
+            return Err(invalid(
+                "The temperature and tint must be between -100 and 100.",
+            ));
+        }
+
+        let gain = 2f32.powf(self.exposure);
+        let wb = white_balance(self.temperature as f64, self.tint as f64);
+        let tone = |name: &str, points: &[[f32; 2]]| -> Result<Option<Curve>, Error> {
+            if points.is_empty() {
+                return Ok(None);
+            }
+            Curve::tone(points).map(Some).ok_or_else(|| {
+                invalid(&format!(
+                    "The {} must have increasing inputs within [0, 1] and outputs within [0, 1].",
+                    name
+                ))
+            })
+        };
+        let hue_vs_hue = if self.hue_vs_hue.is_empty() {
+            None
+        } else {
+            Some(Curve::hue(&self.hue_vs_hue).ok_or_else(|| {
+                invalid("The hue-vs-hue curve must have distinct hues within [0, 360).")
+            })?)
+        };
+        Ok(Prepared {
+            matrix: wb.map(|row| row.map(|v| v as f32 * gain)),
+            contrast: self.contrast,
+            saturation: self.saturation,
+            hue_vs_hue,
+            curve: tone("tone curve", &self.curve)?,
+            channel_curves: [
+                tone("red curve", &self.red_curve)?,
+                tone("green curve", &self.green_curve)?,
+                tone("blue curve", &self.blue_curve)?,
+            ],
+        })
+    }
+}
+
+impl Prepared {
+    /// Applies the adjustments to an sRGB-encoded color.
+    fn apply(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let srgb = ColorSpace::Srgb;
+        let lin = rgb.map(|v| srgb.to_linear(v));
+        let m = &self.matrix;
+        let lin: [f32; 3] =
+            std::array::from_fn(|i| m[i][0] * lin[0] + m[i][1] * lin[1] + m[i][2] * lin[2]);
+
+        // Contrast is a straight line through mid grey in log space
+        let lin = lin.map(|v| {
+            if v > 0.0 {
+                MID_GREY * (v / MID_GREY).powf(self.contrast)
+            } else {
+                v
+            }
+        });
+        let luma = lin[0] * LUMA[0] + lin[1] * LUMA[1] + lin[2] * LUMA[2];
+        let lin = lin.map(|v| luma + self.saturation * (v - luma));
+        let mut rgb = lin.map(|v| srgb.from_linear(v).clamp(0.0, 1.0));
+
+        if let Some(hue_vs_hue) = &self.hue_vs_hue {
+            let [h, s, v] = rgb_to_hsv(rgb);
+            rgb = hsv_to_rgb([(h + hue_vs_hue.eval(h)).rem_euclid(360.0), s, v]);
+        }
+        if let Some(curve) = &self.curve {
+            rgb = rgb.map(|v| curve.eval(v));
+        }
+        for (v, curve) in rgb.iter_mut().zip(&self.channel_curves) {
+            if let Some(curve) = curve {
+                *v = curve.eval(*v);
+            }
+        }
+        rgb.map(|v| v.clamp(0.0, 1.0))
+    }
+}
+
+/// Generates a LUT from parametric adjustments.
+///
+/// # Arguments
+/// * `adjustments` - The adjustments to evaluate
+/// * `size` - The size of each dimension of the lattice (e.g. 33)
+///
+/// # Returns
+/// A `Result` containing the generated `Lut3D`, its comments listing the
+/// adjustments
+///
+/// # Errors
+/// Returns an error if `size` is smaller than 2, or an adjustment is out of range
+/// or has a malformed curve
+pub fn bake(adjustments: &Adjustments, size: usize) -> Result<Lut3D, Error> {
+    let prepared = adjustments.prepare()?;
+    let mut lut = Lut3D::bake(size, |rgb| prepared.apply(rgb))?;
+    lut.set_title("Generated LUT");
+    lut.add_comment(format!(
+        " Exposure {}, contrast {}, temperature {}, tint {}, saturation {}",
+        adjustments.exposure,
+        adjustments.contrast,
+        adjustments.temperature,
+        adjustments.tint,
+        adjustments.saturation
+    ));
+    let curves = [
+        ("Hue vs hue", &adjustments.hue_vs_hue),
+        ("Tone curve", &adjustments.curve),
+        ("Red curve", &adjustments.red_curve),
+        ("Green curve", &adjustments.green_curve),
+        ("Blue curve", &adjustments.blue_curve),
+    ];
+    for (name, points) in curves {
+        if !points.is_empty() {
+            lut.add_comment(format!(" {} {:?}", name, points));
+        }
+    }
+    Ok(lut)
+}
+
+/// A cubic Hermite curve through control points.
+struct Curve {
+    /// Control points sorted by input
+    points: Vec<[f32; 2]>,
+    /// Slope at each control point
+    slopes: Vec<f32>,
+}
+
+impl Curve {
+    /// Builds a monotone tone curve, so increasing points never overshoot.
+    ///
+    /// `(0, 0)` and `(1, 1)` are added unless the points already start at 0 or end
+    /// at 1. Returns `None` if inputs aren't increasing or a value is outside [0, 1].
+    fn tone(points: &[[f32; 2]]) -> Option<Curve> {
+        let in_range = |v: f32| (0.0..=1.0).contains(&v);
+        if !points.iter().flatten().all(|&v| in_range(v))
+            || points.windows(2).any(|w| w[1][0] <= w[0][0])
+        {
+            return None;
+        }
+        let mut points = points.to_vec();
+        if points[0][0] > 0.0 {
+            points.insert(0, [0.0, 0.0]);
+        }
+        if points[points.len() - 1][0] < 1.0 {
+            points.push([1.0, 1.0]);
+        }
+
+        // Fritsch-Carlson: start from the secant average and limit the slopes so
+        // each segment stays within its end values
+        let secants: Vec<f32> = points
+            .windows(2)
+            .map(|w| (w[1][1] - w[0][1]) / (w[1][0] - w[0][0]))
+            .collect();
+        let n = points.len();
+        let mut slopes: Vec<f32> = (0..n)
+            .map(|i| match i {
+                0 => secants[0],
+                i if i == n - 1 => secants[n - 2],
+                i if secants[i - 1] * secants[i] <= 0.0 => 0.0,
+                i => (secants[i - 1] + secants[i]) / 2.0,
+            })
+            .collect();
+        for (i, &d) in secants.iter().enumerate() {
+            if d == 0.0 {
+                slopes[i] = 0.0;
+                slopes[i + 1] = 0.0;
+                continue;
+            }
+            let (a, b) = (slopes[i] / d, slopes[i + 1] / d);
+            let norm = (a * a + b * b).sqrt();
+            if norm > 3.0 {
+                slopes[i] = 3.0 * a / norm * d;
+                slopes[i + 1] = 3.0 * b / norm * d;
+            }
+        }
+        Some(Curve { points, slopes })
+    }
+
+    /// Builds a smooth periodic curve over hue in degrees.
+    ///
+    /// Returns `None` if hues repeat or are outside [0, 360).
+    fn hue(points: &[[f32; 2]]) -> Option<Curve> {
+        let mut sorted = points.to_vec();
+        sorted.sort_by(|a, b| a[0].total_cmp(&b[0]));
+        if !sorted
+            .iter()
+            .all(|p| (0.0..360.0).contains(&p[0]) && p[1].is_finite())
+            || sorted.windows(2).any(|w| w[1][0] <= w[0][0])
+        {
+            return None;
+        }
+
+        // Repeat the points one turn before and after, so the Catmull-Rom slopes and
+        // the evaluation wrap around the hue circle
+        let turn = |offset: f32| sorted.iter().map(move |p| [p[0] + offset, p[1]]);
+        let points: Vec<[f32; 2]> = turn(-360.0).chain(turn(0.0)).chain(turn(360.0)).collect();
+        let n = points.len();
+        let slopes = (0..n)
+            .map(|i| {
+                let (a, b) = (points[i.saturating_sub(1)], points[(i + 1).min(n - 1)]);
+                (b[1] - a[1]) / (b[0] - a[0])
+            })
+            .collect();
+        Some(Curve { points, slopes })
+    }
+
+    /// Evaluates the curve, holding the end values beyond the outer points.
+    fn eval(&self, x: f32) -> f32 {
+        let p = &self.points;
+        let last = p.len() - 1;
+        if x <= p[0][0] {
+            return p[0][1];
+        }
+        if x >= p[last][0] {
+            return p[last][1];
+        }
+        let i = p.partition_point(|q| q[0] <= x) - 1;
+        let (x0, y0, x1, y1) = (p[i][0], p[i][1], p[i + 1][0], p[i + 1][1]);
+        let h = x1 - x0;
+        let t = (x - x0) / h;
+        let (t2, t3) = (t * t, t * t * t);
+        (2.0 * t3 - 3.0 * t2 + 1.0) * y0
+            + (t3 - 2.0 * t2 + t) * h * self.slopes[i]
+            + (-2.0 * t3 + 3.0 * t2) * y1
+            + (t3 - t2) * h * self.slopes[i + 1]
+    }
+}
+
+/// Matrix adapting linear Rec.709 colors so neutrals take on the shifted white.
+///
+/// The temperature moves the white along the CIE daylight locus in mireds, the tint
+/// moves it perpendicular to the locus; both are zero for no change.
+fn white_balance(temperature: f64, tint: f64) -> [[f64; 3]; 3] {
+    let mired = 1e6 / NEUTRAL_CCT;
+    let to_uv = |[x, y]: [f64; 2]| {
+        let d = -2.0 * x + 12.0 * y + 3.0;
+        [4.0 * x / d, 6.0 * y / d]
+    };
+    let uv = to_uv(daylight(1e6 / (mired + temperature)));
+
+    // Unit normal of the locus pointing away from green (towards lower v)
+    let next = to_uv(daylight(1e6 / (mired + temperature + 1.0)));
+    let d = [next[0] - uv[0], next[1] - uv[1]];
+    let len = d[0].hypot(d[1]);
+    let mut normal = [-d[1] / len, d[0] / len];
+    if normal[1] > 0.0 {
+        normal = normal.map(|v| -v);
+    }
+    let [u, v] = [
+        uv[0] + normal[0] * tint * TINT_SCALE,
+        uv[1] + normal[1] * tint * TINT_SCALE,
+    ];
+    let d = 2.0 * u - 8.0 * v + 4.0;
+    let [x, y] = [3.0 * u / d, 2.0 * v / d];
+
+    let xyz = |[x, y]: [f64; 2]| [x / y, 1.0, (1.0 - x - y) / y];
+    let adapt = color_space::bradford(xyz(daylight(NEUTRAL_CCT)), xyz([x, y]));
+    let to_xyz = ColorSpace::LinearRec709.to_xyz();
+    let from_xyz = color_space::invert(&to_xyz);
+    color_space::mul(&from_xyz, &color_space::mul(&adapt, &to_xyz))
+}
+
+/// CIE xy chromaticity of the daylight illuminant with the given correlated color
+/// temperature (valid from about 4000 K to 25000 K, extrapolated beyond).
+fn daylight(cct: f64) -> [f64; 2] {
+    let (t, t2, t3) = (cct, cct * cct, cct * cct * cct);
+    let x = if cct <= 7000.0 {
+        -4.6070e9 / t3 + 2.9678e6 / t2 + 0.09911e3 / t + 0.244063
+    } else {
+        -2.0064e9 / t3 + 1.9018e6 / t2 + 0.24748e3 / t + 0.237040
+    };
+    [x, -3.0 * x * x + 2.870 * x - 0.275]
+}
+
+/// Converts an RGB color to hue in degrees, saturation, and value.
+fn rgb_to_hsv([r, g, b]: [f32; 3]) -> [f32; 3] {
+    let max = r.max(g).max(b);
+    let min = r.min(g).min(b);
+    let delta = max - min;
+    let h = if delta <= 0.0 {
+        0.0
+    } else if max == r {
+        60.0 * ((g - b) / delta).rem_euclid(6.0)
+    } else if max == g {
+        60.0 * ((b - r) / delta + 2.0)
+    } else {
+        60.0 * ((r - g) / delta + 4.0)
+    };
+    let s = if max > 0.0 { delta / max } else { 0.0 };
+    [h, s, max]
+}
+
+/// Converts hue in degrees, saturation, and value to an RGB color.
+fn hsv_to_rgb([h, s, v]: [f32; 3]) -> [f32; 3] {
+    let c = v * s;
+    let h = h / 60.0;
+    let x = c * (1.0 - (h.rem_euclid(2.0) - 1.0).abs());
+    let [r, g, b] = match h as u32 {
+        0 => [c, x, 0.0],
+        1 => [x, c, 0.0],
+        2 => [0.0, c, x],
+        3 => [0.0, x, c],
+        4 => [x, 0.0, c],
+        _ => [c, 0.0, x],
+    };
+    let m = v - c;
+    [r + m, g + m, b + m]
+}
+
+/// Creates an `InvalidInput` error.
+fn invalid(message: &str) -> Error {
+    Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_adjustments_bake_to_the_identity() {
+        let lut = bake(&Adjustments::default(), 9).unwrap();
+        for (actual, expected) in lut.data().iter().zip(Lut3D::identity(9).data()) {
+            for c in 0..3 {
+                assert!(
+                    (actual[c] - expected[c]).abs() < 1e-4,
+                    "{:?} != {:?}",
+                    actual,
+                    expected
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn one_stop_doubles_linear_mid_grey() {
+        let srgb = ColorSpace::Srgb;
+        let adjustments = Adjustments {
+            exposure: 1.0,
+            ..Adjustments::default()
+        };
+        let grey = srgb.from_linear(MID_GREY);
+        let out = adjustments.prepare().unwrap().apply([grey; 3]);
+        for v in out {
+            assert!(
+                (srgb.to_linear(v) - 2.0 * MID_GREY).abs() < 1e-4,
+                "{:?}",
+                out
+            );
+        }
+    }
+
+    #[test]
+    fn tone_curves_need_increasing_inputs() {
+        assert!(Curve::tone(&[[0.25, 0.2], [0.75, 0.8]]).is_some());
+        assert!(Curve::tone(&[[0.5, 0.4], [0.5, 0.6]]).is_none());
+        assert!(Curve::tone(&[[0.75, 0.8], [0.25, 0.2]]).is_none());
+        assert!(Curve::tone(&[[0.5, 1.2]]).is_none());
+
+        let adjustments = Adjustments {
+            green_curve: vec![[0.6, 0.5], [0.4, 0.5]],
+            ..Adjustments::default()
+        };
+        assert!(bake(&adjustments, 9).is_err());
+    }
+}
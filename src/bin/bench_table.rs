@@ -0,0 +1,181 @@
+use opsin::lut_batch::PixelBuffer;
+use opsin::lut_compact::CompactLut;
+use opsin::lut3d::{ApplyOptions, Interpolation, Lut3D};
+use rayon::prelude::*;
+use std::{env, time::Instant};
+
+/// Width and height of the synthetic test image (12 megapixels).
+const WIDTH: usize = 4000;
+const HEIGHT: usize = 3000;
+
+/// Width and height of the synthetic image graded as a whole buffer (14 megapixels).
+const BUFFER_WIDTH: usize = 4592;
+const BUFFER_HEIGHT: usize = 3056;
+
+/// Compact lattice sizes compared against the full table.
+const COMPACT_SIZES: [usize; 3] = [33, 65, 129];
+
+/// Compares the full 256³ precomputed table with compact tables of several sizes.
+///
+/// Usage: `bench_table [lut_path] [nearest|trilinear|tetrahedral]`
+///
+/// For each table this reports the build time, the size in memory, the
+/// single-threaded throughput on a synthetic 12 megapixel image, and the maximum
+/// and mean error in 8-bit code values against the full table over all 256³ inputs.
+/// It then compares [`Lut3D::apply_to_buffer`] with a per-pixel
+/// [`Lut3D::apply_precomputed`] loop on a 14 megapixel buffer, both on all cores.
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let args: Vec<String> = env::args().collect();
+    let lut_path = args
+        .get(1)
+        .map(String::as_str)
+        .unwrap_or("assets/luts/SONY_CYBERSHOT_DSC-WX5.CUBE");
+    let interpolation = match args.get(2).map(String::as_str) {
+        None => Interpolation::default(),
+        Some(name) => Interpolation::ALL
+            .into_iter()
+            .find(|m| m.name() == name)
+            .ok_or_else(|| format!("Unknown interpolation method: {}", name))?,
+    };
+    let options = ApplyOptions {
+        interpolation,
+        ..ApplyOptions::default()
+    };
+
+    let lut = Lut3D::load(lut_path)?;
+    println!(
+        "{} ({}-point lattice, {} interpolation)",
+        lut_path,
+        lut.size(),
+        interpolation.name()
+    );
+
+    // Deterministic pseudo-random pixels so runs are comparable
+    let noise = |len: usize| -> Vec<u8> {
+        let mut state = 0x2545_f491_4f6c_dd1du64;
+        (0..len)
+            .map(|_| {
+                state ^= state << 13;
+                state ^= state >> 7;
+                state ^= state << 17;
+                (state >> 56) as u8
+            })
+            .collect()
+    };
+    let pixels = noise(WIDTH * HEIGHT * 3);
+    let megapixels = (WIDTH * HEIGHT) as f64 / 1e6;
+    let throughput = |apply: &dyn Fn(u8, u8, u8) -> [u8; 3]| {
+        let mut buf = pixels.clone();
+        let start = Instant::now();
+        buf.chunks_mut(3).for_each(|px| {
+            let rgb = apply(px[0], px[1], px[2]);
+            px.copy_from_slice(&rgb);
+        });
+        megapixels / start.elapsed().as_secs_f64()
+    };
+
+    println!(
+        "{:<14} {:>10} {:>10} {:>12} {:>9} {:>9}",
+        "table", "build ms", "size MB", "Mpx/s (1T)", "max err", "mean err"
+    );
+
+    let start = Instant::now();
+    let full = lut.generate_map(&options, &|_| {});
+    let build_ms = start.elapsed().as_secs_f64() * 1e3;
+    let mpx = throughput(&|r, g, b| Lut3D::apply_precomputed(&full, r, g, b));
+    println!(
+        "{:<14} {:>10.0} {:>10.1} {:>12.1} {:>9} {:>9}",
+        "full 256³",
+        build_ms,
+        full.len() as f64 / (1024.0 * 1024.0),
+        mpx,
+        0,
+        "0.000"
+    );
+
+    for size in COMPACT_SIZES {
+        let start = Instant::now();
+        let compact = lut.bake_compact(size, &options)?;
+        let build_ms = start.elapsed().as_secs_f64() * 1e3;
+        let mpx = throughput(&|r, g, b| compact.apply(r, g, b));
+
+        // Compare every 8-bit input against the full table
+        let (max_err, total_err) = full
+            .par_chunks(256 * 256 * 3)
+            .enumerate()
+            .map(|(r, slab)| {
+                let mut max_err = 0u8;
+                let mut total_err = 0u64;
+                for (i, expected) in slab.chunks_exact(3).enumerate() {
+                    let rgb = compact.apply(r as u8, (i >> 8) as u8, (i & 0xff) as u8);
+                    for c in 0..3 {
+                        let err = rgb[c].abs_diff(expected[c]);
+                        max_err = max_err.max(err);
+                        total_err += err as u64;
+                    }
+                }
+                (max_err, total_err)
+            })
+            .reduce(|| (0, 0), |a, b| (a.0.max(b.0), a.1 + b.1));
+
+        println!(
+            "{:<14} {:>10.0} {:>10.1} {:>12.1} {:>9} {:>9.3}",
+            format!("compact {}³", size),
+            build_ms,
+            CompactLut::byte_len(size) as f64 / (1024.0 * 1024.0),
+            mpx,
+            max_err,
+            total_err as f64 / full.len() as f64
+        );
+    }
+
+    let mpx = throughput(&|r, g, b| lut.apply_with(r, g, b, &options));
+    println!(
+        "{:<14} {:>10} {:>10} {:>12.1} {:>9} {:>9}",
+        "direct", "-", "-", mpx, 0, "0.000"
+    );
+
+    // Grade a whole buffer on all cores, through the full table one pixel at a time
+    // and by interpolating the LUT eight pixels at a time
+    let image = noise(BUFFER_WIDTH * BUFFER_HEIGHT * 3);
+    let megapixels = (BUFFER_WIDTH * BUFFER_HEIGHT) as f64 / 1e6;
+    println!();
+    println!("{:<26} {:>10} {:>9}", "14 Mpx buffer", "ms", "Mpx/s");
+
+    let mut per_pixel = image.clone();
+    let start = Instant::now();
+    per_pixel.par_chunks_mut(3).for_each(|px| {
+        let rgb = Lut3D::apply_precomputed(&full, px[0], px[1], px[2]);
+        px.copy_from_slice(&rgb);
+    });
+    let elapsed = start.elapsed().as_secs_f64();
+    println!(
+        "{:<26} {:>10.1} {:>9.1}",
+        "apply_precomputed loop",
+        elapsed * 1e3,
+        megapixels / elapsed
+    );
+
+    let mut batched = image;
+    let start = Instant::now();
+    lut.apply_to_buffer(PixelBuffer::Rgb8(&mut batched), &options);
+    let elapsed = start.elapsed().as_secs_f64();
+    println!(
+        "{:<26} {:>10.1} {:>9.1}",
+        "apply_to_buffer",
+        elapsed * 1e3,
+        megapixels / elapsed
+    );
+
+    let mismatches = per_pixel
+        .chunks_exact(3)
+        .zip(batched.chunks_exact(3))
+        .filter(|(a, b)| a != b)
+        .count();
+    println!(
+        "{} of {} pixels differ",
+        mismatches,
+        BUFFER_WIDTH * BUFFER_HEIGHT
+    );
+    Ok(())
+}
@@ -1,16 +1,17 @@
 use std::path::Path;
 use image::RgbImage;
 use rayon::prelude::*;
-use opsin::lut3d::Lut3D;
+use opsin::lut3d::{ApplyOptions, Lut3D};
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Load LUT and precompute table (or load existing)
     let lut = Lut3D::from_cube("assets/luts/SONY_CYBERSHOT_DSC-WX5.CUBE")?;
-    let table = lut.load_or_generate_map("assets/luts/lut_precomputed.bin")?;
+    let table =
+        lut.load_or_generate_map("assets/luts/lut_precomputed.bin", &ApplyOptions::default())?;
 
     // Load input image and get raw pixel buffer
     let input_path = Path::new("testing images/DSC01067.JPG");
-    let img = image::open(&input_path)?.to_rgb8();
+    let img = image::open(input_path)?.to_rgb8();
     let (w, h) = img.dimensions();
     let mut raw = img.into_raw();
 
@@ -0,0 +1,341 @@
+use crate::lut_error::LutError;
+use crate::lut3d::{Interpolation, Lut3D};
+use roxmltree::{Document, Node};
+use serde::Deserialize;
+use std::{fs, io::Error};
+
+/// File extensions of ASC CDL files: single decisions, corrections, and collections.
+pub const CDL_EXTENSIONS: [&str; 3] = ["cdl", "cc", "ccc"];
+
+/// Rec.709 luma weights used by the CDL saturation operator.
+const LUMA: [f32; 3] = [0.2126, 0.7152, 0.0722];
+
+/// An ASC Color Decision List correction: slope, offset, and power per channel,
+/// followed by a global saturation.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cdl {
+    /// The correction's `id` attribute, if it has one
+    pub id: Option<String>,
+    /// Per-channel multiplier
+    pub slope: [f32; 3],
+    /// Per-channel value added after the slope
+    pub offset: [f32; 3],
+    /// Per-channel exponent applied after the offset
+    pub power: [f32; 3],
+    /// Saturation around Rec.709 luma (1 leaves colors unchanged)
+    pub saturation: f32,
+}
+
+impl Default for Cdl {
+    /// The identity correction.
+    fn default() -> Self {
+        Cdl {
+            id: None,
+            slope: [1.0; 3],
+            offset: [0.0; 3],
+            power: [1.0; 3],
+            saturation: 1.0,
+        }
+    }
+}
+
+/// Where a CDL is applied relative to the LUT.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CdlPlacement {
+    /// Grade the source colors, then apply the LUT
+    #[default]
+    Before,
+    /// Apply the LUT, then grade its output
+    After,
+}
+
+impl CdlPlacement {
+    /// All placements, in the order they are offered in the GUI.
+    pub const ALL: [CdlPlacement; 2] = [CdlPlacement::Before, CdlPlacement::After];
+
+    /// Short lowercase name used in configuration files.
+    pub fn name(self) -> &'static str {
+        match self {
+            CdlPlacement::Before => "before",
+            CdlPlacement::After => "after",
+        }
+    }
+}
+
+impl Cdl {
+    /// Parses every color correction in a `.cdl`, `.cc`, or `.ccc` document.
+    ///
+    /// Corrections are found wherever they are nested, so all three file types (and
+    /// any XML namespace version) are handled alike. Missing `SOPNode` or `SatNode`
+    /// elements leave the corresponding values at the identity.
+    ///
+    /// # Arguments
+    /// * `text` - The XML document
+    ///
+    /// # Returns
+    /// A `Result` containing the corrections in document order
+    ///
+    /// # Errors
+    /// Returns an error if the XML is malformed, a value isn't a number, a triple
+    /// doesn't hold 3 values, or the document contains no correction
+    pub fn parse(text: &str) -> Result<Vec<Cdl>, LutError> {
+        let doc = Document::parse(text)?;
+        let corrections = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("ColorCorrection"))
+            .map(|node| Self::from_node(&doc, node))
+            .collect::<Result<Vec<_>, _>>()?;
+        if corrections.is_empty() {
+            return Err(LutError::Invalid(
+                "The file contains no ColorCorrection.".to_string(),
+            ));
+        }
+        Ok(corrections)
+    }
+
+    /// Loads a CDL file and picks one of its corrections.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the `.cdl`, `.cc`, or `.ccc` file
+    /// * `id` - The `id` of the correction to use, or `None` for the first one
+    ///
+    /// # Returns
+    /// A `Result` containing the selected correction
+    ///
+    /// # Errors
+    /// Returns an error if the file can't be read or parsed, or has no correction
+    /// with the requested `id`
+    pub fn load(path: &str, id: Option<&str>) -> Result<Cdl, LutError> {
+        let text = fs::read_to_string(path)?;
+        let mut corrections = Self::parse(&text)?;
+        match id {
+            None => Ok(corrections.swap_remove(0)),
+            Some(id) => corrections
+                .into_iter()
+                .find(|c| c.id.as_deref() == Some(id))
+                .ok_or_else(|| {
+                    LutError::Invalid(format!("No ColorCorrection with id '{}' in {}", id, path))
+                }),
+        }
+    }
+
+    /// Reads a `ColorCorrection` element.
+    fn from_node(doc: &Document, node: Node) -> Result<Cdl, LutError> {
+        let mut cdl = Cdl {
+            id: node.attribute("id").map(str::to_string),
+            ..Cdl::default()
+        };
+        for child in node.descendants().filter(Node::is_element) {
+            match child.tag_name().name() {
+                "Slope" => cdl.slope = read_values(doc, child)?,
+                "Offset" => cdl.offset = read_values(doc, child)?,
+                "Power" => cdl.power = read_values(doc, child)?,
+                "Saturation" => cdl.saturation = read_values::<1>(doc, child)?[0],
+                _ => {}
+            }
+        }
+        Ok(cdl)
+    }
+
+    /// Applies the correction to an RGB color.
+    ///
+    /// Follows the ASC CDL v1.2 equations: `(in · slope + offset) ^ power`, then
+    /// saturation; results of both steps are clamped to [0, 1].
+    pub fn apply(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let sop: [f32; 3] = std::array::from_fn(|c| {
+            (rgb[c] * self.slope[c] + self.offset[c])
+                .clamp(0.0, 1.0)
+                .powf(self.power[c])
+        });
+        let luma = sop[0] * LUMA[0] + sop[1] * LUMA[1] + sop[2] * LUMA[2];
+        sop.map(|v| (luma + self.saturation * (v - luma)).clamp(0.0, 1.0))
+    }
+}
+
+/// Reads the whitespace-separated numbers of an element's text.
+fn read_values<const N: usize>(doc: &Document, node: Node) -> Result<[f32; N], LutError> {
+    let pos = doc.text_pos_at(node.range().start);
+    let (line, column) = (pos.row as usize, pos.col as usize);
+    let values = node
+        .text()
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(|v| {
+            v.parse::<f32>().map_err(|_| LutError::InvalidValue {
+                line,
+                column,
+                value: v.to_string(),
+            })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    values
+        .try_into()
+        .map_err(|v: Vec<f32>| LutError::MalformedLine {
+            line,
+            column,
+            message: format!(
+                "{} expects {} values, found {}",
+                node.tag_name().name(),
+                N,
+                v.len()
+            ),
+        })
+}
+
+/// Bakes a CDL and a LUT into a single LUT.
+///
+/// The result can be saved as a cube or precomputed like any other LUT.
+///
+/// # Arguments
+/// * `lut` - The LUT to combine with the CDL
+/// * `cdl` - The correction to bake
+/// * `placement` - Whether the CDL grades the LUT's input or its output
+/// * `size` - The size of each dimension of the baked lattice (e.g. 33)
+/// * `interpolation` - Interpolation used to sample the LUT
+///
+/// # Returns
+/// A `Result` containing the baked `Lut3D`
+///
+/// # Errors
+/// Returns an error if `size` is smaller than 2
+pub fn bake(
+    lut: &Lut3D,
+    cdl: &Cdl,
+    placement: CdlPlacement,
+    size: usize,
+    interpolation: Interpolation,
+) -> Result<Lut3D, Error> {
+    let mut baked = Lut3D::bake(size, |rgb| match placement {
+        CdlPlacement::Before => lut.sample(cdl.apply(rgb), interpolation),
+        CdlPlacement::After => cdl.apply(lut.sample(rgb, interpolation)),
+    })?;
+    baked.set_out_of_domain(lut.out_of_domain());
+    if let Some(title) = lut.title() {
+        baked.set_title(title);
+    }
+    baked.add_comment(format!(
+        " ASC CDL {} applied {} the LUT at size {} ({})",
+        cdl.id.as_deref().unwrap_or("(no id)"),
+        placement.name(),
+        size,
+        interpolation.name()
+    ));
+    baked.add_comment(format!(
+        " slope {:?} offset {:?} power {:?} saturation {}",
+        cdl.slope, cdl.offset, cdl.power, cdl.saturation
+    ));
+    Ok(baked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A correction with distinct slope, offset and power per channel.
+    const GRADE: &str = r#"
+        <SOPNode>
+            <Slope>1.2 1.0 0.8</Slope>
+            <Offset>0.1 0.0 -0.05</Offset>
+            <Power>1.0 2.0 0.5</Power>
+        </SOPNode>"#;
+
+    fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+        for c in 0..3 {
+            assert!(
+                (actual[c] - expected[c]).abs() < 1e-5,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn parses_cc_and_ccc_files() {
+        let cc = format!(
+            r#"<?xml version="1.0" encoding="UTF-8"?>
+            <ColorCorrection id="shot_010" xmlns="urn:ASC:CDL:v1.2">{}
+                <SatNode><Saturation>0.5</Saturation></SatNode>
+            </ColorCorrection>"#,
+            GRADE
+        );
+        let parsed = Cdl::parse(&cc).unwrap();
+        assert_eq!(
+            parsed,
+            [Cdl {
+                id: Some("shot_010".to_string()),
+                slope: [1.2, 1.0, 0.8],
+                offset: [0.1, 0.0, -0.05],
+                power: [1.0, 2.0, 0.5],
+                saturation: 0.5,
+            }]
+        );
+
+        let ccc = format!(
+            r#"<ColorCorrectionCollection xmlns="urn:ASC:CDL:v1.01">
+                <ColorCorrection id="a">{}</ColorCorrection>
+                <ColorCorrection id="b">
+                    <SatNode><Saturation>1.5</Saturation></SatNode>
+                </ColorCorrection>
+                <ColorCorrection><SOPNode><Slope>2 2 2</Slope></SOPNode></ColorCorrection>
+            </ColorCorrectionCollection>"#,
+            GRADE
+        );
+        let parsed = Cdl::parse(&ccc).unwrap();
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(parsed[0].id.as_deref(), Some("a"));
+        assert_eq!(parsed[0].power, [1.0, 2.0, 0.5]);
+        assert_eq!(parsed[0].saturation, 1.0);
+        assert_eq!(
+            parsed[1],
+            Cdl {
+                id: Some("b".to_string()),
+                saturation: 1.5,
+                ..Cdl::default()
+            }
+        );
+        assert_eq!(parsed[2].id, None);
+        assert_eq!(parsed[2].slope, [2.0; 3]);
+        assert_eq!(parsed[2].offset, [0.0; 3]);
+    }
+
+    #[test]
+    fn applies_sop_and_saturation_per_asc_v1_2() {
+        let cc = format!("<ColorCorrection>{}</ColorCorrection>", GRADE);
+        let mut cdl = Cdl::parse(&cc).unwrap().remove(0);
+        cdl.saturation = 0.5;
+        assert_close(
+            cdl.apply([0.5, 0.5, 0.5]),
+            [0.535_167, 0.310_167, 0.480_971],
+        );
+
+        // Out-of-range SOP results and saturated colors are clamped
+        cdl.saturation = 1.3;
+        assert_close(cdl.apply([0.9, 0.6, 0.05]), [1.0, 0.326_978, 0.0]);
+        assert_eq!(Cdl::default().apply([0.2, 0.4, 0.6]), [0.2, 0.4, 0.6]);
+    }
+
+    #[test]
+    fn rejects_malformed_slopes() {
+        let cc = |slope: &str| {
+            format!(
+                "<ColorCorrection>\n<SOPNode>\n<Slope>{}</Slope>\n</SOPNode>\n</ColorCorrection>",
+                slope
+            )
+        };
+        assert!(matches!(
+            Cdl::parse(&cc("1.0 one 1.0")),
+            Err(LutError::InvalidValue { line: 3, ref value, .. }) if value == "one"
+        ));
+        assert!(matches!(
+            Cdl::parse(&cc("1.0 1.0")),
+            Err(LutError::MalformedLine { line: 3, .. })
+        ));
+        assert!(matches!(
+            Cdl::parse(&cc("1.0 1.0 1.0 1.0")),
+            Err(LutError::MalformedLine { line: 3, .. })
+        ));
+        assert!(Cdl::parse("<ColorDecisionList />").is_err());
+    }
+}
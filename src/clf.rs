@@ -0,0 +1,834 @@
+use crate::lut_error::LutError;
+use crate::lut1d::Lut1D;
+use crate::lut3d::{Interpolation, Lut3D};
+use roxmltree::{Document, Node};
+use std::{fs, io::Error};
+
+/// Smallest lattice size used when a CLF is baked into a [`Lut3D`] on load.
+pub const MIN_BAKE_SIZE: usize = 33;
+
+/// A transform read from an Academy/ASC Common LUT Format (`.clf`) file.
+///
+/// The file's process nodes are kept as a chain and evaluated in order; values are
+/// normalized to [0, 1] at each node's declared bit depths while parsing, so nodes
+/// with integer and float depths can be mixed.
+pub struct Clf {
+    /// The `name` attribute of the process list
+    name: Option<String>,
+    /// `Description` elements of the process list
+    descriptions: Vec<String>,
+    /// Process nodes, applied first to last
+    nodes: Vec<ProcessNode>,
+}
+
+/// A single CLF process node with its values normalized.
+enum ProcessNode {
+    /// Per-channel curve
+    Lut1D(Lut1D),
+    /// 3D lattice sampled with the node's interpolation
+    Lut3D(Lut3D, Interpolation),
+    /// 3x3 matrix with an offset column
+    Matrix([[f32; 4]; 3]),
+    /// Linear remapping with optional clamping
+    Range(Range),
+    /// Logarithmic or exponential curve
+    Log(Log),
+}
+
+/// Parameters of a `Range` node, normalized to [0, 1].
+struct Range {
+    min_in: Option<f32>,
+    max_in: Option<f32>,
+    min_out: Option<f32>,
+    max_out: Option<f32>,
+    /// Whether results are clamped to the output range (`style="Clamp"`)
+    clamp: bool,
+}
+
+/// A `Log` node.
+struct Log {
+    style: LogStyle,
+    /// Parameters per channel
+    params: [LogParams; 3],
+}
+
+/// The `style` of a `Log` node.
+#[derive(Clone, Copy, PartialEq)]
+enum LogStyle {
+    Log10,
+    AntiLog10,
+    Log2,
+    AntiLog2,
+    LinToLog,
+    LogToLin,
+    CameraLinToLog,
+    CameraLogToLin,
+}
+
+/// `LogParams` of one channel of a `Log` node.
+#[derive(Clone, Copy)]
+struct LogParams {
+    base: f32,
+    log_side_slope: f32,
+    log_side_offset: f32,
+    lin_side_slope: f32,
+    lin_side_offset: f32,
+    /// Linear value below which camera styles use a straight segment
+    lin_side_break: Option<f32>,
+    /// Slope of the straight segment; derived for continuity if absent
+    linear_slope: Option<f32>,
+}
+
+impl Default for LogParams {
+    fn default() -> Self {
+        LogParams {
+            base: 2.0,
+            log_side_slope: 1.0,
+            log_side_offset: 0.0,
+            lin_side_slope: 1.0,
+            lin_side_offset: 0.0,
+            lin_side_break: None,
+            linear_slope: None,
+        }
+    }
+}
+
+impl LogParams {
+    /// The log side of the pure logarithmic segment.
+    fn lin_to_log(&self, x: f32) -> f32 {
+        let lin = (self.lin_side_slope * x + self.lin_side_offset).max(f32::MIN_POSITIVE);
+        self.log_side_slope * lin.log(self.base) + self.log_side_offset
+    }
+
+    /// The inverse of [`Self::lin_to_log`].
+    fn log_to_lin(&self, y: f32) -> f32 {
+        let lin = self
+            .base
+            .powf((y - self.log_side_offset) / self.log_side_slope);
+        (lin - self.lin_side_offset) / self.lin_side_slope
+    }
+
+    /// Slope and offset of the straight segment below the break of camera styles, and
+    /// the log value at the break.
+    fn camera_segment(&self, lin_break: f32) -> (f32, f32, f32) {
+        let log_break = self.lin_to_log(lin_break);
+        let slope = self.linear_slope.unwrap_or_else(|| {
+            self.log_side_slope * self.lin_side_slope
+                / ((self.lin_side_slope * lin_break + self.lin_side_offset) * self.base.ln())
+        });
+        (slope, log_break - slope * lin_break, log_break)
+    }
+}
+
+impl Log {
+    /// Applies the curve to one channel.
+    ///
+    /// The curve is evaluated on normalized values whatever the node's bit depths,
+    /// as CLF specifies for `Log` nodes.
+    fn apply(&self, c: usize, x: f32) -> f32 {
+        let p = &self.params[c];
+        match self.style {
+            LogStyle::Log10 => x.max(f32::MIN_POSITIVE).log10(),
+            LogStyle::AntiLog10 => 10f32.powf(x),
+            LogStyle::Log2 => x.max(f32::MIN_POSITIVE).log2(),
+            LogStyle::AntiLog2 => 2f32.powf(x),
+            LogStyle::LinToLog => p.lin_to_log(x),
+            LogStyle::LogToLin => p.log_to_lin(x),
+            LogStyle::CameraLinToLog => match p.lin_side_break {
+                Some(lin_break) if x <= lin_break => {
+                    let (slope, offset, _) = p.camera_segment(lin_break);
+                    slope * x + offset
+                }
+                _ => p.lin_to_log(x),
+            },
+            LogStyle::CameraLogToLin => match p.lin_side_break {
+                Some(lin_break) => {
+                    let (slope, offset, log_break) = p.camera_segment(lin_break);
+                    if x <= log_break {
+                        (x - offset) / slope
+                    } else {
+                        p.log_to_lin(x)
+                    }
+                }
+                None => p.log_to_lin(x),
+            },
+        }
+    }
+}
+
+impl Range {
+    /// Remaps one channel.
+    fn apply(&self, x: f32) -> f32 {
+        match (self.min_in, self.max_in, self.min_out, self.max_out) {
+            (Some(min_in), Some(max_in), Some(min_out), Some(max_out)) => {
+                let y = (x - min_in) * (max_out - min_out) / (max_in - min_in) + min_out;
+                if self.clamp {
+                    y.clamp(min_out.min(max_out), max_out.max(min_out))
+                } else {
+                    y
+                }
+            }
+            (Some(min_in), _, Some(min_out), _) => (x - min_in + min_out).max(min_out),
+            (_, Some(max_in), _, Some(max_out)) => (x - max_in + max_out).min(max_out),
+            _ => x,
+        }
+    }
+}
+
+impl ProcessNode {
+    /// Applies the node to a normalized RGB color.
+    fn apply(&self, rgb: [f32; 3]) -> [f32; 3] {
+        match self {
+            ProcessNode::Lut1D(lut) => lut.apply(rgb),
+            ProcessNode::Lut3D(lut, interpolation) => lut.sample(rgb, *interpolation),
+            ProcessNode::Matrix(m) => std::array::from_fn(|i| {
+                m[i][0] * rgb[0] + m[i][1] * rgb[1] + m[i][2] * rgb[2] + m[i][3]
+            }),
+            ProcessNode::Range(range) => rgb.map(|v| range.apply(v)),
+            ProcessNode::Log(log) => std::array::from_fn(|c| log.apply(c, rgb[c])),
+        }
+    }
+}
+
+impl Clf {
+    /// Loads a `.clf` file.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the CLF file
+    ///
+    /// # Returns
+    /// A `Result` containing the parsed transform or a `LutError` if loading fails
+    pub fn load(path: &str) -> Result<Self, LutError> {
+        let text = fs::read_to_string(path)?;
+        Self::parse(&text)
+    }
+
+    /// Parses a CLF document.
+    ///
+    /// `LUT1D`, `LUT3D`, `Matrix`, `Range`, and `Log` process nodes are supported,
+    /// in both the v2 and v3 layouts of their arrays.
+    ///
+    /// # Arguments
+    /// * `text` - The XML document
+    ///
+    /// # Returns
+    /// A `Result` containing the parsed transform
+    ///
+    /// # Errors
+    /// Returns an error if:
+    /// - The XML is malformed or its root isn't a `ProcessList`
+    /// - The list contains another kind of process node, or none at all
+    /// - A node has missing or malformed attributes, values, or arrays
+    /// - A LUT uses a half-float domain or an index map
+    pub fn parse(text: &str) -> Result<Self, LutError> {
+        let doc = Document::parse(text)?;
+        let root = doc.root_element();
+        if !root.has_tag_name("ProcessList") {
+            return Err(LutError::Invalid(format!(
+                "Not a CLF file: the root element is <{}>, expected <ProcessList>",
+                root.tag_name().name()
+            )));
+        }
+
+        let mut clf = Clf {
+            name: root.attribute("name").map(str::to_string),
+            descriptions: Vec::new(),
+            nodes: Vec::new(),
+        };
+        for node in root.children().filter(Node::is_element) {
+            let name = node.tag_name().name();
+            let process_node = match name {
+                "Description" => {
+                    clf.descriptions
+                        .push(node.text().unwrap_or_default().trim().to_string());
+                    continue;
+                }
+                "InputDescriptor" | "OutputDescriptor" | "Info" => continue,
+                "LUT1D" => read_lut1d(&doc, node)?,
+                "LUT3D" => read_lut3d(&doc, node)?,
+                "Matrix" => read_matrix(&doc, node)?,
+                "Range" => read_range(&doc, node)?,
+                "Log" => read_log(&doc, node)?,
+                _ => {
+                    return Err(node_error(
+                        &doc,
+                        node,
+                        format!("unsupported CLF process node <{}>", name),
+                    ));
+                }
+            };
+            clf.nodes.push(process_node);
+        }
+        if clf.nodes.is_empty() {
+            return Err(LutError::Invalid(
+                "The CLF file contains no process nodes.".to_string(),
+            ));
+        }
+        Ok(clf)
+    }
+
+    /// The `name` attribute of the process list, if it has one.
+    pub fn name(&self) -> Option<&str> {
+        self.name.as_deref()
+    }
+
+    /// Applies the transform chain to an RGB color.
+    ///
+    /// # Arguments
+    /// * `rgb` - Input RGB values, normalized to [0, 1] at the first node's input depth
+    ///
+    /// # Returns
+    /// The transformed RGB values, normalized at the last node's output depth (not clamped)
+    pub fn apply(&self, rgb: [f32; 3]) -> [f32; 3] {
+        self.nodes.iter().fold(rgb, |rgb, node| node.apply(rgb))
+    }
+
+    /// A lattice size that preserves the detail of the transform when baking: the
+    /// size of its largest `LUT3D`, but at least [`MIN_BAKE_SIZE`].
+    pub fn bake_size(&self) -> usize {
+        self.nodes
+            .iter()
+            .filter_map(|node| match node {
+                ProcessNode::Lut3D(lut, _) => Some(lut.size()),
+                _ => None,
+            })
+            .fold(MIN_BAKE_SIZE, usize::max)
+    }
+
+    /// Bakes the transform chain into a 3D LUT over the [0, 1] input range.
+    ///
+    /// # Arguments
+    /// * `size` - The size of each dimension of the baked lattice (e.g. 33)
+    ///
+    /// # Returns
+    /// A `Result` containing the baked `Lut3D`, titled with the process list name
+    ///
+    /// # Errors
+    /// Returns an error if `size` is smaller than 2
+    pub fn bake(&self, size: usize) -> Result<Lut3D, Error> {
+        let mut lut = Lut3D::bake(size, |rgb| self.apply(rgb))?;
+        if let Some(name) = &self.name {
+            lut.set_title(name);
+        }
+        for description in &self.descriptions {
+            lut.add_comment(format!(" {}", description));
+        }
+        lut.add_comment(format!(
+            " Baked from {} CLF process nodes at size {}",
+            self.nodes.len(),
+            size
+        ));
+        Ok(lut)
+    }
+}
+
+/// Loads a `.clf` file and bakes it into a 3D LUT at [`Clf::bake_size`].
+///
+/// # Arguments
+/// * `path` - Path to the CLF file
+///
+/// # Returns
+/// A `Result` containing the baked `Lut3D` or a `LutError` if loading fails
+pub fn load_lut(path: &str) -> Result<Lut3D, LutError> {
+    let clf = Clf::load(path)?;
+    Ok(clf.bake(clf.bake_size())?)
+}
+
+/// Scale of a CLF bit depth: the largest code value of integer depths, 1 for floats.
+fn bit_depth_scale(doc: &Document, node: Node, attribute: &str) -> Result<f32, LutError> {
+    match node.attribute(attribute) {
+        Some("8i") => Ok(255.0),
+        Some("10i") => Ok(1023.0),
+        Some("12i") => Ok(4095.0),
+        Some("16i") => Ok(65535.0),
+        Some("16f" | "32f") => Ok(1.0),
+        Some(depth) => Err(node_error(
+            doc,
+            node,
+            format!("unknown {} '{}'", attribute, depth),
+        )),
+        None => Err(node_error(
+            doc,
+            node,
+            format!("<{}> has no {}", node.tag_name().name(), attribute),
+        )),
+    }
+}
+
+/// Reads the `Array` child of a node: its dimensions and values.
+fn read_array(doc: &Document, node: Node) -> Result<(Vec<usize>, Vec<f32>), LutError> {
+    let array = node
+        .children()
+        .find(|n| n.has_tag_name("Array"))
+        .ok_or_else(|| {
+            node_error(
+                doc,
+                node,
+                format!("<{}> has no Array", node.tag_name().name()),
+            )
+        })?;
+    let dims = array
+        .attribute("dim")
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(|d| d.parse::<usize>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|_| node_error(doc, array, "invalid Array dim".to_string()))?;
+    let values = read_numbers(doc, array)?;
+    // v2 matrices append a component count to the dimensions that isn't part of the
+    // number of values ("3 3 3" holds 9 coefficients)
+    let counted = if node.has_tag_name("Matrix") {
+        2
+    } else {
+        dims.len()
+    };
+    let expected: usize = dims.iter().take(counted).product();
+    if dims.is_empty() || values.len() != expected {
+        return Err(LutError::SizeMismatch {
+            expected,
+            found: values.len(),
+        });
+    }
+    Ok((dims, values))
+}
+
+/// Reads the whitespace-separated numbers of an element's text.
+fn read_numbers(doc: &Document, node: Node) -> Result<Vec<f32>, LutError> {
+    let pos = doc.text_pos_at(node.range().start);
+    node.text()
+        .unwrap_or_default()
+        .split_whitespace()
+        .map(|v| {
+            v.parse::<f32>().map_err(|_| LutError::InvalidValue {
+                line: pos.row as usize,
+                column: pos.col as usize,
+                value: v.to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Reads the single number held by the child element `name`, if present.
+fn read_child_value(doc: &Document, node: Node, name: &str) -> Result<Option<f32>, LutError> {
+    let Some(child) = node.children().find(|n| n.has_tag_name(name)) else {
+        return Ok(None);
+    };
+    match read_numbers(doc, child)?.as_slice() {
+        [v] => Ok(Some(*v)),
+        _ => Err(node_error(doc, child, format!("{} expects 1 value", name))),
+    }
+}
+
+/// Reads a `LUT1D` node.
+fn read_lut1d(doc: &Document, node: Node) -> Result<ProcessNode, LutError> {
+    if node.attribute("halfDomain").is_some() || node.attribute("rawHalfs").is_some() {
+        return Err(node_error(
+            doc,
+            node,
+            "half-float LUT1D domains are not supported".to_string(),
+        ));
+    }
+    if node.children().any(|n| n.has_tag_name("IndexMap")) {
+        return Err(node_error(
+            doc,
+            node,
+            "LUT1D index maps are not supported".to_string(),
+        ));
+    }
+    let out_scale = bit_depth_scale(doc, node, "outBitDepth")?;
+    let (dims, values) = read_array(doc, node)?;
+    let data: Vec<[f32; 3]> = match dims.as_slice() {
+        [n, 1] if *n >= 2 => values.iter().map(|&v| [v / out_scale; 3]).collect(),
+        [n, 3] if *n >= 2 => values
+            .chunks_exact(3)
+            .map(|v| [v[0] / out_scale, v[1] / out_scale, v[2] / out_scale])
+            .collect(),
+        _ => {
+            return Err(node_error(
+                doc,
+                node,
+                "LUT1D Array dim must be 'N 1' or 'N 3'".to_string(),
+            ));
+        }
+    };
+    Ok(ProcessNode::Lut1D(Lut1D::new(data, [0.0; 3], [1.0; 3])))
+}
+
+/// Reads a `LUT3D` node.
+fn read_lut3d(doc: &Document, node: Node) -> Result<ProcessNode, LutError> {
+    let out_scale = bit_depth_scale(doc, node, "outBitDepth")?;
+    let interpolation = match node.attribute("interpolation") {
+        None | Some("trilinear") => Interpolation::Trilinear,
+        Some("tetrahedral") => Interpolation::Tetrahedral,
+        Some(other) => {
+            return Err(node_error(
+                doc,
+                node,
+                format!("unknown LUT3D interpolation '{}'", other),
+            ));
+        }
+    };
+    let (dims, values) = read_array(doc, node)?;
+    let size = match dims.as_slice() {
+        [r, g, b, 3] if r == g && g == b && *r >= 2 => *r,
+        _ => {
+            return Err(node_error(
+                doc,
+                node,
+                "LUT3D Array dim must be 'N N N 3'".to_string(),
+            ));
+        }
+    };
+
+    // Reorder from blue-fastest (CLF) to red-fastest layout
+    let mut data = vec![[0.0f32; 3]; size * size * size];
+    for (i, v) in values.chunks_exact(3).enumerate() {
+        let b = i % size;
+        let g = (i / size) % size;
+        let r = i / (size * size);
+        data[r + g * size + b * size * size] =
+            [v[0] / out_scale, v[1] / out_scale, v[2] / out_scale];
+    }
+    Ok(ProcessNode::Lut3D(
+        Lut3D::from_data(size, data)?,
+        interpolation,
+    ))
+}
+
+/// Reads a `Matrix` node.
+fn read_matrix(doc: &Document, node: Node) -> Result<ProcessNode, LutError> {
+    let in_scale = bit_depth_scale(doc, node, "inBitDepth")?;
+    let out_scale = bit_depth_scale(doc, node, "outBitDepth")?;
+    let (dims, values) = read_array(doc, node)?;
+    // v3 writes "3 3" or "3 4"; v2 appended the component count ("3 3 3", "3 4 3")
+    let columns = match dims.as_slice() {
+        [3, c] | [3, c, 3] if *c == 3 || *c == 4 => *c,
+        _ => {
+            return Err(node_error(
+                doc,
+                node,
+                "Matrix Array dim must be '3 3' or '3 4'".to_string(),
+            ));
+        }
+    };
+    let m = std::array::from_fn(|i| {
+        let row = &values[i * columns..(i + 1) * columns];
+        let offset = row.get(3).map_or(0.0, |o| o / out_scale);
+        [
+            row[0] * in_scale / out_scale,
+            row[1] * in_scale / out_scale,
+            row[2] * in_scale / out_scale,
+            offset,
+        ]
+    });
+    Ok(ProcessNode::Matrix(m))
+}
+
+/// Reads a `Range` node.
+fn read_range(doc: &Document, node: Node) -> Result<ProcessNode, LutError> {
+    let in_scale = bit_depth_scale(doc, node, "inBitDepth")?;
+    let out_scale = bit_depth_scale(doc, node, "outBitDepth")?;
+    let clamp = match node.attribute("style") {
+        None | Some("Clamp") => true,
+        Some("noClamp") => false,
+        Some(other) => {
+            return Err(node_error(
+                doc,
+                node,
+                format!("unknown Range style '{}'", other),
+            ));
+        }
+    };
+    let range = Range {
+        min_in: read_child_value(doc, node, "minInValue")?.map(|v| v / in_scale),
+        max_in: read_child_value(doc, node, "maxInValue")?.map(|v| v / in_scale),
+        min_out: read_child_value(doc, node, "minOutValue")?.map(|v| v / out_scale),
+        max_out: read_child_value(doc, node, "maxOutValue")?.map(|v| v / out_scale),
+        clamp,
+    };
+    let has_min = range.min_in.is_some() && range.min_out.is_some();
+    let has_max = range.max_in.is_some() && range.max_out.is_some();
+    if !has_min && !has_max {
+        return Err(node_error(
+            doc,
+            node,
+            "Range needs minimum or maximum input and output values".to_string(),
+        ));
+    }
+    Ok(ProcessNode::Range(range))
+}
+
+/// Reads a `Log` node.
+fn read_log(doc: &Document, node: Node) -> Result<ProcessNode, LutError> {
+    let style = match node.attribute("style") {
+        Some("log10") => LogStyle::Log10,
+        Some("antiLog10") => LogStyle::AntiLog10,
+        Some("log2") => LogStyle::Log2,
+        Some("antiLog2") => LogStyle::AntiLog2,
+        Some("linToLog") => LogStyle::LinToLog,
+        Some("logToLin") => LogStyle::LogToLin,
+        Some("cameraLinToLog") => LogStyle::CameraLinToLog,
+        Some("cameraLogToLin") => LogStyle::CameraLogToLin,
+        Some(other) => {
+            return Err(node_error(
+                doc,
+                node,
+                format!("unknown Log style '{}'", other),
+            ));
+        }
+        None => return Err(node_error(doc, node, "Log has no style".to_string())),
+    };
+
+    // Params without a channel attribute apply to all channels
+    let mut params = [LogParams::default(); 3];
+    for child in node.children().filter(|n| n.has_tag_name("LogParams")) {
+        let attr = |name: &str| -> Result<Option<f32>, LutError> {
+            child
+                .attribute(name)
+                .map(|v| {
+                    v.trim().parse::<f32>().map_err(|_| {
+                        let pos = doc.text_pos_at(child.range().start);
+                        LutError::InvalidValue {
+                            line: pos.row as usize,
+                            column: pos.col as usize,
+                            value: v.to_string(),
+                        }
+                    })
+                })
+                .transpose()
+        };
+        let defaults = LogParams::default();
+        let p = LogParams {
+            base: attr("base")?.unwrap_or(defaults.base),
+            log_side_slope: attr("logSideSlope")?.unwrap_or(defaults.log_side_slope),
+            log_side_offset: attr("logSideOffset")?.unwrap_or(defaults.log_side_offset),
+            lin_side_slope: attr("linSideSlope")?.unwrap_or(defaults.lin_side_slope),
+            lin_side_offset: attr("linSideOffset")?.unwrap_or(defaults.lin_side_offset),
+            lin_side_break: attr("linSideBreak")?,
+            linear_slope: attr("linearSlope")?,
+        };
+        match child.attribute("channel") {
+            None => params = [p; 3],
+            Some("R") => params[0] = p,
+            Some("G") => params[1] = p,
+            Some("B") => params[2] = p,
+            Some(other) => {
+                return Err(node_error(
+                    doc,
+                    child,
+                    format!("unknown LogParams channel '{}'", other),
+                ));
+            }
+        }
+    }
+    let camera = matches!(style, LogStyle::CameraLinToLog | LogStyle::CameraLogToLin);
+    if camera && params.iter().any(|p| p.lin_side_break.is_none()) {
+        return Err(node_error(
+            doc,
+            node,
+            "camera Log styles need a linSideBreak".to_string(),
+        ));
+    }
+    // The bit depths don't scale a Log node, but are still validated
+    bit_depth_scale(doc, node, "inBitDepth")?;
+    bit_depth_scale(doc, node, "outBitDepth")?;
+    Ok(ProcessNode::Log(Log { style, params }))
+}
+
+/// Creates an error pointing at the start of `node`.
+fn node_error(doc: &Document, node: Node, message: String) -> LutError {
+    let pos = doc.text_pos_at(node.range().start);
+    LutError::MalformedLine {
+        line: pos.row as usize,
+        column: pos.col as usize,
+        message,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Parses a process list holding the given nodes.
+    fn process_list(nodes: &str) -> Clf {
+        Clf::parse(&format!(
+            r#"<ProcessList id="test" compCLFversion="3">{}</ProcessList>"#,
+            nodes
+        ))
+        .unwrap()
+    }
+
+    fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+        for c in 0..3 {
+            assert!(
+                (actual[c] - expected[c]).abs() < 1e-5,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    /// A process list with a single `Log` node at the given bit depths.
+    fn log_clf(style: &str, in_depth: &str, out_depth: &str) -> Clf {
+        Clf::parse(&format!(
+            r#"<ProcessList id="test" compCLFversion="3">
+                <Log inBitDepth="{}" outBitDepth="{}" style="{}">
+                    <LogParams base="2" logSideSlope="0.25" logSideOffset="0.5"
+                        linSideSlope="1" linSideOffset="0.01" linSideBreak="0.02" />
+                </Log>
+            </ProcessList>"#,
+            in_depth, out_depth, style
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn log_nodes_ignore_bit_depths() {
+        for style in [
+            "log10",
+            "antiLog10",
+            "log2",
+            "antiLog2",
+            "linToLog",
+            "logToLin",
+            "cameraLinToLog",
+            "cameraLogToLin",
+        ] {
+            let integer = log_clf(style, "10i", "10i");
+            let float = log_clf(style, "32f", "32f");
+            for v in [0.01, 0.18, 0.5, 0.9] {
+                let (a, b) = (integer.apply([v; 3]), float.apply([v; 3]));
+                assert!((a[0] - b[0]).abs() < 1e-6, "{}: {:?} != {:?}", style, a, b);
+            }
+        }
+        let log10 = log_clf("log10", "10i", "10i");
+        assert!((log10.apply([0.5; 3])[0] - 0.5f32.log10()).abs() < 1e-6);
+    }
+
+    #[test]
+    fn evaluates_lut1d_nodes() {
+        let shared = process_list(
+            r#"<LUT1D inBitDepth="10i" outBitDepth="10i">
+                <Array dim="3 1">0 256 1023</Array>
+            </LUT1D>"#,
+        );
+        assert_close(shared.apply([0.0, 0.25, 1.0]), [0.0, 128.0 / 1023.0, 1.0]);
+
+        let per_channel = process_list(
+            r#"<LUT1D inBitDepth="32f" outBitDepth="32f">
+                <Array dim="2 3">0 0 0 1 0.5 0.25</Array>
+            </LUT1D>"#,
+        );
+        assert_close(per_channel.apply([1.0; 3]), [1.0, 0.5, 0.25]);
+        assert_close(per_channel.apply([0.5; 3]), [0.5, 0.25, 0.125]);
+    }
+
+    #[test]
+    fn reorders_lut3d_nodes_to_red_fastest() {
+        // An identity lattice listed with blue varying fastest, as CLF stores it
+        let values: Vec<String> = (0..8)
+            .map(|i| {
+                let (r, g, b) = (i >> 2, i >> 1 & 1, i & 1);
+                format!("{} {} {}", r * 4095, g * 4095, b * 4095)
+            })
+            .collect();
+        for interpolation in ["trilinear", "tetrahedral"] {
+            let clf = process_list(&format!(
+                r#"<LUT3D inBitDepth="12i" outBitDepth="12i" interpolation="{}">
+                    <Array dim="2 2 2 3">{}</Array>
+                </LUT3D>"#,
+                interpolation,
+                values.join("\n")
+            ));
+            for rgb in [[1.0, 0.0, 0.0], [0.0, 0.0, 1.0], [0.2, 0.5, 0.9]] {
+                assert_close(clf.apply(rgb), rgb);
+            }
+        }
+    }
+
+    #[test]
+    fn evaluates_matrix_nodes() {
+        let m3x3 = process_list(
+            r#"<Matrix inBitDepth="32f" outBitDepth="32f">
+                <Array dim="3 3">0 1 0  0 0 1  1 0 0</Array>
+            </Matrix>"#,
+        );
+        assert_close(m3x3.apply([0.1, 0.2, 0.3]), [0.2, 0.3, 0.1]);
+
+        let m3x4 = process_list(
+            r#"<Matrix inBitDepth="32f" outBitDepth="32f">
+                <Array dim="3 4">0.5 0 0 0.1  0 1 0 0  0 0 2 -0.2</Array>
+            </Matrix>"#,
+        );
+        assert_close(m3x4.apply([0.4, 0.5, 0.5]), [0.3, 0.5, 0.8]);
+
+        // Offsets are in output code values, coefficients map input to output depth
+        let m3x4 = process_list(
+            r#"<Matrix inBitDepth="8i" outBitDepth="10i">
+                <Array dim="3 4 3">4 0 0 102.3  0 4 0 0  0 0 4 0</Array>
+            </Matrix>"#,
+        );
+        assert_close(
+            m3x4.apply([0.5, 0.25, 1.0]),
+            [
+                0.5 * 1020.0 / 1023.0 + 0.1,
+                0.25 * 1020.0 / 1023.0,
+                1020.0 / 1023.0,
+            ],
+        );
+    }
+
+    #[test]
+    fn evaluates_range_nodes() {
+        let range = |style: &str| {
+            process_list(&format!(
+                r#"<Range inBitDepth="32f" outBitDepth="32f" style="{}">
+                    <minInValue>0.1</minInValue><maxInValue>0.9</maxInValue>
+                    <minOutValue>0</minOutValue><maxOutValue>1</maxOutValue>
+                </Range>"#,
+                style
+            ))
+        };
+        assert_close(range("Clamp").apply([0.5, 0.95, 0.0]), [0.5, 1.0, 0.0]);
+        assert_close(
+            range("noClamp").apply([0.5, 0.95, 0.0]),
+            [0.5, 1.0625, -0.125],
+        );
+
+        // Only a minimum: shift and clamp below, whatever the style
+        let min_only = process_list(
+            r#"<Range inBitDepth="10i" outBitDepth="32f">
+                <minInValue>64</minInValue><minOutValue>0</minOutValue>
+            </Range>"#,
+        );
+        assert_close(
+            min_only.apply([0.0, 0.5, 1.0]),
+            [0.0, 0.5 - 64.0 / 1023.0, 1.0 - 64.0 / 1023.0],
+        );
+    }
+
+    #[test]
+    fn evaluates_nodes_in_order() {
+        let clf = process_list(
+            r#"<Matrix inBitDepth="32f" outBitDepth="32f">
+                <Array dim="3 4">2 0 0 0  0 2 0 0  0 0 2 -0.5</Array>
+            </Matrix>
+            <Range inBitDepth="32f" outBitDepth="32f" style="Clamp">
+                <minInValue>0</minInValue><maxInValue>1</maxInValue>
+                <minOutValue>0</minOutValue><maxOutValue>1</maxOutValue>
+            </Range>
+            <LUT1D inBitDepth="32f" outBitDepth="32f">
+                <Array dim="2 1">1 0</Array>
+            </LUT1D>"#,
+        );
+        // Scale, clamp, then invert
+        assert_close(clf.apply([0.2, 0.7, 0.5]), [0.6, 0.0, 0.5]);
+
+        let lut = clf.bake(5).unwrap();
+        assert_close(
+            lut.sample([0.25, 0.75, 0.5], Interpolation::Trilinear),
+            clf.apply([0.25, 0.75, 0.5]),
+        );
+    }
+}
@@ -0,0 +1,394 @@
+use crate::lut3d::{Interpolation, Lut3D};
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::io::Error;
+
+/// Chromaticity of the D65 white point shared by all supported color spaces.
+pub(crate) const D65: [f64; 2] = [0.3127, 0.3290];
+
+/// A color space a LUT expects as input or produces as output.
+///
+/// Each space combines a set of primaries with a transfer function; all of them use
+/// a D65 white point, so converting between them needs no chromatic adaptation.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Deserialize)]
+pub enum ColorSpace {
+    /// sRGB primaries and piecewise sRGB transfer function (JPEG and PNG default)
+    #[default]
+    #[serde(rename = "srgb")]
+    Srgb,
+    /// Rec.709 primaries, scene-linear
+    #[serde(rename = "linear-rec709")]
+    LinearRec709,
+    /// Display P3 primaries with the sRGB transfer function
+    #[serde(rename = "display-p3")]
+    DisplayP3,
+    /// Rec.2020 primaries with a pure 2.4 gamma (BT.1886 with a zero black level)
+    #[serde(rename = "rec2020")]
+    Rec2020,
+    /// Sony S-Log3 with S-Gamut3.Cine primaries
+    #[serde(rename = "slog3")]
+    SLog3,
+    /// ARRI LogC3 (EI 800) with ARRI Wide Gamut 3 primaries
+    #[serde(rename = "logc3")]
+    LogC3,
+    /// Panasonic V-Log with V-Gamut primaries
+    #[serde(rename = "vlog")]
+    VLog,
+}
+
+impl ColorSpace {
+    /// All color spaces, in the order they are offered in the GUI.
+    pub const ALL: [ColorSpace; 7] = [
+        ColorSpace::Srgb,
+        ColorSpace::LinearRec709,
+        ColorSpace::DisplayP3,
+        ColorSpace::Rec2020,
+        ColorSpace::SLog3,
+        ColorSpace::LogC3,
+        ColorSpace::VLog,
+    ];
+
+    /// Short lowercase name used in configuration files.
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorSpace::Srgb => "srgb",
+            ColorSpace::LinearRec709 => "linear-rec709",
+            ColorSpace::DisplayP3 => "display-p3",
+            ColorSpace::Rec2020 => "rec2020",
+            ColorSpace::SLog3 => "slog3",
+            ColorSpace::LogC3 => "logc3",
+            ColorSpace::VLog => "vlog",
+        }
+    }
+
+    /// CIE xy chromaticities of the red, green, and blue primaries.
+    fn primaries(self) -> [[f64; 2]; 3] {
+        match self {
+            ColorSpace::Srgb | ColorSpace::LinearRec709 => {
+                [[0.640, 0.330], [0.300, 0.600], [0.150, 0.060]]
+            }
+            ColorSpace::DisplayP3 => [[0.680, 0.320], [0.265, 0.690], [0.150, 0.060]],
+            ColorSpace::Rec2020 => [[0.708, 0.292], [0.170, 0.797], [0.131, 0.046]],
+            ColorSpace::SLog3 => [[0.766, 0.275], [0.225, 0.800], [0.089, -0.087]],
+            ColorSpace::LogC3 => [[0.6840, 0.3130], [0.2210, 0.8480], [0.0861, -0.1020]],
+            ColorSpace::VLog => [[0.730, 0.280], [0.165, 0.840], [0.100, -0.030]],
+        }
+    }
+
+    /// Decodes an encoded channel value to linear light.
+    pub fn to_linear(self, v: f32) -> f32 {
+        match self {
+            ColorSpace::Srgb | ColorSpace::DisplayP3 => {
+                // Mirrored for negative values so out-of-gamut colors round-trip
+                let a = v.abs();
+                let lin = if a <= 0.04045 {
+                    a / 12.92
+                } else {
+                    ((a + 0.055) / 1.055).powf(2.4)
+                };
+                lin.copysign(v)
+            }
+            ColorSpace::LinearRec709 => v,
+            ColorSpace::Rec2020 => v.abs().powf(2.4).copysign(v),
+            ColorSpace::SLog3 => {
+                if v >= 171.210_3 / 1023.0 {
+                    10f32.powf((v * 1023.0 - 420.0) / 261.5) * (0.18 + 0.01) - 0.01
+                } else {
+                    (v * 1023.0 - 95.0) * 0.011_25 / (171.210_3 - 95.0)
+                }
+            }
+            ColorSpace::LogC3 => {
+                let (cut, a, b, c, d, e, f) = LOGC3;
+                if v > e * cut + f {
+                    (10f32.powf((v - d) / c) - b) / a
+                } else {
+                    (v - f) / e
+                }
+            }
+            ColorSpace::VLog => {
+                if v < 0.181 {
+                    (v - 0.125) / 5.6
+                } else {
+                    10f32.powf((v - 0.598_206) / 0.241_514) - 0.008_73
+                }
+            }
+        }
+    }
+
+    /// Encodes a linear-light channel value with the space's transfer function.
+    pub fn from_linear(self, v: f32) -> f32 {
+        match self {
+            ColorSpace::Srgb | ColorSpace::DisplayP3 => {
+                let a = v.abs();
+                let enc = if a <= 0.003_130_8 {
+                    a * 12.92
+                } else {
+                    1.055 * a.powf(1.0 / 2.4) - 0.055
+                };
+                enc.copysign(v)
+            }
+            ColorSpace::LinearRec709 => v,
+            ColorSpace::Rec2020 => v.abs().powf(1.0 / 2.4).copysign(v),
+            ColorSpace::SLog3 => {
+                if v >= 0.011_25 {
+                    (420.0 + ((v + 0.01) / (0.18 + 0.01)).log10() * 261.5) / 1023.0
+                } else {
+                    (v * (171.210_3 - 95.0) / 0.011_25 + 95.0) / 1023.0
+                }
+            }
+            ColorSpace::LogC3 => {
+                let (cut, a, b, c, d, e, f) = LOGC3;
+                if v > cut {
+                    c * (a * v + b).log10() + d
+                } else {
+                    e * v + f
+                }
+            }
+            ColorSpace::VLog => {
+                if v < 0.01 {
+                    5.6 * v + 0.125
+                } else {
+                    0.241_514 * (v + 0.008_73).log10() + 0.598_206
+                }
+            }
+        }
+    }
+
+    /// Matrix converting linear RGB in this space to CIE XYZ.
+    pub(crate) fn to_xyz(self) -> [[f64; 3]; 3] {
+        let xyz = |[x, y]: [f64; 2]| [x / y, 1.0, (1.0 - x - y) / y];
+        let [r, g, b] = self.primaries().map(xyz);
+        let primaries = [[r[0], g[0], b[0]], [r[1], g[1], b[1]], [r[2], g[2], b[2]]];
+
+        // Scale each primary so that RGB (1, 1, 1) maps to the white point
+        let s = mul_vec(&invert(&primaries), xyz(D65));
+        std::array::from_fn(|i| std::array::from_fn(|j| primaries[i][j] * s[j]))
+    }
+
+    /// Creates a conversion from colors encoded in this space to colors in `target`.
+    pub fn conversion_to(self, target: ColorSpace) -> Conversion {
+        let m = mul(&invert(&target.to_xyz()), &self.to_xyz());
+        Conversion {
+            source: self,
+            target,
+            matrix: m.map(|row| row.map(|v| v as f32)),
+        }
+    }
+}
+
+/// ARRI LogC3 (EI 800) parameters: cut, a, b, c, d, e, f.
+const LOGC3: (f32, f32, f32, f32, f32, f32, f32) = (
+    0.010_591, 5.555_556, 0.052_272, 0.247_190, 0.385_537, 5.367_655, 0.092_809,
+);
+
+/// A precomputed conversion between two color spaces, see [`ColorSpace::conversion_to`].
+#[derive(Clone, Copy, Debug)]
+pub struct Conversion {
+    source: ColorSpace,
+    target: ColorSpace,
+    /// Linear source RGB to linear target RGB
+    matrix: [[f32; 3]; 3],
+}
+
+impl Conversion {
+    /// Returns `true` if the conversion leaves colors unchanged.
+    pub fn is_identity(&self) -> bool {
+        self.source == self.target
+    }
+
+    /// Converts an encoded RGB color from the source to the target space.
+    ///
+    /// Colors outside the target gamut are not clipped.
+    pub fn apply(&self, rgb: [f32; 3]) -> [f32; 3] {
+        if self.is_identity() {
+            return rgb;
+        }
+        let lin = rgb.map(|v| self.source.to_linear(v));
+        let m = &self.matrix;
+        std::array::from_fn(|i| {
+            let v = m[i][0] * lin[0] + m[i][1] * lin[1] + m[i][2] * lin[2];
+            self.target.from_linear(v)
+        })
+    }
+
+    /// Converts every pixel of an interleaved float RGB buffer in place.
+    pub fn apply_to_buffer(&self, buf: &mut [f32]) {
+        if self.is_identity() {
+            return;
+        }
+        buf.par_chunks_exact_mut(3).for_each(|px| {
+            let rgb = self.apply([px[0], px[1], px[2]]);
+            px.copy_from_slice(&rgb);
+        });
+    }
+}
+
+/// Multiplies two 3x3 matrices.
+pub(crate) fn mul(a: &[[f64; 3]; 3], b: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    std::array::from_fn(|i| std::array::from_fn(|j| (0..3).map(|k| a[i][k] * b[k][j]).sum()))
+}
+
+/// Multiplies a 3x3 matrix with a vector.
+pub(crate) fn mul_vec(a: &[[f64; 3]; 3], v: [f64; 3]) -> [f64; 3] {
+    std::array::from_fn(|i| a[i][0] * v[0] + a[i][1] * v[1] + a[i][2] * v[2])
+}
+
+/// Bradford cone response matrix used for chromatic adaptation.
+const BRADFORD: [[f64; 3]; 3] = [
+    [0.8951, 0.2664, -0.1614],
+    [-0.7502, 1.7135, 0.0367],
+    [0.0389, -0.0685, 1.0296],
+];
+
+/// Bradford matrix adapting XYZ colors from the white `src` to the white `dst`.
+pub(crate) fn bradford(src: [f64; 3], dst: [f64; 3]) -> [[f64; 3]; 3] {
+    let (src, dst) = (mul_vec(&BRADFORD, src), mul_vec(&BRADFORD, dst));
+    let scale: [[f64; 3]; 3] = std::array::from_fn(|i| {
+        std::array::from_fn(|j| if i == j { dst[i] / src[i] } else { 0.0 })
+    });
+    mul(&invert(&BRADFORD), &mul(&scale, &BRADFORD))
+}
+
+/// Inverts a 3x3 matrix using its adjugate.
+pub(crate) fn invert(m: &[[f64; 3]; 3]) -> [[f64; 3]; 3] {
+    let cofactor = |r: usize, c: usize| {
+        let (r1, r2) = ((r + 1) % 3, (r + 2) % 3);
+        let (c1, c2) = ((c + 1) % 3, (c + 2) % 3);
+        m[r1][c1] * m[r2][c2] - m[r1][c2] * m[r2][c1]
+    };
+    let det = (0..3).map(|c| m[0][c] * cofactor(0, c)).sum::<f64>();
+    std::array::from_fn(|i| std::array::from_fn(|j| cofactor(j, i) / det))
+}
+
+/// Bakes color space conversions around a LUT.
+///
+/// The result takes colors in `working` space, converts them to the LUT's
+/// `input` space, applies the LUT, and converts its `output` back to `working`,
+/// so technical LUTs built for linear or log input can be applied to ordinary
+/// images. The lattice of `size` points per axis is sampled in the working space.
+///
+/// # Arguments
+/// * `lut` - The LUT to convert around
+/// * `input` - The color space the LUT expects
+/// * `output` - The color space the LUT produces
+/// * `working` - The color space of the images being graded
+/// * `size` - The size of each dimension of the baked lattice (e.g. 33)
+/// * `interpolation` - Interpolation used to sample the LUT
+///
+/// # Returns
+/// A `Result` containing the color-managed `Lut3D`
+///
+/// # Errors
+/// Returns an error if `size` is smaller than 2
+pub fn bake(
+    lut: &Lut3D,
+    input: ColorSpace,
+    output: ColorSpace,
+    working: ColorSpace,
+    size: usize,
+    interpolation: Interpolation,
+) -> Result<Lut3D, Error> {
+    let to_input = working.conversion_to(input);
+    let from_output = output.conversion_to(working);
+    let mut baked = Lut3D::bake(size, |rgb| {
+        from_output.apply(lut.sample(to_input.apply(rgb), interpolation))
+    })?;
+    baked.set_out_of_domain(lut.out_of_domain());
+    if let Some(title) = lut.title() {
+        baked.set_title(title);
+    }
+    baked.add_comment(format!(
+        " {} to {} LUT baked for {} images at size {} ({})",
+        input.name(),
+        output.name(),
+        working.name(),
+        size,
+        interpolation.name()
+    ));
+    Ok(baked)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transfer_functions_round_trip() {
+        for space in ColorSpace::ALL {
+            for i in 0..=100 {
+                let v = i as f32 / 100.0;
+                let back = space.from_linear(space.to_linear(v));
+                assert!(
+                    (back - v).abs() < 1e-4,
+                    "{}: {} -> {}",
+                    space.name(),
+                    v,
+                    back
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn encodes_mid_grey_at_published_code_values() {
+        for (space, code) in [
+            (ColorSpace::Srgb, 0.461_356),
+            (ColorSpace::LinearRec709, 0.18),
+            // 420 of 1023
+            (ColorSpace::SLog3, 0.410_557),
+            // EI 800
+            (ColorSpace::LogC3, 0.391_007),
+            (ColorSpace::VLog, 0.423_311),
+        ] {
+            let encoded = space.from_linear(0.18);
+            assert!(
+                (encoded - code).abs() < 1e-5,
+                "{}: {}",
+                space.name(),
+                encoded
+            );
+            assert!(
+                (space.to_linear(code) - 0.18).abs() < 1e-4,
+                "{}",
+                space.name()
+            );
+        }
+    }
+
+    #[test]
+    fn gamuts_round_trip_through_xyz() {
+        let white = [D65[0] / D65[1], 1.0, (1.0 - D65[0] - D65[1]) / D65[1]];
+        for space in ColorSpace::ALL {
+            let to_xyz = space.to_xyz();
+            let round_trip = mul(&invert(&to_xyz), &to_xyz);
+            for (i, row) in round_trip.iter().enumerate() {
+                for (j, v) in row.iter().enumerate() {
+                    let identity = if i == j { 1.0 } else { 0.0 };
+                    assert!(
+                        (v - identity).abs() < 1e-9,
+                        "{}: {:?}",
+                        space.name(),
+                        round_trip
+                    );
+                }
+            }
+
+            // RGB white is the D65 white point
+            let xyz = mul_vec(&to_xyz, [1.0; 3]);
+            for c in 0..3 {
+                assert!(
+                    (xyz[c] - white[c]).abs() < 1e-9,
+                    "{}: {:?}",
+                    space.name(),
+                    xyz
+                );
+            }
+        }
+
+        // Rec.709 luminance weights
+        let y = ColorSpace::Srgb.to_xyz()[1];
+        for (actual, expected) in y.iter().zip([0.2126, 0.7152, 0.0722]) {
+            assert!((actual - expected).abs() < 1e-4, "{:?}", y);
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use crate::lut3d;
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::sync::OnceLock;
+
+/// Side length of the tiled blue-noise threshold map.
+const NOISE_SIZE: usize = 64;
+
+/// Standard deviation of the Gaussian used to measure clustering while building the
+/// blue-noise map, in pixels.
+const NOISE_SIGMA: f32 = 1.5;
+
+/// How graded float colors are quantized to 8 bits.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Dither {
+    /// Round to the nearest value (fast, may band in smooth gradients)
+    #[default]
+    None,
+    /// Ordered dithering with a tiled blue-noise threshold map
+    BlueNoise,
+    /// Floyd–Steinberg error diffusion along serpentine rows
+    FloydSteinberg,
+}
+
+impl Dither {
+    /// All dithering methods, in the order they are offered in the GUI.
+    pub const ALL: [Dither; 3] = [Dither::None, Dither::BlueNoise, Dither::FloydSteinberg];
+
+    /// Short lowercase name used in configuration files.
+    pub fn name(self) -> &'static str {
+        match self {
+            Dither::None => "none",
+            Dither::BlueNoise => "bluenoise",
+            Dither::FloydSteinberg => "floydsteinberg",
+        }
+    }
+}
+
+/// Quantizes an interleaved float RGB image to 8 bits.
+///
+/// # Arguments
+/// * `src` - RGB values in the range [0, 1], row by row; values outside are clamped
+/// * `dst` - Receives the 8-bit RGB values; must be as long as `src`
+/// * `width` - Image width in pixels
+/// * `dither` - The quantization method
+///
+/// # Panics
+/// Panics if `src` and `dst` differ in length or don't hold whole rows of `width` pixels
+pub fn quantize_rgb8(src: &[f32], dst: &mut [u8], width: usize, dither: Dither) {
+    let row_len = width * 3;
+    assert_eq!(src.len(), dst.len(), "Source and destination sizes differ");
+    assert!(
+        row_len > 0 && src.len().is_multiple_of(row_len),
+        "Buffer doesn't hold whole rows"
+    );
+
+    match dither {
+        Dither::None => dst
+            .par_iter_mut()
+            .zip(src.par_iter())
+            .for_each(|(d, &s)| *d = lut3d::unit_to_u8(s)),
+        Dither::BlueNoise => {
+            let noise = blue_noise();
+            dst.par_chunks_mut(row_len)
+                .zip(src.par_chunks(row_len))
+                .enumerate()
+                .for_each(|(y, (d, s))| {
+                    for (i, (d, &s)) in d.iter_mut().zip(s).enumerate() {
+                        // Offset the map per channel so the channels' patterns don't align
+                        let (x, c) = (i / 3, i % 3);
+                        let nx = (x + c * 23) % NOISE_SIZE;
+                        let ny = (y + c * 41) % NOISE_SIZE;
+                        *d = quantize(s * 255.0 + noise[ny * NOISE_SIZE + nx]);
+                    }
+                });
+        }
+        Dither::FloydSteinberg => floyd_steinberg(src, dst, row_len),
+    }
+}
+
+/// Converts a value in 8-bit units (already offset for rounding) to `u8`.
+fn quantize(v: f32) -> u8 {
+    v.floor().clamp(0.0, 255.0) as u8
+}
+
+/// Floyd–Steinberg error diffusion, alternating the direction of each row.
+fn floyd_steinberg(src: &[f32], dst: &mut [u8], row_len: usize) {
+    // Error carried into the current and the next row, per interleaved channel
+    let mut current = vec![0.0f32; row_len + 6];
+    let mut next = vec![0.0f32; row_len + 6];
+    let width = row_len / 3;
+
+    for (y, (d, s)) in dst.chunks_mut(row_len).zip(src.chunks(row_len)).enumerate() {
+        let reverse = y % 2 == 1;
+        for step in 0..width {
+            let x = if reverse { width - 1 - step } else { step };
+            for c in 0..3 {
+                let i = x * 3 + c;
+                // Error buffers are padded by one pixel on each side
+                let e = i + 3;
+                let v = s[i].clamp(0.0, 1.0) * 255.0 + current[e];
+                let q = quantize(v + 0.5);
+                d[i] = q;
+                let err = v - q as f32;
+                let (ahead, behind) = if reverse {
+                    (e - 3, e + 3)
+                } else {
+                    (e + 3, e - 3)
+                };
+                current[ahead] += err * 7.0 / 16.0;
+                next[behind] += err * 3.0 / 16.0;
+                next[e] += err * 5.0 / 16.0;
+                next[ahead] += err * 1.0 / 16.0;
+            }
+        }
+        std::mem::swap(&mut current, &mut next);
+        next.fill(0.0);
+    }
+}
+
+/// The blue-noise threshold map, built on first use.
+///
+/// Each entry is a threshold in (0, 1); adding it before truncation dithers a value
+/// without biasing it.
+fn blue_noise() -> &'static [f32] {
+    static NOISE: OnceLock<Vec<f32>> = OnceLock::new();
+    NOISE.get_or_init(|| {
+        let n = NOISE_SIZE * NOISE_SIZE;
+        void_and_cluster()
+            .into_iter()
+            .map(|rank| (rank as f32 + 0.5) / n as f32)
+            .collect()
+    })
+}
+
+/// Ranks the pixels of a tileable blue-noise pattern with the void-and-cluster method.
+///
+/// A sparse random pattern is relaxed by moving points from the tightest cluster to
+/// the largest void. Ranks are then assigned by removing the prototype's points one
+/// cluster at a time and finally filling the remaining voids.
+fn void_and_cluster() -> Vec<usize> {
+    let n = NOISE_SIZE * NOISE_SIZE;
+
+    // Gaussian weight by toroidal offset, so the tile repeats seamlessly
+    let kernel: Vec<f32> = (0..n)
+        .map(|i| {
+            let wrap = |d: usize| d.min(NOISE_SIZE - d) as f32;
+            let (dx, dy) = (wrap(i % NOISE_SIZE), wrap(i / NOISE_SIZE));
+            (-(dx * dx + dy * dy) / (2.0 * NOISE_SIGMA * NOISE_SIGMA)).exp()
+        })
+        .collect();
+    let offset = |a: usize, b: usize| {
+        let dx = (a % NOISE_SIZE + NOISE_SIZE - b % NOISE_SIZE) % NOISE_SIZE;
+        let dy = (a / NOISE_SIZE + NOISE_SIZE - b / NOISE_SIZE) % NOISE_SIZE;
+        dy * NOISE_SIZE + dx
+    };
+    let update = |energy: &mut [f32], p: usize, sign: f32| {
+        for (i, e) in energy.iter_mut().enumerate() {
+            *e += sign * kernel[offset(i, p)];
+        }
+    };
+    let tightest = |points: &[bool], energy: &[f32]| {
+        (0..n)
+            .filter(|&i| points[i])
+            .max_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+    let largest_void = |points: &[bool], energy: &[f32]| {
+        (0..n)
+            .filter(|&i| !points[i])
+            .min_by(|&a, &b| energy[a].total_cmp(&energy[b]))
+            .unwrap()
+    };
+
+    // Deterministic sparse initial pattern covering a tenth of the pixels
+    let mut points = vec![false; n];
+    let mut energy = vec![0.0f32; n];
+    let mut state = 0x9e37_79b9_7f4a_7c15u64;
+    let mut placed = 0;
+    while placed < n / 10 {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        let p = (state % n as u64) as usize;
+        if !points[p] {
+            points[p] = true;
+            update(&mut energy, p, 1.0);
+            placed += 1;
+        }
+    }
+
+    // Relax into the prototype pattern
+    for _ in 0..n {
+        let cluster = tightest(&points, &energy);
+        points[cluster] = false;
+        update(&mut energy, cluster, -1.0);
+        let void = largest_void(&points, &energy);
+        points[void] = true;
+        update(&mut energy, void, 1.0);
+        if void == cluster {
+            break;
+        }
+    }
+
+    let mut rank = vec![0usize; n];
+
+    // Rank the prototype's points from the tightest cluster down
+    let (mut proto_points, mut proto_energy) = (points.clone(), energy.clone());
+    for r in (0..placed).rev() {
+        let cluster = tightest(&proto_points, &proto_energy);
+        proto_points[cluster] = false;
+        update(&mut proto_energy, cluster, -1.0);
+        rank[cluster] = r;
+    }
+
+    // Rank the remaining pixels by filling the largest voids
+    for r in placed..n {
+        let void = largest_void(&points, &energy);
+        points[void] = true;
+        update(&mut energy, void, 1.0);
+        rank[void] = r;
+    }
+    rank
+}
@@ -1,8 +1,17 @@
-use crate::lut3d::Lut3D;
 use crate::metadata_handler::copy_metadata;
-use image::{ImageReader, RgbImage};
+use image::codecs::{jpeg::JpegEncoder, png::PngEncoder};
+use image::{
+    ColorType, DynamicImage, ImageDecoder, ImageEncoder, ImageError, ImageReader, ImageResult,
+    Rgb32FImage, RgbImage,
+};
+use opsin::color_space::ColorSpace;
+use opsin::dither::{self, Dither};
+use opsin::icc::{IccProfile, OutputProfile, ProfileConversion};
+use opsin::lut_batch::PixelBuffer;
+use opsin::lut_cache::LazyTable;
 use std::{
-    fs,
+    fs::{self, File},
+    io::BufWriter,
     path::Path,
     sync::{Arc, Mutex},
 };
@@ -11,17 +20,25 @@ use walkdir::WalkDir;
 /// Processes images in the input directory by applying LUT transformations and copying to output.
 /// 
 /// This function walks through all files in the input directory, applies the specified LUT
-/// transformation to supported image formats (JPG, JPEG, PNG), and saves both the original
-/// and processed versions to the output directory. Non-image files are copied as-is.
+/// transformation to supported image formats (JPG, JPEG, PNG, TIFF, EXR, HDR), and saves both
+/// the original and processed versions to the output directory. Non-image files are copied as-is.
 /// 
 /// # Arguments
 /// * `input_dir` - Directory containing source images to process
 /// * `output_dir` - Directory where processed images and copies will be saved
-/// * `lut_table` - Precomputed LUT lookup table for fast color transformations
+/// * `lut_table` - LUT with its precomputed lookup table; images processed before the
+///   table is ready are transformed by direct interpolation instead
+/// * `dither` - How graded colors of 8-bit images are quantized
+/// * `output_profile` - Color profile exported JPEG and PNG images are converted to
+///   and tagged with
 /// * `logger` - Thread-safe logger for status updates and progress tracking
 /// 
 /// # Behavior
 /// - For supported image formats: Creates a "_RAW" backup copy and a LUT-processed version
+/// - 8-bit images use the precomputed table; 16-bit (PNG, TIFF) and float (EXR, HDR)
+///   images are interpolated in floating point and saved at their source bit depth
+/// - Images with an embedded ICC profile are converted to sRGB before grading;
+///   untagged images are assumed to be sRGB
 /// - For other files: Creates a direct copy without processing
 /// - Preserves directory structure in the output
 /// - Copies EXIF metadata from originals to processed images
@@ -29,7 +46,9 @@ use walkdir::WalkDir;
 pub fn process_images(
     input_dir: &Path,
     output_dir: &Path,
-    lut_table: &[u8],
+    lut_table: &LazyTable,
+    dither: Dither,
+    output_profile: OutputProfile,
     logger: Arc<Mutex<Vec<String>>>,
 ) {
     // Validate input directory exists
@@ -86,27 +105,86 @@ pub fn process_images(
             .map(|s| s.to_lowercase())
         {
             match ext.as_str() {
-                "jpg" | "jpeg" | "png" => {
+                "jpg" | "jpeg" | "png" | "tif" | "tiff" | "exr" | "hdr" => {
                     // Create backup copy with "RAW_" prefix
                     let orig_name = format!("{}_RAW", rel.file_name().unwrap().to_string_lossy());
                     let orig_out = out_path.with_file_name(orig_name);
                     fs::copy(path, &orig_out).unwrap();
 
-                    // Load and process the image with LUT transformation
-                    let img = ImageReader::open(path).unwrap().decode().unwrap().to_rgb8();
-                    let (w, h) = img.dimensions();
-                    let mut buf = img.into_raw();
-                    
-                    // Apply LUT transformation to each pixel
-                    buf.chunks_mut(3).for_each(|px| {
-                        // Transform RGB values using precomputed LUT table
-                        let rgb = Lut3D::apply_precomputed(lut_table, px[0], px[1], px[2]);
-                        px.copy_from_slice(&rgb);
+                    // Load the image along with its embedded color profile
+                    let mut decoder = match ImageReader::open(path)
+                        .map_err(ImageError::IoError)
+                        .and_then(|reader| reader.into_decoder())
+                    {
+                        Ok(decoder) => decoder,
+                        Err(e) => {
+                            logger.lock().unwrap().push(format!(
+                                "Error processing {}: {}",
+                                rel.display(),
+                                e
+                            ));
+                            continue;
+                        }
+                    };
+                    let icc = decoder.icc_profile().unwrap_or(None);
+                    let img = match DynamicImage::from_decoder(decoder) {
+                        Ok(img) => img,
+                        Err(e) => {
+                            logger.lock().unwrap().push(format!(
+                                "Error processing {}: {}",
+                                rel.display(),
+                                e
+                            ));
+                            continue;
+                        }
+                    };
+                    let source = icc.and_then(|bytes| match IccProfile::parse(&bytes) {
+                        Ok(profile) => {
+                            let conversion = profile.conversion_to(ColorSpace::Srgb);
+                            if !conversion.is_identity() {
+                                logger.lock().unwrap().push(format!(
+                                    "Converting {} from {} to sRGB",
+                                    rel.display(),
+                                    profile.description().unwrap_or("its ICC profile")
+                                ));
+                            }
+                            Some(conversion)
+                        }
+                        Err(e) => {
+                            logger.lock().unwrap().push(format!(
+                                "Ignoring ICC profile of {}, assuming sRGB: {}",
+                                rel.display(),
+                                e
+                            ));
+                            None
+                        }
                     });
-                    
-                    // Reconstruct and save the processed image
-                    let processed = RgbImage::from_raw(w, h, buf).unwrap();
-                    processed.save(&out_path).unwrap();
+                    let export =
+                        matches!(ext.as_str(), "jpg" | "jpeg" | "png").then_some(output_profile);
+
+                    // Process the image with LUT transformation
+                    let managed = source.as_ref().is_some_and(|c| !c.is_identity())
+                        || export.is_some_and(|p| p != OutputProfile::Srgb);
+                    let uses_table =
+                        bit_depth(img.color()) == 8 && dither == Dither::None && !managed;
+                    if lut_table.table().is_none() && uses_table {
+                        logger.lock().unwrap().push(format!(
+                            "Precomputed table not ready, interpolating {} directly",
+                            rel.display()
+                        ));
+                    }
+                    let output = export.map_or(ColorSpace::Srgb, OutputProfile::color_space);
+                    let processed = grade_image(img, lut_table, source.as_ref(), output, dither);
+
+                    // Save the processed image at the source bit depth
+                    if let Err(e) = save_image(&processed, &out_path, export) {
+                        logger.lock().unwrap().push(format!(
+                            "Error processing {}: {}",
+                            rel.display(),
+                            e
+                        ));
+                        continue;
+                    }
 
                     // Copy EXIF metadata from original to processed image
                     if let Err(e) = copy_metadata(path, &out_path) {
@@ -136,6 +214,142 @@ pub fn process_images(
         .push(format!("Finished processing {} files.", total));
 }
 
+/// Loads an image as sRGB-encoded float pixels, the colors a LUT sees when grading it.
+///
+/// Images with an embedded ICC profile are converted to sRGB, float images are
+/// encoded from linear Rec.709 to sRGB, and alpha channels are dropped.
+///
+/// # Arguments
+/// * `path` - Path to the image file
+///
+/// # Returns
+/// An `ImageResult` containing the float RGB image
+pub fn load_srgb(path: &Path) -> ImageResult<Rgb32FImage> {
+    let mut decoder = ImageReader::open(path)?.into_decoder()?;
+    let icc = decoder.icc_profile().unwrap_or(None);
+    let img = DynamicImage::from_decoder(decoder)?;
+    let linear = bit_depth(img.color()) == 32;
+    let mut buf = img.into_rgb32f();
+    if linear {
+        ColorSpace::LinearRec709
+            .conversion_to(ColorSpace::Srgb)
+            .apply_to_buffer(&mut buf);
+    } else if let Some(profile) = icc.and_then(|bytes| IccProfile::parse(&bytes).ok()) {
+        profile
+            .conversion_to(ColorSpace::Srgb)
+            .apply_to_buffer(&mut buf);
+    }
+    Ok(buf)
+}
+
+/// Bits per channel of a decoded image's color type.
+fn bit_depth(color: ColorType) -> u16 {
+    color.bits_per_pixel() / color.channel_count() as u16
+}
+
+/// Applies the LUT to a decoded image, keeping its bit depth.
+///
+/// 8-bit images go through the precomputed table unless they are dithered, which
+/// needs the unquantized output; 16-bit and float images are interpolated directly
+/// so no precision is lost. Alpha channels are dropped.
+///
+/// LUTs work on sRGB-encoded colors, so float images, which hold linear Rec.709,
+/// are encoded to sRGB before grading and decoded back afterwards. Other images are
+/// converted from their ICC profile to sRGB and from sRGB to the output space in
+/// floating point, bypassing the precomputed table.
+///
+/// # Arguments
+/// * `img` - The decoded source image
+/// * `lut_table` - LUT with its precomputed lookup table
+/// * `source` - Conversion from the image's embedded ICC profile to sRGB, if it has one
+/// * `output` - The color space integer images are exported in
+/// * `dither` - How graded colors of 8-bit images are quantized
+///
+/// # Returns
+/// The graded image as 8-bit RGB, 16-bit RGB, or 32-bit float RGB
+fn grade_image(
+    img: DynamicImage,
+    lut_table: &LazyTable,
+    source: Option<&ProfileConversion>,
+    output: ColorSpace,
+    dither: Dither,
+) -> DynamicImage {
+    let (lut, options) = (lut_table.lut(), lut_table.options());
+    let to_working = source.filter(|c| !c.is_identity());
+    let from_working = ColorSpace::Srgb.conversion_to(output);
+    let managed = to_working.is_some() || !from_working.is_identity();
+    let grade_managed = |buf: &mut [f32]| {
+        if let Some(conversion) = to_working {
+            conversion.apply_to_buffer(buf);
+        }
+        lut.apply_to_buffer(PixelBuffer::Rgb32F(buf), options);
+        from_working.apply_to_buffer(buf);
+    };
+
+    match bit_depth(img.color()) {
+        32 => {
+            let mut buf = img.into_rgb32f();
+            let (linear, srgb) = (ColorSpace::LinearRec709, ColorSpace::Srgb);
+            linear.conversion_to(srgb).apply_to_buffer(&mut buf);
+            lut.apply_to_buffer(PixelBuffer::Rgb32F(&mut buf), options);
+            srgb.conversion_to(linear).apply_to_buffer(&mut buf);
+            DynamicImage::ImageRgb32F(buf)
+        }
+        16 if managed => {
+            let mut buf = img.into_rgb32f();
+            grade_managed(&mut buf);
+            DynamicImage::ImageRgb32F(buf).into_rgb16().into()
+        }
+        16 => {
+            let mut buf = img.into_rgb16();
+            lut.apply_to_buffer(PixelBuffer::Rgb16(&mut buf), options);
+            DynamicImage::ImageRgb16(buf)
+        }
+        _ if managed || dither != Dither::None => {
+            let mut buf = img.into_rgb32f();
+            grade_managed(&mut buf);
+            let mut out = RgbImage::new(buf.width(), buf.height());
+            dither::quantize_rgb8(&buf, &mut out, buf.width() as usize, dither);
+            DynamicImage::ImageRgb8(out)
+        }
+        _ => {
+            let mut buf = img.into_rgb8();
+            lut_table.apply_to_rgb8(&mut buf);
+            DynamicImage::ImageRgb8(buf)
+        }
+    }
+}
+
+/// Saves a processed image, embedding `profile` in JPEG and PNG files.
+///
+/// # Arguments
+/// * `img` - The processed image
+/// * `path` - Destination path; its extension selects the format
+/// * `profile` - Profile to embed, or `None` to save the image untagged
+fn save_image(img: &DynamicImage, path: &Path, profile: Option<OutputProfile>) -> ImageResult<()> {
+    let Some(profile) = profile else {
+        return img.save(path);
+    };
+    let writer = BufWriter::new(File::create(path)?);
+    let icc = profile.icc_bytes();
+    if path
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("png"))
+    {
+        let mut encoder = PngEncoder::new(writer);
+        encoder
+            .set_icc_profile(icc)
+            .map_err(ImageError::Unsupported)?;
+        img.write_with_encoder(encoder)
+    } else {
+        let mut encoder = JpegEncoder::new(writer);
+        encoder
+            .set_icc_profile(icc)
+            .map_err(ImageError::Unsupported)?;
+        img.write_with_encoder(encoder)
+    }
+}
+
 /// Processes video files by copying them from input to output directory.
 /// 
 /// This function searches for video files with specific extensions (MTS, M2TS) and
@@ -214,15 +428,15 @@ pub fn process_videos(input_dir: &Path, output_dir: &Path, logger: Arc<Mutex<Vec
         ));
 
         // Ensure output directory structure exists
-        if let Some(parent) = out_path.parent() {
-            if let Err(e) = fs::create_dir_all(parent) {
-                logger.lock().unwrap().push(format!(
-                    "Error creating directory {}: {}",
-                    parent.display(),
-                    e
-                ));
-                continue;
-            }
+        if let Some(parent) = out_path.parent()
+            && let Err(e) = fs::create_dir_all(parent)
+        {
+            logger.lock().unwrap().push(format!(
+                "Error creating directory {}: {}",
+                parent.display(),
+                e
+            ));
+            continue;
         }
 
         // Copy the video file to the output location
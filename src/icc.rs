@@ -0,0 +1,538 @@
+use crate::color_space::{self, ColorSpace, D65};
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::io::{self, Error};
+
+/// The D50 illuminant of the ICC profile connection space, in XYZ.
+const D50: [f64; 3] = [0.9642, 1.0, 0.8249];
+
+/// Largest per-channel difference from the input, over a grid of test colors, for
+/// which a conversion is treated as doing nothing (a quarter of an 8-bit step).
+const IDENTITY_TOLERANCE: f32 = 0.25 / 255.0;
+
+/// An RGB matrix/TRC ICC profile read from an image.
+///
+/// This covers the display profiles cameras and editors embed (sRGB, Display P3,
+/// Adobe RGB, ProPhoto RGB). Profiles built from lookup tables are not supported.
+pub struct IccProfile {
+    /// The profile's description, if it has one
+    description: Option<String>,
+    /// Linear RGB to D50 connection space XYZ
+    matrix: [[f64; 3]; 3],
+    /// Tone curve decoding each channel to linear
+    curves: [Curve; 3],
+}
+
+/// A tone reproduction curve from a `curv` or `para` tag.
+#[derive(Clone)]
+enum Curve {
+    /// Sampled curve, linearly interpolated
+    Table(Vec<f32>),
+    /// Parametric curve: `(a·x + b)^g + e` from `d` up, `c·x + f` below, stored as
+    /// `[g, a, b, c, d, e, f]`
+    Parametric([f32; 7]),
+}
+
+impl Curve {
+    /// Decodes an encoded value in [0, 1] to linear.
+    fn eval(&self, v: f32) -> f32 {
+        let x = v.clamp(0.0, 1.0);
+        match self {
+            Curve::Table(table) => {
+                let pos = x * (table.len() - 1) as f32;
+                let i0 = (pos as usize).min(table.len() - 2);
+                let t = pos - i0 as f32;
+                table[i0] + (table[i0 + 1] - table[i0]) * t
+            }
+            Curve::Parametric([g, a, b, c, d, e, f]) => {
+                if x >= *d {
+                    (a * x + b).max(0.0).powf(*g) + e
+                } else {
+                    c * x + f
+                }
+            }
+        }
+    }
+}
+
+impl IccProfile {
+    /// Parses an ICC profile.
+    ///
+    /// # Arguments
+    /// * `bytes` - The profile as embedded in the image
+    ///
+    /// # Returns
+    /// A `Result` containing the parsed profile
+    ///
+    /// # Errors
+    /// Returns an error if the profile is truncated, isn't an RGB profile, or lacks
+    /// the colorant and tone curve tags of a matrix/TRC profile
+    pub fn parse(bytes: &[u8]) -> Result<Self, Error> {
+        let header = slice(bytes, 0, 132)?;
+        if &header[36..40] != b"acsp" {
+            return Err(invalid("Not an ICC profile"));
+        }
+        if &header[16..20] != b"RGB " {
+            return Err(invalid(format!(
+                "Unsupported ICC color space '{}'",
+                String::from_utf8_lossy(&header[16..20]).trim_end()
+            )));
+        }
+        if &header[20..24] != b"XYZ " {
+            return Err(invalid(
+                "Only ICC profiles with an XYZ connection space are supported",
+            ));
+        }
+
+        let count = u32_at(bytes, 128)? as usize;
+        let tag = |sig: &[u8; 4]| -> Result<Option<&[u8]>, Error> {
+            for i in 0..count {
+                let entry = slice(bytes, 132 + i * 12, 12)?;
+                if &entry[0..4] == sig {
+                    let offset = u32_at(entry, 4)? as usize;
+                    let size = u32_at(entry, 8)? as usize;
+                    return slice(bytes, offset, size).map(Some);
+                }
+            }
+            Ok(None)
+        };
+        let required = |sig: &[u8; 4]| {
+            tag(sig)?.ok_or_else(|| {
+                invalid(format!(
+                    "ICC profile has no '{}' tag (only matrix/TRC profiles are supported)",
+                    String::from_utf8_lossy(sig)
+                ))
+            })
+        };
+
+        let columns = [
+            read_xyz(required(b"rXYZ")?)?,
+            read_xyz(required(b"gXYZ")?)?,
+            read_xyz(required(b"bXYZ")?)?,
+        ];
+        let matrix = std::array::from_fn(|i| std::array::from_fn(|j| columns[j][i]));
+        let curves = [
+            read_curve(required(b"rTRC")?)?,
+            read_curve(required(b"gTRC")?)?,
+            read_curve(required(b"bTRC")?)?,
+        ];
+        let description = tag(b"desc")?.and_then(read_text);
+        Ok(IccProfile {
+            description,
+            matrix,
+            curves,
+        })
+    }
+
+    /// The profile's description, e.g. "sRGB IEC61966-2.1".
+    pub fn description(&self) -> Option<&str> {
+        self.description.as_deref()
+    }
+
+    /// Creates a conversion from colors encoded with this profile to `target`.
+    pub fn conversion_to(&self, target: ColorSpace) -> ProfileConversion {
+        let target_d50 = color_space::mul(&adaptation(D65), &target.to_xyz());
+        let m = color_space::mul(&color_space::invert(&target_d50), &self.matrix);
+        let mut conversion = ProfileConversion {
+            curves: self.curves.clone(),
+            matrix: m.map(|row| row.map(|v| v as f32)),
+            target,
+            identity: false,
+        };
+
+        // Profiles describing the target space itself need no conversion
+        let steps = 8;
+        conversion.identity = (0..(steps + 1) * (steps + 1) * (steps + 1)).all(|i| {
+            let rgb = [
+                i % (steps + 1),
+                i / (steps + 1) % (steps + 1),
+                i / (steps + 1) / (steps + 1),
+            ]
+            .map(|v| v as f32 / steps as f32);
+            let out = conversion.apply(rgb);
+            (0..3).all(|c| (out[c] - rgb[c]).abs() <= IDENTITY_TOLERANCE)
+        });
+        conversion
+    }
+}
+
+/// A precomputed conversion from an ICC profile to a color space, see
+/// [`IccProfile::conversion_to`].
+pub struct ProfileConversion {
+    /// The source profile's tone curves
+    curves: [Curve; 3],
+    /// Linear source RGB to linear target RGB
+    matrix: [[f32; 3]; 3],
+    /// The color space converted to
+    target: ColorSpace,
+    /// Whether the conversion leaves colors unchanged
+    identity: bool,
+}
+
+impl ProfileConversion {
+    /// Returns `true` if the profile matches the target space, so converting can be
+    /// skipped.
+    pub fn is_identity(&self) -> bool {
+        self.identity
+    }
+
+    /// Converts an RGB color encoded with the source profile to the target space.
+    pub fn apply(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let lin: [f32; 3] = std::array::from_fn(|c| self.curves[c].eval(rgb[c]));
+        let m = &self.matrix;
+        std::array::from_fn(|i| {
+            let v = m[i][0] * lin[0] + m[i][1] * lin[1] + m[i][2] * lin[2];
+            self.target.from_linear(v)
+        })
+    }
+
+    /// Converts every pixel of an interleaved float RGB buffer in place.
+    pub fn apply_to_buffer(&self, buf: &mut [f32]) {
+        if self.identity {
+            return;
+        }
+        buf.par_chunks_exact_mut(3).for_each(|px| {
+            let rgb = self.apply([px[0], px[1], px[2]]);
+            px.copy_from_slice(&rgb);
+        });
+    }
+}
+
+/// Color profile embedded in exported images.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+pub enum OutputProfile {
+    /// sRGB, the profile viewers assume for untagged images
+    #[default]
+    #[serde(rename = "srgb")]
+    Srgb,
+    /// Display P3, the wider gamut of recent phones and monitors
+    #[serde(rename = "display-p3")]
+    DisplayP3,
+}
+
+impl OutputProfile {
+    /// All output profiles, in the order they are offered in the GUI.
+    pub const ALL: [OutputProfile; 2] = [OutputProfile::Srgb, OutputProfile::DisplayP3];
+
+    /// Short lowercase name used in configuration files.
+    pub fn name(self) -> &'static str {
+        self.color_space().name()
+    }
+
+    /// The color space exported images are encoded in.
+    pub fn color_space(self) -> ColorSpace {
+        match self {
+            OutputProfile::Srgb => ColorSpace::Srgb,
+            OutputProfile::DisplayP3 => ColorSpace::DisplayP3,
+        }
+    }
+
+    /// Builds an ICC v4 display profile describing the output space.
+    ///
+    /// The profile holds the primaries adapted to D50, the sRGB tone curve (shared by
+    /// both spaces) as a parametric curve, and a Bradford adaptation matrix from D65.
+    pub fn icc_bytes(self) -> Vec<u8> {
+        let (description, space) = match self {
+            OutputProfile::Srgb => ("sRGB", ColorSpace::Srgb),
+            OutputProfile::DisplayP3 => ("Display P3", ColorSpace::DisplayP3),
+        };
+        let chad = adaptation(D65);
+        let colorants = color_space::mul(&chad, &space.to_xyz());
+        let column = |c: usize| xyz_tag([colorants[0][c], colorants[1][c], colorants[2][c]]);
+
+        // sRGB transfer function as a type 3 parametric curve
+        let mut trc = b"para\0\0\0\0\0\x03\0\0".to_vec();
+        for p in [2.4, 1.0 / 1.055, 0.055 / 1.055, 1.0 / 12.92, 0.04045] {
+            trc.extend_from_slice(&s15_fixed16(p));
+        }
+        let mut chad_tag = b"sf32\0\0\0\0".to_vec();
+        for v in chad.iter().flatten() {
+            chad_tag.extend_from_slice(&s15_fixed16(*v));
+        }
+
+        let tags: [(&[u8; 4], Vec<u8>); 9] = [
+            (b"desc", mluc_tag(description)),
+            (b"cprt", mluc_tag("No copyright, use freely")),
+            (b"wtpt", xyz_tag(D50)),
+            (b"chad", chad_tag),
+            (b"rXYZ", column(0)),
+            (b"gXYZ", column(1)),
+            (b"bXYZ", column(2)),
+            (b"rTRC", trc.clone()),
+            (b"gTRC", trc),
+        ];
+        // The blue curve shares the green curve's data
+        let count = tags.len() + 1;
+
+        let mut table = Vec::new();
+        let mut data = Vec::new();
+        let data_start = 128 + 4 + count * 12;
+        let mut last = (0, 0);
+        for (sig, bytes) in &tags {
+            last = (data_start + data.len(), bytes.len());
+            table.extend_from_slice(*sig);
+            table.extend_from_slice(&(last.0 as u32).to_be_bytes());
+            table.extend_from_slice(&(last.1 as u32).to_be_bytes());
+            data.extend_from_slice(bytes);
+            while !data.len().is_multiple_of(4) {
+                data.push(0);
+            }
+        }
+        table.extend_from_slice(b"bTRC");
+        table.extend_from_slice(&(last.0 as u32).to_be_bytes());
+        table.extend_from_slice(&(last.1 as u32).to_be_bytes());
+
+        let size = data_start + data.len();
+        let mut profile = Vec::with_capacity(size);
+        profile.extend_from_slice(&(size as u32).to_be_bytes());
+        profile.extend_from_slice(&[0; 4]); // preferred CMM
+        profile.extend_from_slice(&[4, 0x30, 0, 0]); // version 4.3
+        profile.extend_from_slice(b"mntrRGB XYZ ");
+        // Creation date: 2024-01-01 00:00:00
+        for v in [2024u16, 1, 1, 0, 0, 0] {
+            profile.extend_from_slice(&v.to_be_bytes());
+        }
+        profile.extend_from_slice(b"acsp");
+        profile.extend_from_slice(&[0; 24]); // platform, flags, device, attributes
+        profile.extend_from_slice(&[0; 4]); // perceptual rendering intent
+        for v in D50 {
+            profile.extend_from_slice(&s15_fixed16(v));
+        }
+        profile.extend_from_slice(&[0; 4 + 16 + 28]); // creator, profile ID, reserved
+        profile.extend_from_slice(&(count as u32).to_be_bytes());
+        profile.extend_from_slice(&table);
+        profile.extend_from_slice(&data);
+        profile
+    }
+}
+
+/// Bradford matrix adapting XYZ colors from `white` to the D50 connection space.
+fn adaptation(white: [f64; 2]) -> [[f64; 3]; 3] {
+    let [x, y] = white;
+    color_space::bradford([x / y, 1.0, (1.0 - x - y) / y], D50)
+}
+
+/// Reads an `XYZ ` tag holding a single color.
+fn read_xyz(tag: &[u8]) -> Result<[f64; 3], Error> {
+    if tag.len() < 20 || &tag[0..4] != b"XYZ " {
+        return Err(invalid("Malformed XYZ tag in ICC profile"));
+    }
+    Ok(std::array::from_fn(|i| {
+        i32::from_be_bytes(tag[8 + i * 4..12 + i * 4].try_into().unwrap()) as f64 / 65536.0
+    }))
+}
+
+/// Reads a `curv` or `para` tone curve tag.
+fn read_curve(tag: &[u8]) -> Result<Curve, Error> {
+    match tag.get(0..4) {
+        Some(b"curv") => {
+            let count = u32_at(tag, 8)? as usize;
+            let entries = slice(tag, 12, count * 2)?;
+            let u16_at = |i: usize| u16::from_be_bytes([entries[i * 2], entries[i * 2 + 1]]);
+            Ok(match count {
+                0 => Curve::Parametric([1.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0]),
+                1 => Curve::Parametric([u16_at(0) as f32 / 256.0, 1.0, 0.0, 0.0, 0.0, 0.0, 0.0]),
+                _ => Curve::Table((0..count).map(|i| u16_at(i) as f32 / 65535.0).collect()),
+            })
+        }
+        Some(b"para") => {
+            let kind = u16::from_be_bytes(slice(tag, 8, 2)?.try_into().unwrap());
+            let len = match kind {
+                0 => 1,
+                1 => 3,
+                2 => 4,
+                3 => 5,
+                4 => 7,
+                _ => {
+                    return Err(invalid(format!(
+                        "Unknown ICC parametric curve type {}",
+                        kind
+                    )));
+                }
+            };
+            let params = slice(tag, 12, len * 4)?;
+            let p: Vec<f32> = params
+                .chunks_exact(4)
+                .map(|v| i32::from_be_bytes(v.try_into().unwrap()) as f32 / 65536.0)
+                .collect();
+            // Express every type in the general form of type 4
+            Ok(Curve::Parametric(match kind {
+                0 => [p[0], 1.0, 0.0, 0.0, 0.0, 0.0, 0.0],
+                1 => [p[0], p[1], p[2], 0.0, -p[2] / p[1], 0.0, 0.0],
+                2 => [p[0], p[1], p[2], 0.0, -p[2] / p[1], p[3], p[3]],
+                3 => [p[0], p[1], p[2], p[3], p[4], 0.0, 0.0],
+                _ => [p[0], p[1], p[2], p[3], p[4], p[5], p[6]],
+            }))
+        }
+        _ => Err(invalid("Unsupported tone curve type in ICC profile")),
+    }
+}
+
+/// Reads the text of a `desc` (ICC v2) or `mluc` (ICC v4) tag.
+fn read_text(tag: &[u8]) -> Option<String> {
+    match tag.get(0..4)? {
+        b"desc" => {
+            let len = u32_at(tag, 8).ok()? as usize;
+            let text = slice(tag, 12, len).ok()?;
+            Some(
+                String::from_utf8_lossy(text)
+                    .trim_end_matches('\0')
+                    .to_string(),
+            )
+        }
+        b"mluc" => {
+            // First record: language, country, length, offset
+            let len = u32_at(tag, 20).ok()? as usize;
+            let offset = u32_at(tag, 24).ok()? as usize;
+            let units: Vec<u16> = slice(tag, offset, len)
+                .ok()?
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            Some(String::from_utf16_lossy(&units))
+        }
+        _ => None,
+    }
+}
+
+/// Encodes an `XYZ ` tag.
+fn xyz_tag(xyz: [f64; 3]) -> Vec<u8> {
+    let mut tag = b"XYZ \0\0\0\0".to_vec();
+    for v in xyz {
+        tag.extend_from_slice(&s15_fixed16(v));
+    }
+    tag
+}
+
+/// Encodes an `mluc` tag with a single en-US string.
+fn mluc_tag(text: &str) -> Vec<u8> {
+    let utf16: Vec<u8> = text.encode_utf16().flat_map(u16::to_be_bytes).collect();
+    let mut tag = b"mluc\0\0\0\0".to_vec();
+    tag.extend_from_slice(&1u32.to_be_bytes());
+    tag.extend_from_slice(&12u32.to_be_bytes());
+    tag.extend_from_slice(b"enUS");
+    tag.extend_from_slice(&(utf16.len() as u32).to_be_bytes());
+    tag.extend_from_slice(&28u32.to_be_bytes());
+    tag.extend_from_slice(&utf16);
+    tag
+}
+
+/// Encodes a number in the ICC `s15Fixed16Number` format.
+fn s15_fixed16(v: f64) -> [u8; 4] {
+    ((v * 65536.0).round() as i32).to_be_bytes()
+}
+
+/// Returns `len` bytes from `offset`, or an error if they run past the end.
+fn slice(bytes: &[u8], offset: usize, len: usize) -> Result<&[u8], Error> {
+    offset
+        .checked_add(len)
+        .and_then(|end| bytes.get(offset..end))
+        .ok_or_else(|| invalid("ICC profile is truncated"))
+}
+
+/// Reads a big-endian `u32` at `offset`.
+fn u32_at(bytes: &[u8], offset: usize) -> Result<u32, Error> {
+    Ok(u32::from_be_bytes(
+        slice(bytes, offset, 4)?.try_into().unwrap(),
+    ))
+}
+
+/// Creates an `InvalidData` error.
+fn invalid(msg: impl Into<String>) -> Error {
+    Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The D50-adapted colorants of the red, green and blue primaries, per column.
+    fn colorants(profile: &IccProfile) -> [[f64; 3]; 3] {
+        std::array::from_fn(|c| std::array::from_fn(|i| profile.matrix[i][c]))
+    }
+
+    #[test]
+    fn parses_the_embedded_profiles() {
+        // Colorants as published in the ICC sRGB and Apple Display P3 profiles
+        let expected = [
+            (
+                OutputProfile::Srgb,
+                "sRGB",
+                [
+                    [0.4361, 0.2225, 0.0139],
+                    [0.3851, 0.7169, 0.0971],
+                    [0.1431, 0.0606, 0.7141],
+                ],
+            ),
+            (
+                OutputProfile::DisplayP3,
+                "Display P3",
+                [
+                    [0.5151, 0.2412, -0.0011],
+                    [0.2920, 0.6922, 0.0419],
+                    [0.1571, 0.0666, 0.7841],
+                ],
+            ),
+        ];
+        for (output, description, primaries) in expected {
+            let profile = IccProfile::parse(&output.icc_bytes()).unwrap();
+            assert_eq!(profile.description(), Some(description));
+            for (actual, expected) in colorants(&profile)
+                .iter()
+                .flatten()
+                .zip(primaries.iter().flatten())
+            {
+                assert!(
+                    (actual - expected).abs() < 5e-4,
+                    "{}: {:?}",
+                    description,
+                    colorants(&profile)
+                );
+            }
+
+            // Both use the sRGB tone curve on every channel
+            for curve in &profile.curves {
+                for v in [0.0, 0.02, 0.18, 0.5, 1.0] {
+                    let linear = ColorSpace::Srgb.to_linear(v);
+                    assert!(
+                        (curve.eval(v) - linear).abs() < 1e-4,
+                        "{}: {}",
+                        description,
+                        v
+                    );
+                }
+            }
+            assert!(profile.conversion_to(output.color_space()).is_identity());
+        }
+
+        let p3 = IccProfile::parse(&OutputProfile::DisplayP3.icc_bytes()).unwrap();
+        assert!(!p3.conversion_to(ColorSpace::Srgb).is_identity());
+    }
+
+    #[test]
+    fn rejects_truncated_and_corrupt_profiles() {
+        let bytes = OutputProfile::DisplayP3.icc_bytes();
+        for len in 0..bytes.len() {
+            let _ = IccProfile::parse(&bytes[..len]);
+        }
+        assert!(IccProfile::parse(&bytes[..131]).is_err());
+        assert!(IccProfile::parse(&bytes[..bytes.len() - 4]).is_err());
+
+        // Overwrite each byte in turn with values that break signatures, counts,
+        // offsets and lengths
+        for i in 0..bytes.len() {
+            for v in [0x00, 0x7f, 0xff] {
+                let mut corrupt = bytes.clone();
+                corrupt[i] = v;
+                let _ = IccProfile::parse(&corrupt);
+            }
+        }
+
+        assert!(IccProfile::parse(&[]).is_err());
+        assert!(IccProfile::parse(&[0xff; 512]).is_err());
+        let garbage: Vec<u8> = (0..4096u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 24) as u8)
+            .collect();
+        assert!(IccProfile::parse(&garbage).is_err());
+    }
+}
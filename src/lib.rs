@@ -1 +1,15 @@
-pub mod lut3d;
\ No newline at end of file
+pub mod adjustments;
+pub mod cdl;
+pub mod clf;
+pub mod color_space;
+pub mod dither;
+pub mod icc;
+pub mod lut1d;
+pub mod lut3d;
+pub mod lut_analysis;
+pub mod lut_batch;
+pub mod lut_cache;
+pub mod lut_compact;
+pub mod lut_error;
+pub mod lut_fit;
+pub mod lut_inverse;
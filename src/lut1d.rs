@@ -0,0 +1,94 @@
+/// A 1D Look-Up Table (LUT) applying an independent curve to each RGB channel.
+///
+/// 1D LUTs appear in .cube files either on their own (`LUT_1D_SIZE` only) or as a
+/// shaper placed in front of a 3D LUT, where they typically linearize or
+/// log-encode the input so the 3D lattice is sampled evenly.
+#[derive(Clone)]
+pub struct Lut1D {
+    /// The actual curve data stored as RGB triplets, one entry per sample point
+    data: Vec<[f32; 3]>,
+    /// The minimum input domain values for R, G, B channels (typically [0,0,0])
+    domain_min: [f32; 3],
+    /// The maximum input domain values for R, G, B channels (typically [1,1,1])
+    domain_max: [f32; 3],
+}
+
+impl Lut1D {
+    /// Creates a 1D LUT from its sample points and input domain.
+    ///
+    /// # Arguments
+    /// * `data` - Output RGB values for evenly spaced inputs across the domain
+    /// * `domain_min` - The input value mapped to the first sample, per channel
+    /// * `domain_max` - The input value mapped to the last sample, per channel
+    ///
+    /// # Panics
+    /// Panics if `data` contains fewer than 2 samples
+    pub fn new(data: Vec<[f32; 3]>, domain_min: [f32; 3], domain_max: [f32; 3]) -> Self {
+        assert!(data.len() >= 2, "A 1D LUT needs at least 2 samples");
+        Lut1D {
+            data,
+            domain_min,
+            domain_max,
+        }
+    }
+
+    /// Number of sample points per channel.
+    pub fn size(&self) -> usize {
+        self.data.len()
+    }
+
+    /// The curve's sample points.
+    pub fn data(&self) -> &[[f32; 3]] {
+        &self.data
+    }
+
+    /// The input value mapped to the first sample, per channel.
+    pub fn domain_min(&self) -> [f32; 3] {
+        self.domain_min
+    }
+
+    /// The input value mapped to the last sample, per channel.
+    pub fn domain_max(&self) -> [f32; 3] {
+        self.domain_max
+    }
+
+    /// Applies the curve to an RGB color using linear interpolation between samples.
+    ///
+    /// Inputs outside the domain are clamped to the first or last sample.
+    ///
+    /// # Arguments
+    /// * `rgb` - Input RGB values
+    ///
+    /// # Returns
+    /// The transformed RGB values
+    pub fn apply(&self, rgb: [f32; 3]) -> [f32; 3] {
+        self.eval(rgb, false)
+    }
+
+    /// Applies the curve like [`Lut1D::apply`], but continues the first and last
+    /// segments in a straight line for inputs outside the domain.
+    ///
+    /// # Arguments
+    /// * `rgb` - Input RGB values
+    ///
+    /// # Returns
+    /// The transformed RGB values
+    pub fn extrapolate(&self, rgb: [f32; 3]) -> [f32; 3] {
+        self.eval(rgb, true)
+    }
+
+    /// Interpolates the curve, clamping or extending inputs outside the domain.
+    fn eval(&self, rgb: [f32; 3], extend: bool) -> [f32; 3] {
+        let last = self.data.len() - 1;
+        let f = last as f32;
+        std::array::from_fn(|c| {
+            let (min, max) = (self.domain_min[c], self.domain_max[c]);
+            let n = (rgb[c] - min) / (max - min);
+            let x = if extend { n * f } else { n.clamp(0.0, 1.0) * f };
+            // Inputs beyond the domain use the edge segment, with t outside 0..1
+            let i0 = (x.floor().max(0.0) as usize).min(last - 1);
+            let t = x - i0 as f32;
+            self.data[i0][c] * (1.0 - t) + self.data[i0 + 1][c] * t
+        })
+    }
+}
@@ -1,15 +1,221 @@
 use std::{
-    fs,
     fs::File,
-    io::{self, BufRead, BufReader, Error},
+    io::{self, BufRead, BufReader, BufWriter, Error, Write},
     path::Path,
+    sync::atomic::{AtomicUsize, Ordering},
 };
 
+use rayon::prelude::*;
+use serde::Deserialize;
+
+use crate::adjustments::{self, Adjustments};
+use crate::cdl::{self, Cdl, CdlPlacement};
+use crate::clf;
+use crate::color_space::{self, ColorSpace};
+use crate::lut_analysis::{self, LutReport};
+use crate::lut_batch::{self, PixelBuffer};
+use crate::lut_cache::{self, TableData, TableHeader};
+use crate::lut_compact::{self, CompactLut};
+use crate::lut_error::{LutError, ParseMode};
+use crate::lut_fit::{self, FittedLut};
+use crate::lut_inverse::{self, InverseLut};
+use crate::lut1d::Lut1D;
+
+/// LUT file extensions understood by [`Lut3D::load`] (lowercase, without the dot).
+///
+/// Image extensions are loaded as Hald CLUTs; CLF transforms are baked into a lattice.
+pub const LUT_EXTENSIONS: [&str; 6] = ["cube", "3dl", "clf", "png", "tif", "tiff"];
+
+/// Interpolation method used to sample between the lattice points of a [`Lut3D`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Interpolation {
+    /// Snap to the closest lattice point (fast, but produces visible stepping)
+    Nearest,
+    /// Blend the 8 corners of the enclosing lattice cell
+    #[default]
+    Trilinear,
+    /// Blend the 4 corners of the enclosing tetrahedron (Resolve/OCIO default)
+    Tetrahedral,
+}
+
+impl Interpolation {
+    /// All interpolation methods, in the order they are offered in the GUI.
+    pub const ALL: [Interpolation; 3] = [
+        Interpolation::Nearest,
+        Interpolation::Trilinear,
+        Interpolation::Tetrahedral,
+    ];
+
+    /// Short lowercase name used in configuration files and cache file names.
+    pub fn name(self) -> &'static str {
+        match self {
+            Interpolation::Nearest => "nearest",
+            Interpolation::Trilinear => "trilinear",
+            Interpolation::Tetrahedral => "tetrahedral",
+        }
+    }
+}
+
+/// How a [`Lut3D`] handles input colors outside its `DOMAIN_MIN`..`DOMAIN_MAX`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OutOfDomain {
+    /// Clamp each channel to the domain, so colors beyond it get the edge values
+    #[default]
+    Clamp,
+    /// Continue the edge cells of the lattice (and shaper) in a straight line;
+    /// nearest-neighbor lookups still clamp, as they have no slope to follow
+    Extrapolate,
+    /// Leave colors with any channel outside the domain unchanged
+    Passthrough,
+}
+
+impl OutOfDomain {
+    /// All out-of-domain modes, in the order they are offered in the GUI.
+    pub const ALL: [OutOfDomain; 3] = [
+        OutOfDomain::Clamp,
+        OutOfDomain::Extrapolate,
+        OutOfDomain::Passthrough,
+    ];
+
+    /// Short lowercase name used in configuration files and cache file names.
+    pub fn name(self) -> &'static str {
+        match self {
+            OutOfDomain::Clamp => "clamp",
+            OutOfDomain::Extrapolate => "extrapolate",
+            OutOfDomain::Passthrough => "passthrough",
+        }
+    }
+}
+
+/// Settings controlling how a [`Lut3D`] is applied to colors.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ApplyOptions {
+    /// Interpolation method used between lattice points
+    pub interpolation: Interpolation,
+    /// Blend between the original (0.0) and the fully graded (1.0) color
+    pub strength: f32,
+}
+
+impl Default for ApplyOptions {
+    fn default() -> Self {
+        ApplyOptions {
+            interpolation: Interpolation::default(),
+            strength: 1.0,
+        }
+    }
+}
+
+/// Largest integer code value representable with `bits` bits.
+fn bit_depth_max(bits: u32) -> f32 {
+    ((1u64 << bits.min(32)) - 1) as f32
+}
+
+/// Guesses the integer bit depth of a .3dl table from its largest code value.
+///
+/// Returns the largest code value of the smallest common depth (8, 10, 12, 14
+/// or 16 bits) that can hold `max_value`.
+fn likely_bit_depth_max(max_value: u32) -> f32 {
+    [8, 10, 12, 14, 16]
+        .into_iter()
+        .map(bit_depth_max)
+        .find(|&m| max_value as f32 <= m)
+        .unwrap_or_else(|| bit_depth_max(32))
+}
+
+/// Splits a line into whitespace-separated tokens with their 1-based column.
+fn tokenize(line: &str) -> Vec<(usize, &str)> {
+    let mut tokens = Vec::new();
+    let mut start = None;
+    for (column, (i, c)) in line.char_indices().enumerate() {
+        match (c.is_whitespace(), start) {
+            (false, None) => start = Some((column + 1, i)),
+            (true, Some((col, s))) => {
+                tokens.push((col, &line[s..i]));
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some((col, s)) = start {
+        tokens.push((col, &line[s..]));
+    }
+    tokens
+}
+
+/// Parses exactly `N` finite floats from a line's tokens.
+///
+/// `end` is the column just past the last character, reported when values are missing.
+fn parse_floats<const N: usize>(
+    line: usize,
+    tokens: &[(usize, &str)],
+    end: usize,
+) -> Result<[f32; N], LutError> {
+    if tokens.len() != N {
+        return Err(LutError::MalformedLine {
+            line,
+            column: tokens.get(N).map_or(end, |t| t.0),
+            message: format!("expected {} values, found {}", N, tokens.len()),
+        });
+    }
+    let mut values = [0.0; N];
+    for (value, &(column, token)) in values.iter_mut().zip(tokens) {
+        *value = token
+            .parse::<f32>()
+            .ok()
+            .filter(|v| v.is_finite())
+            .ok_or_else(|| LutError::InvalidValue {
+                line,
+                column,
+                value: token.to_string(),
+            })?;
+    }
+    Ok(values)
+}
+
+/// Parses a single lattice size between 2 and `max` from a keyword's arguments.
+fn parse_size(
+    line: usize,
+    tokens: &[(usize, &str)],
+    end: usize,
+    max: usize,
+) -> Result<usize, LutError> {
+    if tokens.len() != 1 {
+        return Err(LutError::MalformedLine {
+            line,
+            column: tokens.get(1).map_or(end, |t| t.0),
+            message: format!("expected 1 value, found {}", tokens.len()),
+        });
+    }
+    let (column, token) = tokens[0];
+    token
+        .parse::<usize>()
+        .ok()
+        .filter(|v| (2..=max).contains(v))
+        .ok_or_else(|| LutError::InvalidValue {
+            line,
+            column,
+            value: token.to_string(),
+        })
+}
+
+/// Converts a normalized RGB triplet to the nearest 8-bit values, clamping to [0, 1].
+pub(crate) fn to_u8(c: [f32; 3]) -> [u8; 3] {
+    c.map(unit_to_u8)
+}
+
+/// Converts a normalized channel value to the nearest 8-bit value, clamping to [0, 1].
+pub(crate) fn unit_to_u8(v: f32) -> u8 {
+    (v.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
 /// A 3D Look-Up Table (LUT) for color grading and transformation.
 /// 
 /// This structure represents a cubic color transformation table that maps
 /// input RGB values to output RGB values. LUTs are commonly used in color
 /// grading workflows to apply specific color transformations to images and videos.
+#[derive(Clone)]
 pub struct Lut3D {
     /// The size of each dimension of the cubic LUT (e.g., 32 means 32x32x32)
     size: usize,
@@ -19,126 +225,962 @@ pub struct Lut3D {
     domain_min: [f32; 3],
     /// The maximum input domain values for R, G, B channels (typically [1,1,1])
     domain_max: [f32; 3],
+    /// Optional per-channel curve applied before the 3D lattice lookup
+    shaper: Option<Lut1D>,
+    /// The LUT title from the `TITLE` keyword, if present
+    title: Option<String>,
+    /// Comment lines from the source file, without the leading `#`
+    comments: Vec<String>,
+    /// How inputs outside the domain are handled
+    out_of_domain: OutOfDomain,
 }
 
 impl Lut3D {
+    /// Creates an identity 3D LUT that maps every color to itself.
+    ///
+    /// # Arguments
+    /// * `size` - The size of each dimension of the lattice (at least 2)
+    pub fn identity(size: usize) -> Self {
+        let f = (size - 1) as f32;
+        let mut data = Vec::with_capacity(size * size * size);
+        for b in 0..size {
+            for g in 0..size {
+                for r in 0..size {
+                    data.push([r as f32 / f, g as f32 / f, b as f32 / f]);
+                }
+            }
+        }
+        Lut3D {
+            size,
+            data,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            shaper: None,
+            title: None,
+            comments: Vec::new(),
+            out_of_domain: OutOfDomain::default(),
+        }
+    }
+
+    /// Creates a 3D LUT from lattice data over the default [0, 1] domain.
+    ///
+    /// # Arguments
+    /// * `size` - The size of each dimension of the lattice (at least 2)
+    /// * `data` - `size³` RGB triplets ordered with red varying fastest, then green, then blue
+    ///
+    /// # Errors
+    /// Returns an error if `size` is smaller than 2 or `data` has the wrong length
+    pub fn from_data(size: usize, data: Vec<[f32; 3]>) -> Result<Self, Error> {
+        if size < 2 {
+            return Err(Error::new(
+                io::ErrorKind::InvalidInput,
+                "LUT sizes must be at least 2.",
+            ));
+        }
+        if data.len() != size * size * size {
+            return Err(Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "LUT data size mismatch. Expected {} entries, found {}",
+                    size * size * size,
+                    data.len()
+                ),
+            ));
+        }
+        Ok(Lut3D {
+            size,
+            data,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            shaper: None,
+            title: None,
+            comments: Vec::new(),
+            out_of_domain: OutOfDomain::default(),
+        })
+    }
+
+    /// Creates a 3D LUT over the default [0, 1] domain by evaluating a function at
+    /// every lattice point, in parallel.
+    ///
+    /// # Arguments
+    /// * `size` - The size of each dimension of the lattice (at least 2)
+    /// * `f` - Maps the normalized coordinates of a lattice point to its output
+    ///
+    /// # Errors
+    /// Returns an error if `size` is smaller than 2
+    pub fn bake(size: usize, f: impl Fn([f32; 3]) -> [f32; 3] + Sync) -> Result<Self, Error> {
+        if size < 2 {
+            return Err(Error::new(
+                io::ErrorKind::InvalidInput,
+                "LUT sizes must be at least 2.",
+            ));
+        }
+        let mut lut = Lut3D::identity(size);
+        lut.data.par_iter_mut().for_each(|v| *v = f(*v));
+        Ok(lut)
+    }
+
+    /// Creates a new 3D LUT from a .cube file, skipping malformed lines.
+    ///
+    /// This is [`Lut3D::from_cube_with`] in [`ParseMode::Lenient`] with the
+    /// diagnostics discarded.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the .cube file to load
+    ///
+    /// # Returns
+    /// A `Result` containing the loaded `Lut3D` or a `LutError` if loading fails
+    pub fn from_cube(path: &str) -> Result<Self, LutError> {
+        Self::from_cube_with(path, ParseMode::Lenient).map(|(lut, _)| lut)
+    }
+
     /// Creates a new 3D LUT from a .cube file.
     /// 
     /// The .cube format is a standard format for 3D LUTs that includes metadata
     /// such as size, domain range, and the actual color transformation data.
+    /// Resolve-style files may also contain a 1D LUT (`LUT_1D_SIZE`), either on
+    /// its own or as a shaper in front of the 3D LUT. The 1D entries come first
+    /// in the data section and are applied before the 3D lattice. A file with
+    /// only a 1D LUT is loaded as that shaper followed by an identity lattice.
+    ///
+    /// In strict mode any malformed line or unknown keyword fails the load. In
+    /// lenient mode those lines are skipped and returned as diagnostics; a
+    /// skipped data line still makes the load fail with a size mismatch, since
+    /// guessing its values would corrupt the LUT.
     /// 
     /// # Arguments
     /// * `path` - Path to the .cube file to load
+    /// * `mode` - Whether malformed lines are errors or diagnostics
     /// 
     /// # Returns
-    /// A `Result` containing the loaded `Lut3D` or an `Error` if loading fails
+    /// A `Result` containing the loaded `Lut3D` and the diagnostics collected in
+    /// lenient mode, or a `LutError` if loading fails
     /// 
     /// # Errors
     /// Returns an error if:
     /// - The file cannot be opened or read
-    /// - The file format is invalid (missing LUT_3D_SIZE or malformed data)
-    /// - The data size doesn't match the declared LUT size
-    pub fn from_cube(path: &str) -> Result<Self, Error> {
+    /// - The file is missing both LUT_1D_SIZE and LUT_3D_SIZE
+    /// - The data size doesn't match the declared LUT sizes
+    /// - In strict mode, any line is malformed or starts with an unknown keyword
+    pub fn from_cube_with(path: &str, mode: ParseMode) -> Result<(Self, Vec<LutError>), LutError> {
         let file = File::open(path)?;
         let reader = BufReader::new(file);
 
         let mut size = 0;
+        let mut size_1d = 0;
         let mut data = Vec::new();
         let mut domain_min = [0.0; 3]; // Default domain minimum
         let mut domain_max = [1.0; 3]; // Default domain maximum
+        let mut title = None;
+        let mut comments = Vec::new();
+        // Input range of the 1D LUT, when given separately via LUT_1D_INPUT_RANGE
+        let mut range_1d: Option<(f32, f32)> = None;
+
+        // Records a recoverable problem, or fails immediately in strict mode
+        let mut diagnostics = Vec::new();
+        // First data line skipped in lenient mode, reported if the entry count is off
+        let mut first_skipped_data = None;
+        let mut report = |e: LutError| match mode {
+            ParseMode::Strict => Err(e),
+            ParseMode::Lenient => {
+                diagnostics.push(e);
+                Ok(())
+            }
+        };
 
         // Parse each line of the .cube file
-        for line in reader.lines() {
-            let line = line?;
-            let line = line.trim();
-
-            if line.starts_with("LUT_3D_SIZE") {
-                // Extract the cubic dimension size (e.g., "LUT_3D_SIZE 32")
-                let parts: Vec<&str> = line.split_whitespace().collect();
-                if parts.len() >= 2 {
-                    size = parts[1].parse::<usize>().unwrap_or_else(|_| {
-                        eprintln!("Warning: Failed to parse LUT_3D_SIZE value: {}", parts[1]);
-                        0
-                    });
-                }
-            } else if line.starts_with("DOMAIN_MIN") {
-                // Parse minimum domain values for input normalization
-                let parts: Vec<f32> = line
-                    .split_whitespace()
-                    .skip(1) // Skip the "DOMAIN_MIN" keyword
-                    .map(|s| {
-                        s.parse::<f32>().unwrap_or_else(|_| {
-                            eprintln!("Warning: Failed to parse DOMAIN_MIN value: {}", s);
-                            0.0
-                        })
-                    })
-                    .collect();
-                if parts.len() == 3 {
-                    domain_min.copy_from_slice(&parts);
-                }
-            } else if line.starts_with("DOMAIN_MAX") {
-                // Parse maximum domain values for input normalization
-                let parts: Vec<f32> = line
-                    .split_whitespace()
-                    .skip(1) // Skip the "DOMAIN_MAX" keyword
-                    .map(|s| {
-                        s.parse::<f32>().unwrap_or_else(|_| {
-                            eprintln!("Warning: Failed to parse DOMAIN_MAX value: {}", s);
-                            1.0
-                        })
-                    })
-                    .collect();
-                if parts.len() == 3 {
-                    domain_max.copy_from_slice(&parts);
+        for (index, line) in reader.lines().enumerate() {
+            let line_no = index + 1;
+            let raw = line?;
+            let line = raw.trim();
+
+            if line.is_empty() {
+                // Skip empty lines
+                continue;
+            }
+            if let Some(comment) = line.strip_prefix('#') {
+                // Keep comments so they can be written back by to_cube
+                comments.push(comment.to_string());
+                continue;
+            }
+
+            let tokens = tokenize(&raw);
+            let end = raw.trim_end().chars().count() + 1;
+            let (column, keyword) = tokens[0];
+            let args = &tokens[1..];
+
+            if keyword.starts_with(|c: char| c.is_ascii_digit() || "+-.".contains(c)) {
+                // RGB color data line (three space-separated float values)
+                match parse_floats::<3>(line_no, &tokens, end) {
+                    Ok(rgb) => data.push(rgb),
+                    Err(e) => {
+                        report(e)?;
+                        first_skipped_data.get_or_insert(line_no);
+                    }
                 }
-            } else if line.is_empty() || line.starts_with('#') || line.starts_with("TITLE") {
-                // Skip empty lines, comments, and title metadata
                 continue;
-            } else {
-                // Parse RGB color data lines (three space-separated float values)
-                let vals: Vec<f32> = line
-                    .split_whitespace()
-                    .map(|s| {
-                        s.parse::<f32>().unwrap_or_else(|_| {
-                            eprintln!("Warning: Failed to parse color data value: {}", s);
-                            0.0
-                        })
-                    })
-                    .collect();
-                if vals.len() == 3 {
-                    data.push([vals[0], vals[1], vals[2]]);
+            }
+
+            match keyword {
+                "TITLE" => {
+                    // Title metadata, e.g. TITLE "SONY_CYBERSHOT_DSC-WX5"
+                    let rest = line["TITLE".len()..].trim();
+                    title = Some(rest.trim_matches('"').to_string());
                 }
+                "LUT_3D_SIZE" => match parse_size(line_no, args, end, 256) {
+                    Ok(value) => size = value,
+                    Err(e) => report(e)?,
+                },
+                "LUT_1D_SIZE" => match parse_size(line_no, args, end, 65536) {
+                    Ok(value) => size_1d = value,
+                    Err(e) => report(e)?,
+                },
+                // Minimum and maximum domain values for input normalization
+                "DOMAIN_MIN" => match parse_floats::<3>(line_no, args, end) {
+                    Ok(values) => domain_min = values,
+                    Err(e) => report(e)?,
+                },
+                "DOMAIN_MAX" => match parse_floats::<3>(line_no, args, end) {
+                    Ok(values) => domain_max = values,
+                    Err(e) => report(e)?,
+                },
+                // Resolve equivalent of DOMAIN_MIN/DOMAIN_MAX with one value for all channels
+                "LUT_3D_INPUT_RANGE" => match parse_floats::<2>(line_no, args, end) {
+                    Ok([min, max]) => {
+                        domain_min = [min; 3];
+                        domain_max = [max; 3];
+                    }
+                    Err(e) => report(e)?,
+                },
+                "LUT_1D_INPUT_RANGE" => match parse_floats::<2>(line_no, args, end) {
+                    Ok([min, max]) => range_1d = Some((min, max)),
+                    Err(e) => report(e)?,
+                },
+                _ => report(LutError::UnknownKeyword {
+                    line: line_no,
+                    column,
+                    keyword: keyword.to_string(),
+                })?,
             }
         }
-        
+
         // Validate the parsed data
+        if size == 0 && size_1d == 0 {
+            return Err(LutError::MissingSize);
+        }
+        
+        // Ensure data size matches expected dimensions (1D entries come first)
+        let expected = size_1d + size * size * size;
+        if data.len() != expected {
+            // A skipped data line is the likely cause, so report it instead of the count
+            if let Some(line) = first_skipped_data
+                && let Some(i) = diagnostics.iter().position(|d| d.line() == Some(line))
+            {
+                return Err(diagnostics.swap_remove(i));
+            }
+            return Err(LutError::SizeMismatch {
+                expected,
+                found: data.len(),
+            });
+        }
+
+        let lattice = data.split_off(size_1d);
         if size == 0 {
+            // A pure 1D LUT: DOMAIN_MIN/DOMAIN_MAX describe the curve's input range
+            let (min_1d, max_1d) = match range_1d {
+                Some((min, max)) => ([min; 3], [max; 3]),
+                None => (domain_min, domain_max),
+            };
+            let mut lut = Lut3D::identity(2);
+            lut.shaper = Some(Lut1D::new(data, min_1d, max_1d));
+            lut.title = title;
+            lut.comments = comments;
+            return Ok((lut, diagnostics));
+        }
+
+        let shaper = (size_1d > 0).then(|| {
+            let (min, max) = range_1d.unwrap_or((0.0, 1.0));
+            Lut1D::new(data, [min; 3], [max; 3])
+        });
+        let lut = Lut3D {
+            size,
+            data: lattice,
+            domain_min,
+            domain_max,
+            shaper,
+            title,
+            comments,
+            out_of_domain: OutOfDomain::default(),
+        };
+        Ok((lut, diagnostics))
+    }
+
+    /// Creates a new 3D LUT from an Autodesk/Adobe .3dl file.
+    ///
+    /// A .3dl file starts with a shaper line listing the integer input positions of
+    /// the lattice (its length gives the LUT size), followed by integer RGB triplets
+    /// ordered with blue varying fastest. Lustre-style files may also declare
+    /// `Mesh <in_bits> <out_bits>`; otherwise bit depths are inferred from the
+    /// largest value found in the shaper and the data.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the .3dl file to load
+    ///
+    /// # Returns
+    /// A `Result` containing the loaded `Lut3D` or a `LutError` if loading fails
+    ///
+    /// # Errors
+    /// Returns an error if:
+    /// - The file cannot be opened or read
+    /// - The shaper line is missing, non-uniform, or malformed
+    /// - A data line doesn't hold exactly three integers
+    /// - The data size doesn't match the lattice size declared by the shaper
+    pub fn from_3dl(path: &str) -> Result<Self, LutError> {
+        let file = File::open(path)?;
+        let reader = BufReader::new(file);
+
+        let mut mesh_out_bits: Option<u32> = None;
+        let mut shaper: Vec<u32> = Vec::new();
+        let mut raw: Vec<[u32; 3]> = Vec::new();
+        let mut comments = Vec::new();
+
+        for (index, line) in reader.lines().enumerate() {
+            let line_no = index + 1;
+            let raw_line = line?;
+            let line = raw_line.trim();
+
+            if let Some(comment) = line.strip_prefix('#') {
+                comments.push(comment.to_string());
+                continue;
+            }
+            if line.is_empty() {
+                continue;
+            }
+
+            let tokens = tokenize(&raw_line);
+            let end = raw_line.trim_end().chars().count() + 1;
+            if tokens[0].1.eq_ignore_ascii_case("Mesh") {
+                // Lustre header: "Mesh <mesh bits> <output bits>"; only the output depth
+                // matters here since the lattice size comes from the shaper line
+                if let Some(&(column, token)) = tokens.get(2) {
+                    let out_bits = token
+                        .parse::<u32>()
+                        .ok()
+                        .filter(|bits| (1..=32).contains(bits))
+                        .ok_or_else(|| LutError::InvalidValue {
+                            line: line_no,
+                            column,
+                            value: token.to_string(),
+                        })?;
+                    mesh_out_bits = Some(out_bits);
+                }
+                continue;
+            }
+            if !tokens[0].1.starts_with(|c: char| c.is_ascii_digit()) {
+                // Other keywords ("3DMESH", "LUT8", "GAMMA", ...) carry no lattice data
+                continue;
+            }
+
+            let mut vals = Vec::with_capacity(tokens.len());
+            for &(column, token) in &tokens {
+                let value = token.parse::<u32>().map_err(|_| LutError::InvalidValue {
+                    line: line_no,
+                    column,
+                    value: token.to_string(),
+                })?;
+                vals.push(value);
+            }
+
+            if shaper.is_empty() {
+                // The first numeric line is always the shaper
+                shaper = vals;
+            } else if vals.len() == 3 {
+                raw.push([vals[0], vals[1], vals[2]]);
+            } else {
+                return Err(LutError::MalformedLine {
+                    line: line_no,
+                    column: tokens.get(3).map_or(end, |t| t.0),
+                    message: format!("expected 3 values, found {}", vals.len()),
+                });
+            }
+        }
+
+        let size = shaper.len();
+        if size < 2 {
+            return Err(LutError::Invalid(
+                "The .3dl shaper line is missing or invalid.".to_string(),
+            ));
+        }
+        if raw.len() != size * size * size {
+            return Err(LutError::SizeMismatch {
+                expected: size * size * size,
+                found: raw.len(),
+            });
+        }
+
+        // Resolve the integer scale of the input and output values
+        let shaper_max = shaper.iter().copied().max().unwrap_or(0);
+        let data_max = raw.iter().flatten().copied().max().unwrap_or(0);
+        let in_max = likely_bit_depth_max(shaper_max);
+        let out_max = match mesh_out_bits {
+            Some(out_bits) => bit_depth_max(out_bits),
+            None => likely_bit_depth_max(data_max),
+        };
+
+        // The lattice must be evenly spaced to be represented as a Lut3D
+        let first = shaper[0] as f32;
+        let step = (shaper[size - 1] as f32 - first) / (size - 1) as f32;
+        let uniform = shaper
+            .iter()
+            .enumerate()
+            .all(|(i, &v)| (v as f32 - (first + step * i as f32)).abs() <= 1.0);
+        if step <= 0.0 || !uniform {
+            return Err(LutError::Invalid(
+                "Non-uniform .3dl shaper lines are not supported.".to_string(),
+            ));
+        }
+
+        // Reorder from blue-fastest (.3dl) to red-fastest (.cube) layout
+        let mut data = vec![[0.0f32; 3]; size * size * size];
+        for (i, rgb) in raw.iter().enumerate() {
+            let b = i % size;
+            let g = (i / size) % size;
+            let r = i / (size * size);
+            data[r + g * size + b * size * size] = [
+                rgb[0] as f32 / out_max,
+                rgb[1] as f32 / out_max,
+                rgb[2] as f32 / out_max,
+            ];
+        }
+
+        let domain_min = first / in_max;
+        let domain_max = shaper[size - 1] as f32 / in_max;
+        Ok(Lut3D {
+            size,
+            data,
+            domain_min: [domain_min; 3],
+            domain_max: [domain_max; 3],
+            shaper: None,
+            title: None,
+            comments,
+            out_of_domain: OutOfDomain::default(),
+        })
+    }
+
+    /// Creates a new 3D LUT from a Hald CLUT image.
+    ///
+    /// A Hald CLUT of level `L` is a square image of `L³ × L³` pixels holding an
+    /// `L²`-point lattice, with pixels in row-major order and red varying fastest
+    /// (the same ordering as .cube data). The level is detected from the image
+    /// dimensions; 8-bit and 16-bit images are both supported.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the Hald image (PNG, TIFF, ...)
+    ///
+    /// # Returns
+    /// A `Result` containing the loaded `Lut3D` or an `Error` if loading fails
+    ///
+    /// # Errors
+    /// Returns an error if:
+    /// - The image cannot be opened or decoded
+    /// - The image dimensions don't correspond to a Hald level
+    pub fn from_hald(path: &str) -> Result<Self, LutError> {
+        let img = image::open(path)?.to_rgb32f();
+        let (width, height) = img.dimensions();
+
+        // Find the level L such that width == height == L³
+        let level = (2..=16u32).find(|l| l * l * l == width);
+        let level = match level {
+            Some(l) if width == height => l as usize,
+            _ => {
+                return Err(LutError::Invalid(format!(
+                    "Image size {}x{} is not a Hald CLUT (expected L³ x L³ pixels)",
+                    width, height
+                )));
+            }
+        };
+
+        let data = img.pixels().map(|p| p.0).collect();
+        Ok(Lut3D {
+            size: level * level,
+            data,
+            domain_min: [0.0; 3],
+            domain_max: [1.0; 3],
+            shaper: None,
+            title: None,
+            comments: Vec::new(),
+            out_of_domain: OutOfDomain::default(),
+        })
+    }
+
+    /// Exports the LUT as a 16-bit Hald CLUT PNG.
+    ///
+    /// The LUT (including any shaper) is sampled on an `level²`-point lattice over
+    /// the [0, 1] input range, so a LUT with a non-default domain is baked into the
+    /// standard Hald layout.
+    ///
+    /// # Arguments
+    /// * `path` - Destination path of the PNG image
+    /// * `level` - Hald level between 2 and 16 (8 gives a 512×512 image and a 64³ lattice)
+    /// * `interpolation` - Interpolation used to sample this LUT at the Hald lattice points
+    ///
+    /// # Errors
+    /// Returns an error if the level is out of range or the image cannot be written
+    pub fn to_hald(&self, path: &str, level: u32, interpolation: Interpolation) -> io::Result<()> {
+        if !(2..=16).contains(&level) {
             return Err(Error::new(
-                io::ErrorKind::InvalidData,
-                "LUT_3D_SIZE is missing or invalid.",
+                io::ErrorKind::InvalidInput,
+                format!("Hald level must be between 2 and 16, got {}", level),
             ));
         }
-        
-        // Ensure data size matches expected cubic dimensions
-        if data.len() != size * size * size {
+        let size = (level * level) as usize;
+        let side = level * level * level;
+
+        // Hald pixels use the same red-fastest order as the lattice
+        let lattice = Lut3D::bake(size, |rgb| self.sample(rgb, interpolation))?;
+        let buf: Vec<u16> = lattice
+            .data
+            .iter()
+            .flatten()
+            .map(|v| (v.clamp(0.0, 1.0) * 65535.0).round() as u16)
+            .collect();
+
+        let img = image::ImageBuffer::<image::Rgb<u16>, _>::from_raw(side, side, buf)
+            .expect("Hald buffer size matches its dimensions");
+        img.save(path).map_err(Error::other)
+    }
+
+    /// Writes the LUT to a .cube file.
+    ///
+    /// The title, comment lines, and input domain are written back alongside the
+    /// lattice. A shaper is written as a Resolve-style 1D LUT preceding the 3D data
+    /// (or on its own if the lattice is only the identity placeholder of a 1D LUT).
+    ///
+    /// # Arguments
+    /// * `path` - Destination path of the .cube file
+    ///
+    /// # Errors
+    /// Returns an error if the file cannot be written, or if the shaper uses a
+    /// different input range per channel (which `LUT_1D_INPUT_RANGE` can't express)
+    pub fn to_cube(&self, path: &str) -> io::Result<()> {
+        let mut out = BufWriter::new(File::create(path)?);
+        let fmt3 = |v: [f32; 3]| format!("{:.6} {:.6} {:.6}", v[0], v[1], v[2]);
+        let is_uniform = |v: [f32; 3]| v[0] == v[1] && v[1] == v[2];
+
+        if let Some(title) = &self.title {
+            writeln!(out, "TITLE \"{}\"", title)?;
+        }
+        for comment in &self.comments {
+            writeln!(out, "#{}", comment)?;
+        }
+
+        let shaper_only = self.is_shaper_only();
+        match &self.shaper {
+            Some(shaper) if shaper_only => {
+                writeln!(out, "LUT_1D_SIZE {}", shaper.size())?;
+                writeln!(out, "DOMAIN_MIN {}", fmt3(shaper.domain_min()))?;
+                writeln!(out, "DOMAIN_MAX {}", fmt3(shaper.domain_max()))?;
+            }
+            Some(shaper) => {
+                let (min, max) = (shaper.domain_min(), shaper.domain_max());
+                if !is_uniform(min) || !is_uniform(max) {
+                    return Err(Error::new(
+                        io::ErrorKind::InvalidInput,
+                        "Shaper input ranges differ per channel and can't be written to .cube",
+                    ));
+                }
+                writeln!(out, "LUT_1D_SIZE {}", shaper.size())?;
+                writeln!(out, "LUT_1D_INPUT_RANGE {:.6} {:.6}", min[0], max[0])?;
+                writeln!(out, "LUT_3D_SIZE {}", self.size)?;
+                if is_uniform(self.domain_min) && is_uniform(self.domain_max) {
+                    writeln!(
+                        out,
+                        "LUT_3D_INPUT_RANGE {:.6} {:.6}",
+                        self.domain_min[0], self.domain_max[0]
+                    )?;
+                } else {
+                    writeln!(out, "DOMAIN_MIN {}", fmt3(self.domain_min))?;
+                    writeln!(out, "DOMAIN_MAX {}", fmt3(self.domain_max))?;
+                }
+            }
+            None => {
+                writeln!(out, "LUT_3D_SIZE {}", self.size)?;
+                writeln!(out, "DOMAIN_MIN {}", fmt3(self.domain_min))?;
+                writeln!(out, "DOMAIN_MAX {}", fmt3(self.domain_max))?;
+            }
+        }
+
+        // 1D entries come first, followed by the 3D lattice (red varying fastest)
+        if let Some(shaper) = &self.shaper {
+            for v in shaper.data() {
+                writeln!(out, "{}", fmt3(*v))?;
+            }
+        }
+        if !shaper_only {
+            for v in &self.data {
+                writeln!(out, "{}", fmt3(*v))?;
+            }
+        }
+        out.flush()
+    }
+
+    /// Returns `true` for a pure 1D LUT, stored as a shaper followed by a 2-point
+    /// identity lattice with the default domain.
+    fn is_shaper_only(&self) -> bool {
+        self.shaper.is_some()
+            && self.size == 2
+            && self.domain_min == [0.0; 3]
+            && self.domain_max == [1.0; 3]
+            && self.data == Lut3D::identity(2).data
+    }
+
+    /// The size of each dimension of the lattice.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// The minimum input domain values for R, G, B channels.
+    pub fn domain_min(&self) -> [f32; 3] {
+        self.domain_min
+    }
+
+    /// The maximum input domain values for R, G, B channels.
+    pub fn domain_max(&self) -> [f32; 3] {
+        self.domain_max
+    }
+
+    /// The lattice output colors, red fastest, then green, then blue.
+    pub fn data(&self) -> &[[f32; 3]] {
+        &self.data
+    }
+
+    /// The 1D shaper applied before the lattice, if any.
+    pub fn shaper(&self) -> Option<&Lut1D> {
+        self.shaper.as_ref()
+    }
+
+    /// The LUT title (`TITLE` keyword), if any.
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Sets the LUT title written by [`Lut3D::to_cube`].
+    pub fn set_title(&mut self, title: impl Into<String>) {
+        self.title = Some(title.into());
+    }
+
+    /// Comment lines read from the source file, without the leading `#`.
+    pub fn comments(&self) -> &[String] {
+        &self.comments
+    }
+
+    /// Appends a comment line written by [`Lut3D::to_cube`].
+    pub fn add_comment(&mut self, comment: impl Into<String>) {
+        self.comments.push(comment.into());
+    }
+
+    /// How inputs outside the domain are handled.
+    pub fn out_of_domain(&self) -> OutOfDomain {
+        self.out_of_domain
+    }
+
+    /// Sets how inputs outside the domain are handled.
+    ///
+    /// The mode isn't stored in LUT files, so it has to be set after loading.
+    pub fn set_out_of_domain(&mut self, mode: OutOfDomain) {
+        self.out_of_domain = mode;
+    }
+
+    /// Composes several LUTs into a single baked LUT.
+    ///
+    /// Colors pass through the LUTs in order (`luts[0]` first). The result keeps the
+    /// first LUT's domain and shaper, and its lattice of `size` points per axis is
+    /// sampled in the first LUT's lattice space, so log shapers keep their precision.
+    ///
+    /// # Arguments
+    /// * `luts` - The LUTs to chain, applied in order
+    /// * `size` - The size of each dimension of the baked lattice (e.g. 33)
+    /// * `interpolation` - Interpolation used to sample each LUT in the chain
+    ///
+    /// # Returns
+    /// A `Result` containing the composite `Lut3D`
+    ///
+    /// # Errors
+    /// Returns an error if `luts` is empty or `size` is smaller than 2
+    pub fn compose(
+        luts: &[&Lut3D],
+        size: usize,
+        interpolation: Interpolation,
+    ) -> Result<Self, Error> {
+        let Some((first, rest)) = luts.split_first() else {
             return Err(Error::new(
-                io::ErrorKind::InvalidData,
-                format!(
-                    "LUT data size mismatch. Expected {} entries, found {}",
-                    size * size * size,
-                    data.len()
-                ),
+                io::ErrorKind::InvalidInput,
+                "At least one LUT is required for composition.",
             ));
+        };
+        let mut lut = Lut3D::bake(size, |n| {
+            let mut rgb = first.lattice_sample(n, interpolation);
+            for next in rest {
+                rgb = next.sample(rgb, interpolation);
+            }
+            rgb
+        })?;
+        lut.domain_min = first.domain_min;
+        lut.domain_max = first.domain_max;
+        lut.shaper = first.shaper.clone();
+        lut.out_of_domain = first.out_of_domain;
+
+        let titles: Vec<&str> = luts.iter().filter_map(|l| l.title()).collect();
+        if !titles.is_empty() {
+            lut.title = Some(titles.join(" + "));
         }
+        lut.comments.push(format!(
+            " Composite of {} LUTs baked at size {} ({})",
+            luts.len(),
+            size,
+            interpolation.name()
+        ));
+        Ok(lut)
+    }
 
+    /// Resamples the lattice to a different number of points per axis.
+    ///
+    /// The new lattice points are interpolated from the current lattice, while the
+    /// domain, shaper, title, and comments are kept, so e.g. a 65³ cube can be
+    /// reduced to 33³ for devices with fixed lattice-size requirements.
+    ///
+    /// # Arguments
+    /// * `size` - The size of each dimension of the resampled lattice
+    /// * `interpolation` - Interpolation used to sample the current lattice
+    ///
+    /// # Returns
+    /// A `Result` containing the resampled `Lut3D`
+    ///
+    /// # Errors
+    /// Returns an error if `size` is smaller than 2
+    pub fn resample(&self, size: usize, interpolation: Interpolation) -> Result<Self, Error> {
+        let lattice = Lut3D::bake(size, |n| self.lattice_sample(n, interpolation))?;
         Ok(Lut3D {
             size,
-            data,
-            domain_min,
-            domain_max,
+            data: lattice.data,
+            domain_min: self.domain_min,
+            domain_max: self.domain_max,
+            shaper: self.shaper.clone(),
+            title: self.title.clone(),
+            comments: self.comments.clone(),
+            out_of_domain: self.out_of_domain,
+        })
+    }
+
+    /// Computes an approximate inverse of this LUT, see [`lut_inverse::invert`].
+    pub fn invert(&self, size: usize, interpolation: Interpolation) -> Result<InverseLut, Error> {
+        lut_inverse::invert(self, size, interpolation)
+    }
+
+    /// Bakes this LUT into a compact table for 8-bit input, see [`lut_compact::bake`].
+    pub fn bake_compact(&self, size: usize, options: &ApplyOptions) -> Result<CompactLut, Error> {
+        lut_compact::bake(self, size, options)
+    }
+
+    /// Applies this LUT to every pixel of a buffer, see [`lut_batch::apply_to_buffer`].
+    pub fn apply_to_buffer(&self, buffer: PixelBuffer<'_>, options: &ApplyOptions) {
+        lut_batch::apply_to_buffer(self, buffer, options)
+    }
+
+    /// Bakes color space conversions around this LUT, see [`color_space::bake`].
+    pub fn with_color_spaces(
+        &self,
+        input: ColorSpace,
+        output: ColorSpace,
+        working: ColorSpace,
+        size: usize,
+        interpolation: Interpolation,
+    ) -> Result<Self, Error> {
+        color_space::bake(self, input, output, working, size, interpolation)
+    }
+
+    /// Bakes a CDL before or after this LUT, see [`cdl::bake`].
+    pub fn with_cdl(
+        &self,
+        cdl: &Cdl,
+        placement: CdlPlacement,
+        size: usize,
+        interpolation: Interpolation,
+    ) -> Result<Self, Error> {
+        cdl::bake(self, cdl, placement, size, interpolation)
+    }
+
+    /// Loads a `.clf` file and bakes it into a 3D LUT, see [`clf::load_lut`].
+    pub fn from_clf(path: &str) -> Result<Self, LutError> {
+        clf::load_lut(path)
+    }
+
+    /// Fits a LUT to an original and a graded image, see [`lut_fit::fit`].
+    pub fn fit(
+        before: &[f32],
+        after: &[f32],
+        size: usize,
+        smoothness: f32,
+    ) -> Result<FittedLut, Error> {
+        lut_fit::fit(before, after, size, smoothness)
+    }
+
+    /// Generates a LUT from parametric adjustments, see [`adjustments::bake`].
+    pub fn from_adjustments(adjustments: &Adjustments, size: usize) -> Result<Self, Error> {
+        adjustments::bake(adjustments, size)
+    }
+
+    /// Analyzes this LUT for problems, see [`lut_analysis::analyze`].
+    pub fn analyze(&self) -> LutReport {
+        lut_analysis::analyze(self)
+    }
+
+    /// Loads a LUT file, choosing the parser from the file extension.
+    ///
+    /// Supported extensions are listed in [`LUT_EXTENSIONS`] (case-insensitive).
+    ///
+    /// # Arguments
+    /// * `path` - Path to the LUT file to load
+    ///
+    /// # Returns
+    /// A `Result` containing the loaded `Lut3D` or a `LutError` if loading fails
+    pub fn load(path: &str) -> Result<Self, LutError> {
+        Self::load_with(path, ParseMode::Lenient).map(|(lut, _)| lut)
+    }
+
+    /// Loads a LUT file with the given parse mode, choosing the parser from the file extension.
+    ///
+    /// Only .cube files produce diagnostics; the other formats either load or fail.
+    ///
+    /// # Arguments
+    /// * `path` - Path to the LUT file to load
+    /// * `mode` - Whether malformed lines are errors or diagnostics
+    ///
+    /// # Returns
+    /// A `Result` containing the loaded `Lut3D` and any diagnostics, or a `LutError`
+    /// if loading fails
+    pub fn load_with(path: &str, mode: ParseMode) -> Result<(Self, Vec<LutError>), LutError> {
+        let ext = Path::new(path)
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase())
+            .unwrap_or_default();
+        match ext.as_str() {
+            "cube" => Self::from_cube_with(path, mode),
+            "3dl" => Ok((Self::from_3dl(path)?, Vec::new())),
+            "clf" => Ok((Self::from_clf(path)?, Vec::new())),
+            "png" | "tif" | "tiff" => Ok((Self::from_hald(path)?, Vec::new())),
+            _ => Err(LutError::UnsupportedFormat(path.to_string())),
+        }
+    }
+
+    /// Maps an input RGB value in [0,1] into the LUT's normalized lattice space.
+    ///
+    /// Each channel is rescaled from `domain_min..domain_max` to `0..1` and clamped,
+    /// unless the LUT extrapolates.
+    fn normalize(&self, rgb: [f32; 3]) -> [f32; 3] {
+        let extend = self.out_of_domain == OutOfDomain::Extrapolate;
+        std::array::from_fn(|c| {
+            let n = (rgb[c] - self.domain_min[c]) / (self.domain_max[c] - self.domain_min[c]);
+            if extend { n } else { n.clamp(0.0, 1.0) }
         })
     }
 
+    /// Applies the shaper, if any, following the out-of-domain mode.
+    pub(crate) fn shape(&self, rgb: [f32; 3]) -> [f32; 3] {
+        match &self.shaper {
+            Some(shaper) if self.out_of_domain == OutOfDomain::Extrapolate => {
+                shaper.extrapolate(rgb)
+            }
+            Some(shaper) => shaper.apply(rgb),
+            None => rgb,
+        }
+    }
+
+    /// Returns `true` if every channel of an input color lies within the domain of
+    /// the shaper, or of the lattice if there is no shaper.
+    pub(crate) fn in_domain(&self, rgb: [f32; 3]) -> bool {
+        let (min, max) = match &self.shaper {
+            Some(shaper) => (shaper.domain_min(), shaper.domain_max()),
+            None => (self.domain_min, self.domain_max),
+        };
+        (0..3).all(|c| rgb[c] >= min[c] && rgb[c] <= max[c])
+    }
+
+    /// Samples an RGB color through the LUT using the requested interpolation method.
+    ///
+    /// The shaper (if any) is applied first, then the 3D lattice is interpolated.
+    /// Lattices with only 2 points per axis are always interpolated, since snapping
+    /// to them would threshold the image (this is how pure 1D LUTs are stored).
+    /// Inputs outside the domain are handled as set by [`Lut3D::set_out_of_domain`].
+    ///
+    /// # Arguments
+    /// * `rgb` - Input RGB values in the range [0, 1]
+    /// * `interpolation` - The interpolation method used between lattice points
+    ///
+    /// # Returns
+    /// The transformed RGB values (not clamped, may fall outside [0, 1])
+    pub fn sample(&self, rgb: [f32; 3], interpolation: Interpolation) -> [f32; 3] {
+        if self.out_of_domain == OutOfDomain::Passthrough && !self.in_domain(rgb) {
+            return rgb;
+        }
+        self.lattice_sample(self.normalize(self.shape(rgb)), interpolation)
+    }
+
+    /// Interpolates the 3D lattice at a normalized input, bypassing shaper and domain.
+    fn lattice_sample(&self, n: [f32; 3], interpolation: Interpolation) -> [f32; 3] {
+        match interpolation {
+            Interpolation::Nearest if self.size > 2 => self.lookup_nearest(n),
+            Interpolation::Nearest => self.lookup_trilinear(n),
+            Interpolation::Trilinear => self.lookup_trilinear(n),
+            Interpolation::Tetrahedral => self.lookup_tetrahedral(n),
+        }
+    }
+
+    /// Applies the LUT to an RGB color and blends the result with the original color.
+    ///
+    /// # Arguments
+    /// * `rgb` - Input RGB values in the range [0, 1]
+    /// * `options` - Interpolation method and strength of the effect
+    ///
+    /// # Returns
+    /// The transformed RGB values (not clamped, may fall outside [0, 1])
+    pub fn apply(&self, rgb: [f32; 3], options: &ApplyOptions) -> [f32; 3] {
+        let graded = self.sample(rgb, options.interpolation);
+        if options.strength >= 1.0 {
+            return graded;
+        }
+        let t = options.strength.max(0.0);
+        std::array::from_fn(|i| rgb[i] + (graded[i] - rgb[i]) * t)
+    }
+
+    /// Applies the LUT to an 8-bit RGB color at the strength given in `options`.
+    ///
+    /// # Arguments
+    /// * `r`, `g`, `b` - Input RGB values in the range [0, 255]
+    /// * `options` - Interpolation method and strength of the effect
+    ///
+    /// # Returns
+    /// An array containing the transformed RGB values in the range [0, 255]
+    pub fn apply_with(&self, r: u8, g: u8, b: u8, options: &ApplyOptions) -> [u8; 3] {
+        let rgb = [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0];
+        to_u8(self.apply(rgb, options))
+    }
+
+    /// Applies the LUT transformation to an 8-bit RGB color with the given interpolation method.
+    ///
+    /// # Arguments
+    /// * `r`, `g`, `b` - Input RGB values in the range [0, 255]
+    /// * `interpolation` - The interpolation method used between lattice points
+    ///
+    /// # Returns
+    /// An array containing the transformed RGB values in the range [0, 255]
+    pub fn apply_interpolated(&self, r: u8, g: u8, b: u8, interpolation: Interpolation) -> [u8; 3] {
+        // Convert from u8 [0,255] to f32 [0,1] range
+        let rgb = [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0];
+        to_u8(self.sample(rgb, interpolation))
+    }
+
     /// Applies the LUT transformation to an RGB color using nearest neighbor interpolation.
     /// 
     /// This is the simplest and fastest method, but may produce visible stepping
@@ -150,22 +1192,47 @@ impl Lut3D {
     /// # Returns
     /// An array containing the transformed RGB values in the range [0, 255]
     pub fn apply_lut(&self, r: u8, g: u8, b: u8) -> [u8; 3] {
-        // Convert from u8 [0,255] to f32 [0,1] range
-        let r_f = r as f32 / 255.0;
-        let g_f = g as f32 / 255.0;
-        let b_f = b as f32 / 255.0;
+        self.apply_interpolated(r, g, b, Interpolation::Nearest)
+    }
 
-        // Map input values to the LUT's domain range and clamp
-        let map = |val: f32, min: f32, max: f32| ((val - min) / (max - min)).clamp(0.0f32, 1.0f32);
-        let rn = map(r_f, self.domain_min[0], self.domain_max[0]);
-        let gn = map(g_f, self.domain_min[1], self.domain_max[1]);
-        let bn = map(b_f, self.domain_min[2], self.domain_max[2]);
+    /// Applies the LUT transformation using trilinear interpolation.
+    /// 
+    /// This method provides smooth color transitions by interpolating between
+    /// the 8 nearest LUT entries, resulting in higher quality output at the
+    /// cost of increased computation.
+    /// 
+    /// # Arguments
+    /// * `r`, `g`, `b` - Input RGB values in the range [0, 255]
+    /// 
+    /// # Returns
+    /// An array containing the transformed RGB values in the range [0, 255]
+    pub fn apply_lut_trilinear(&self, r: u8, g: u8, b: u8) -> [u8; 3] {
+        self.apply_interpolated(r, g, b, Interpolation::Trilinear)
+    }
+
+    /// Applies the LUT transformation using tetrahedral interpolation.
+    ///
+    /// Each lattice cell is split into 6 tetrahedra and only the 4 corners of the
+    /// tetrahedron containing the input are blended. This preserves the neutral
+    /// axis better than trilinear interpolation and is the method used by most
+    /// grading applications.
+    ///
+    /// # Arguments
+    /// * `r`, `g`, `b` - Input RGB values in the range [0, 255]
+    ///
+    /// # Returns
+    /// An array containing the transformed RGB values in the range [0, 255]
+    pub fn apply_lut_tetrahedral(&self, r: u8, g: u8, b: u8) -> [u8; 3] {
+        self.apply_interpolated(r, g, b, Interpolation::Tetrahedral)
+    }
 
+    /// Looks up the lattice entry closest to a normalized input.
+    fn lookup_nearest(&self, n: [f32; 3]) -> [f32; 3] {
         // Scale normalized values to LUT indices and round to nearest
         let f = (self.size - 1) as f32;
-        let ri = (rn * f).round().clamp(0.0, f) as usize;
-        let gi = (gn * f).round().clamp(0.0, f) as usize;
-        let bi = (bn * f).round().clamp(0.0, f) as usize;
+        let ri = (n[0] * f).round().clamp(0.0, f) as usize;
+        let gi = (n[1] * f).round().clamp(0.0, f) as usize;
+        let bi = (n[2] * f).round().clamp(0.0, f) as usize;
 
         // Calculate linear index into the 3D LUT data array
         let idx = ri + gi * self.size + bi * self.size * self.size;
@@ -177,56 +1244,31 @@ impl Lut3D {
                 idx,
                 self.data.len()
             );
-            return [0, 0, 0];
+            return [0.0; 3];
         }
-        
-        let out = self.data[idx];
 
-        // Convert back from f32 [0,1] to u8 [0,255] range
-        [
-            (out[0].clamp(0.0, 1.0) * 255.0) as u8,
-            (out[1].clamp(0.0, 1.0) * 255.0) as u8,
-            (out[2].clamp(0.0, 1.0) * 255.0) as u8,
-        ]
+        self.data[idx]
     }
 
-    /// Applies the LUT transformation using trilinear interpolation.
-    /// 
-    /// This method provides smooth color transitions by interpolating between
-    /// the 8 nearest LUT entries, resulting in higher quality output at the
-    /// cost of increased computation.
-    /// 
-    /// # Arguments
-    /// * `r`, `g`, `b` - Input RGB values in the range [0, 255]
-    /// 
-    /// # Returns
-    /// An array containing the transformed RGB values in the range [0, 255]
-    pub fn apply_lut_trilinear(&self, r: u8, g: u8, b: u8) -> [u8; 3] {
-        // Convert from u8 [0,255] to f32 [0,1] range
-        let r_f = r as f32 / 255.0;
-        let g_f = g as f32 / 255.0;
-        let b_f = b as f32 / 255.0;
-
-        // Map input values to the LUT's domain range
-        let map = |val: f32, min: f32, max: f32| ((val - min) / (max - min)).clamp(0.0f32, 1.0f32);
-        let rn = map(r_f, self.domain_min[0], self.domain_max[0]);
-        let gn = map(g_f, self.domain_min[1], self.domain_max[1]);
-        let bn = map(b_f, self.domain_min[2], self.domain_max[2]);
-
+    /// Locates the lattice cell containing a normalized input.
+    ///
+    /// Returns the 8 corner values of the cell (indexed as `c[r + 2g + 4b]`)
+    /// together with the fractional position inside the cell. Inputs outside 0..1
+    /// use the edge cell, with positions outside 0..1 that extrapolate it.
+    fn cell(&self, n: [f32; 3]) -> ([[f32; 3]; 8], [f32; 3]) {
         // Scale to LUT coordinate space (floating point for interpolation)
         let f = (self.size - 1) as f32;
-        let rx = rn * f;
-        let gx = gn * f;
-        let bx = bn * f;
+        let rx = n[0] * f;
+        let gx = n[1] * f;
+        let bx = n[2] * f;
 
         // Find the 8 surrounding LUT points for interpolation
-        let r0 = rx.floor() as usize;
-        let g0 = gx.floor() as usize;
-        let b0 = bx.floor() as usize;
+        let lower = |x: f32| (x.floor().max(0.0) as usize).min(self.size - 2);
+        let r0 = lower(rx);
+        let g0 = lower(gx);
+        let b0 = lower(bx);
 
-        let r1 = (r0 + 1).min(self.size - 1);
-        let g1 = (g0 + 1).min(self.size - 1);
-        let b1 = (b0 + 1).min(self.size - 1);
+        let (r1, g1, b1) = (r0 + 1, g0 + 1, b0 + 1);
 
         // Calculate interpolation weights
         let dr = rx - r0 as f32;
@@ -236,15 +1278,22 @@ impl Lut3D {
         // Helper function to calculate linear index
         let idx = |r, g, b| r + g * self.size + b * self.size * self.size;
 
-        // Sample the 8 corner values of the interpolation cube
-        let c000 = self.data[idx(r0, g0, b0)];
-        let c001 = self.data[idx(r0, g0, b1)];
-        let c010 = self.data[idx(r0, g1, b0)];
-        let c011 = self.data[idx(r0, g1, b1)];
-        let c100 = self.data[idx(r1, g0, b0)];
-        let c101 = self.data[idx(r1, g0, b1)];
-        let c110 = self.data[idx(r1, g1, b0)];
-        let c111 = self.data[idx(r1, g1, b1)];
+        let corners = [
+            self.data[idx(r0, g0, b0)],
+            self.data[idx(r1, g0, b0)],
+            self.data[idx(r0, g1, b0)],
+            self.data[idx(r1, g1, b0)],
+            self.data[idx(r0, g0, b1)],
+            self.data[idx(r1, g0, b1)],
+            self.data[idx(r0, g1, b1)],
+            self.data[idx(r1, g1, b1)],
+        ];
+        (corners, [dr, dg, db])
+    }
+
+    /// Trilinear interpolation of a normalized input across the 8 cell corners.
+    fn lookup_trilinear(&self, n: [f32; 3]) -> [f32; 3] {
+        let (c, [dr, dg, db]) = self.cell(n);
 
         // Linear interpolation helpers
         let lerp = |a: f32, b: f32, t: f32| a * (1.0 - t) + b * t;
@@ -258,24 +1307,48 @@ impl Lut3D {
 
         // Perform trilinear interpolation in three stages
         // First: interpolate along R axis
-        let c00 = lerp3(c000, c100, dr);
-        let c01 = lerp3(c001, c101, dr);
-        let c10 = lerp3(c010, c110, dr);
-        let c11 = lerp3(c011, c111, dr);
+        let c00 = lerp3(c[0b000], c[0b001], dr);
+        let c01 = lerp3(c[0b100], c[0b101], dr);
+        let c10 = lerp3(c[0b010], c[0b011], dr);
+        let c11 = lerp3(c[0b110], c[0b111], dr);
 
         // Second: interpolate along G axis
         let c0 = lerp3(c00, c10, dg);
         let c1 = lerp3(c01, c11, dg);
 
         // Third: interpolate along B axis to get final result
-        let c = lerp3(c0, c1, db);
+        lerp3(c0, c1, db)
+    }
 
-        // Convert back from f32 [0,1] to u8 [0,255] range
-        [
-            (c[0].clamp(0.0, 1.0) * 255.0) as u8,
-            (c[1].clamp(0.0, 1.0) * 255.0) as u8,
-            (c[2].clamp(0.0, 1.0) * 255.0) as u8,
-        ]
+    /// Tetrahedral interpolation of a normalized input.
+    ///
+    /// The fractional offsets are ordered to pick one of the 6 tetrahedra that
+    /// share the cell's main diagonal, then the input is blended along the path
+    /// from the black corner to the white corner of the cell.
+    fn lookup_tetrahedral(&self, n: [f32; 3]) -> [f32; 3] {
+        let (c, [dr, dg, db]) = self.cell(n);
+
+        // Corner bits: 0b001 = R, 0b010 = G, 0b100 = B
+        let (first, second, w) = if dr > dg {
+            if dg > db {
+                (0b001, 0b011, [dr, dg, db])
+            } else if dr > db {
+                (0b001, 0b101, [dr, db, dg])
+            } else {
+                (0b100, 0b101, [db, dr, dg])
+            }
+        } else if db > dg {
+            (0b100, 0b110, [db, dg, dr])
+        } else if db > dr {
+            (0b010, 0b110, [dg, db, dr])
+        } else {
+            (0b010, 0b011, [dg, dr, db])
+        };
+
+        let (c0, c1, c2, c3) = (c[0b000], c[first], c[second], c[0b111]);
+        std::array::from_fn(|i| {
+            c0[i] + w[0] * (c1[i] - c0[i]) + w[1] * (c2[i] - c1[i]) + w[2] * (c3[i] - c2[i])
+        })
     }
 
     /// Loads a precomputed LUT table from disk, or generates and saves one if it doesn't exist.
@@ -284,46 +1357,118 @@ impl Lut3D {
     /// (256³ = 16.7M entries), allowing for extremely fast lookups during processing.
     /// The table is saved as a binary file for quick loading in future sessions.
     /// 
+    /// The file starts with a [`TableHeader`] recording the LUT's content hash, the
+    /// interpolation method and strength, and the table format version. An existing
+    /// file is only reused if its size and header match; otherwise the table is
+    /// regenerated into a new file, see [`lut_cache::write_table`].
+    /// 
     /// # Arguments
     /// * `bin_path` - Path where the binary LUT table should be stored
+    /// * `options` - The interpolation method and strength used to generate the table
     /// 
     /// # Returns
-    /// A `Result` containing the precomputed table as a byte vector, or an I/O error
+    /// A `Result` containing the precomputed table, memory-mapped when it was loaded
+    /// from disk, or an I/O error
     /// 
     /// # Format
-    /// The binary table contains 48MB of data (256³ × 3 bytes) with RGB values
-    /// stored sequentially for each possible input combination.
-    pub fn load_or_generate_map(&self, bin_path: &str) -> io::Result<Vec<u8>> {
+    /// After the header, the binary table contains 48MB of data (256³ × 3 bytes) with
+    /// RGB values stored sequentially for each possible input combination.
+    pub fn load_or_generate_map(
+        &self,
+        bin_path: &str,
+        options: &ApplyOptions,
+    ) -> io::Result<TableData> {
         let path = Path::new(bin_path);
-        if path.exists() {
-            // Load existing precomputed table
-            fs::read(path)
-        } else {
-            // Generate new precomputed table
-            let mut table = Vec::with_capacity(256 * 256 * 256 * 3); // 48MB allocation
-            
-            // Generate LUT output for every possible RGB input
-            for r_val in 0u8..=255u8 {
-                for g_val in 0u8..=255u8 {
-                    for b_val in 0u8..=255u8 {
-                        // Use trilinear interpolation for highest quality
-                        let color = self.apply_lut_trilinear(r_val, g_val, b_val);
-                        table.push(color[0]);
-                        table.push(color[1]);
-                        table.push(color[2]);
-                    }
+        let header = TableHeader::new(self, options);
+        if let Some(table) = lut_cache::read_table(path, &header)? {
+            // Reuse the existing precomputed table
+            return Ok(table);
+        }
+
+        // Generate new precomputed table and save it for future use
+        let table = self.generate_map(options, &|_| {});
+        lut_cache::write_table(path, &header, &table)?;
+        Ok(TableData::Owned(table))
+    }
+
+    /// Generates a precomputed lookup table for all 256³ RGB combinations in parallel.
+    ///
+    /// Each red value is computed as an independent slab on the rayon thread pool.
+    ///
+    /// # Arguments
+    /// * `options` - Interpolation method and strength of the effect
+    /// * `progress` - Called with the completed percentage each time another 10% of
+    ///   the table is done; it may be called from any worker thread
+    ///
+    /// # Returns
+    /// The table in the layout expected by [`Lut3D::apply_precomputed`]
+    pub fn generate_map(
+        &self,
+        options: &ApplyOptions,
+        progress: &(dyn Fn(usize) + Sync),
+    ) -> Vec<u8> {
+        const SLAB_LEN: usize = 256 * 256 * 3;
+        let mut table = vec![0u8; lut_cache::TABLE_LEN]; // 48MB allocation
+        let done = AtomicUsize::new(0);
+
+        // Generate LUT output for every possible RGB input, one red slab per task
+        table
+            .par_chunks_mut(SLAB_LEN)
+            .enumerate()
+            .for_each(|(r_val, slab)| {
+                for (i, px) in slab.chunks_exact_mut(3).enumerate() {
+                    let (g_val, b_val) = (i >> 8, i & 0xff);
+                    let color = self.apply_with(r_val as u8, g_val as u8, b_val as u8, options);
+                    px.copy_from_slice(&color);
+                }
+                let slabs = done.fetch_add(1, Ordering::Relaxed) + 1;
+                if slabs * 10 / 256 != (slabs - 1) * 10 / 256 {
+                    progress(slabs * 100 / 256);
                 }
+            });
+        table
+    }
+
+    /// Hashes everything that affects the colors this LUT produces.
+    ///
+    /// The lattice, domain, out-of-domain mode, and shaper are included; title and
+    /// comments are not.
+    /// The hash uses FNV-1a so it is stable across builds and can be stored on disk.
+    pub fn content_hash(&self) -> u64 {
+        let mut hash = 0xcbf2_9ce4_8422_2325u64;
+        let mut feed = |bytes: &[u8]| {
+            for &b in bytes {
+                hash ^= b as u64;
+                hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
             }
-            
-            // Ensure the directory exists before writing
-            if let Some(parent) = path.parent() {
-                fs::create_dir_all(parent)?;
+        };
+        let mut feed_rgb = |v: [f32; 3]| {
+            for c in v {
+                feed(&c.to_bits().to_le_bytes());
             }
-            
-            // Save the generated table for future use
-            fs::write(path, &table)?;
-            Ok(table)
+        };
+
+        feed_rgb([self.size as f32, 0.0, 0.0]);
+        if self.out_of_domain != OutOfDomain::Clamp {
+            let mode = OutOfDomain::ALL
+                .iter()
+                .position(|&m| m == self.out_of_domain);
+            feed_rgb([mode.unwrap_or(0) as f32, 2.0, 0.0]);
+        }
+        feed_rgb(self.domain_min);
+        feed_rgb(self.domain_max);
+        for v in &self.data {
+            feed_rgb(*v);
         }
+        if let Some(shaper) = &self.shaper {
+            feed_rgb([shaper.size() as f32, 1.0, 0.0]);
+            feed_rgb(shaper.domain_min());
+            feed_rgb(shaper.domain_max());
+            for v in shaper.data() {
+                feed_rgb(*v);
+            }
+        }
+        hash
     }
 
     /// Applies a precomputed LUT transformation to an RGB color.
@@ -347,4 +1492,611 @@ impl Lut3D {
         let idx = ((r as usize) << 16 | (g as usize) << 8 | (b as usize)) * 3;
         [table[idx], table[idx + 1], table[idx + 2]]
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lut_batch::PixelBuffer;
+    use std::fs;
+    use std::path::PathBuf;
+
+    const DOMAIN_MIN: [f32; 3] = [0.2, 0.1, 0.0];
+    const DOMAIN_MAX: [f32; 3] = [0.8, 0.9, 1.0];
+
+    /// A path in the temporary directory, unique to this test process.
+    fn temp_path(file_name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("opsin_{}_{}", std::process::id(), file_name))
+    }
+
+    /// Writes a LUT file to the temporary directory and returns its path.
+    fn write_file(file_name: &str, contents: &str) -> PathBuf {
+        let path = temp_path(file_name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    /// A LUT whose lattice maps normalized input `n` to `0.25 + 0.5 * n`, over a
+    /// per-channel domain of `DOMAIN_MIN`..`DOMAIN_MAX`.
+    fn linear_lut(size: usize, mode: OutOfDomain) -> Lut3D {
+        let mut lut = Lut3D::bake(size, |n| n.map(|x| 0.25 + 0.5 * x)).unwrap();
+        lut.domain_min = DOMAIN_MIN;
+        lut.domain_max = DOMAIN_MAX;
+        lut.set_out_of_domain(mode);
+        lut
+    }
+
+    /// The linear LUT's output for an input color, extrapolated beyond the domain.
+    fn expected(rgb: [f32; 3]) -> [f32; 3] {
+        std::array::from_fn(|c| {
+            0.25 + 0.5 * (rgb[c] - DOMAIN_MIN[c]) / (DOMAIN_MAX[c] - DOMAIN_MIN[c])
+        })
+    }
+
+    fn assert_close(actual: [f32; 3], expected: [f32; 3]) {
+        for c in 0..3 {
+            assert!(
+                (actual[c] - expected[c]).abs() < 1e-5,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    /// Loads a .3dl file and removes it.
+    fn parse_3dl(file_name: &str, contents: &str) -> Result<Lut3D, LutError> {
+        let path = write_file(file_name, contents);
+        let result = Lut3D::from_3dl(path.to_str().unwrap());
+        let _ = fs::remove_file(path);
+        result
+    }
+
+    /// A 3-point .3dl table whose entry for lattice point `(r, g, b)` is
+    /// `scale * (r, g, b)`, listed with blue varying fastest.
+    fn table_3dl(header: &str, scale: u32) -> String {
+        let mut text = format!("{}0 512 1023\n", header);
+        for r in 0..3 {
+            for g in 0..3 {
+                for b in 0..3 {
+                    text.push_str(&format!("{} {} {}\n", r * scale, g * scale, b * scale));
+                }
+            }
+        }
+        text
+    }
+
+    #[test]
+    fn reads_3dl_with_guessed_output_depth() {
+        // Codes up to 1000 are taken as 10-bit output
+        let lut = parse_3dl("guessed_10.3dl", &table_3dl("", 500)).unwrap();
+        assert_eq!(lut.size, 3);
+        assert_eq!(lut.domain_min, [0.0; 3]);
+        assert_eq!(lut.domain_max, [1.0; 3]);
+        assert_close(lut.data[1], [500.0 / 1023.0, 0.0, 0.0]);
+        assert_close(lut.data[3], [0.0, 500.0 / 1023.0, 0.0]);
+        assert_close(lut.data[9], [0.0, 0.0, 500.0 / 1023.0]);
+        assert_close(lut.data[26], [1000.0 / 1023.0; 3]);
+
+        // Codes up to 4000 are taken as 12-bit output
+        let lut = parse_3dl("guessed_12.3dl", &table_3dl("", 2000)).unwrap();
+        assert_close(lut.data[26], [4000.0 / 4095.0; 3]);
+    }
+
+    #[test]
+    fn reads_3dl_with_mesh_output_depth() {
+        // The Mesh line overrides the depth guessed from the data
+        let lut = parse_3dl("mesh_12.3dl", &table_3dl("Mesh 4 12\n", 500)).unwrap();
+        assert_close(lut.data[1], [500.0 / 4095.0, 0.0, 0.0]);
+        assert_close(lut.data[26], [1000.0 / 4095.0; 3]);
+        assert_eq!(lut.domain_max, [1.0; 3]);
+
+        let err = parse_3dl("mesh_bad.3dl", &table_3dl("Mesh 4 x\n", 500))
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            LutError::InvalidValue { line: 1, column: 8, ref value } if value == "x"
+        ));
+    }
+
+    #[test]
+    fn reads_1d_and_shaper_cubes() {
+        // A 1D LUT on its own becomes a shaper in front of an identity lattice
+        let cube = "LUT_1D_SIZE 3\n0 0 0\n0.6 0.5 0.4\n1 1 1\n";
+        let path = write_file("curve_1d.cube", cube);
+        let lut = Lut3D::from_cube(path.to_str().unwrap());
+        let _ = fs::remove_file(path);
+        let lut = lut.unwrap();
+        assert_eq!(lut.size, 2);
+        assert_eq!(lut.shaper.as_ref().unwrap().size(), 3);
+        assert_close(
+            lut.sample([0.5; 3], Interpolation::Trilinear),
+            [0.6, 0.5, 0.4],
+        );
+
+        // The 1D entries come first and map into the 3D lattice's domain
+        let cube = "LUT_1D_SIZE 2\nLUT_1D_INPUT_RANGE 0 2\nLUT_3D_SIZE 2\n\
+                    0 0 0\n1 1 1\n\
+                    0 0 0\n1 0 0\n0 1 0\n1 1 0\n0 0 1\n1 0 1\n0 1 1\n1 1 1\n";
+        let path = write_file("shaper_3d.cube", cube);
+        let lut = Lut3D::from_cube(path.to_str().unwrap());
+        let _ = fs::remove_file(path);
+        let lut = lut.unwrap();
+        let shaper = lut.shaper.as_ref().unwrap();
+        assert_eq!(shaper.domain_max(), [2.0; 3]);
+        assert_eq!(lut.data, Lut3D::identity(2).data);
+        assert_close(
+            lut.sample([1.0, 0.5, 0.0], Interpolation::Trilinear),
+            [0.5, 0.25, 0.0],
+        );
+
+        // Missing 1D entries are a size mismatch
+        let path = write_file("short_1d.cube", "LUT_1D_SIZE 3\n0 0 0\n1 1 1\n");
+        let lut = Lut3D::from_cube(path.to_str().unwrap());
+        let _ = fs::remove_file(path);
+        assert!(lut.is_err());
+    }
+
+    /// Parses a .cube file in the given mode and removes it.
+    fn parse_cube(
+        file_name: &str,
+        contents: &str,
+        mode: ParseMode,
+    ) -> Result<(Lut3D, Vec<LutError>), LutError> {
+        let path = write_file(file_name, contents);
+        let result = Lut3D::from_cube_with(path.to_str().unwrap(), mode);
+        let _ = fs::remove_file(path);
+        result
+    }
+
+    /// Writes a LUT with `to_cube`, reads it back, and returns the text and the LUT.
+    fn cube_round_trip(file_name: &str, lut: &Lut3D) -> (String, Lut3D) {
+        let path = temp_path(file_name);
+        lut.to_cube(path.to_str().unwrap()).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        let parsed = Lut3D::from_cube_with(path.to_str().unwrap(), ParseMode::Strict);
+        let _ = fs::remove_file(path);
+        let (parsed, diagnostics) = parsed.unwrap();
+        assert!(diagnostics.is_empty());
+        (text, parsed)
+    }
+
+    #[test]
+    fn cube_round_trips() {
+        let mut lut = Lut3D::identity(3);
+        for (i, v) in lut.data.iter_mut().enumerate() {
+            let x = i as f32 / 26.0;
+            *v = [x, (x * 7.0) % 1.0, 1.0 - x * x];
+        }
+        lut.domain_min = [0.1, 0.0, -0.25];
+        lut.domain_max = [0.9, 1.0, 1.5];
+        lut.set_title("Round trip");
+        lut.add_comment(" first comment");
+        lut.add_comment(" second comment");
+
+        let (text, parsed) = cube_round_trip("round_trip.cube", &lut);
+        assert_eq!(parsed.size, 3);
+        assert_eq!(parsed.title(), Some("Round trip"));
+        assert_eq!(parsed.comments(), lut.comments());
+        assert_eq!(parsed.domain_min, lut.domain_min);
+        assert_eq!(parsed.domain_max, lut.domain_max);
+        for (a, b) in parsed.data.iter().zip(&lut.data) {
+            assert_close(*a, *b);
+        }
+
+        // Writing the parsed LUT again reproduces the file exactly
+        let (again, _) = cube_round_trip("round_trip_again.cube", &parsed);
+        assert_eq!(again, text);
+    }
+
+    #[test]
+    fn cube_round_trips_shapers() {
+        let shaper = vec![[0.0, 0.0, 0.0], [0.6, 0.5, 0.4], [1.0, 1.0, 1.0]];
+
+        // A shaper in front of a lattice keeps its input range
+        let mut lut = Lut3D::identity(2);
+        lut.shaper = Some(Lut1D::new(shaper.clone(), [-0.5; 3], [2.0; 3]));
+        lut.domain_max = [0.5; 3];
+        let (text, parsed) = cube_round_trip("shaper_3d_round_trip.cube", &lut);
+        assert!(text.contains("LUT_1D_INPUT_RANGE -0.500000 2.000000"));
+        assert!(text.contains("LUT_3D_INPUT_RANGE 0.000000 0.500000"));
+        let parsed_shaper = parsed.shaper.as_ref().unwrap();
+        assert_eq!(parsed_shaper.data(), &shaper[..]);
+        assert_eq!(parsed_shaper.domain_min(), [-0.5; 3]);
+        assert_eq!(parsed_shaper.domain_max(), [2.0; 3]);
+        assert_eq!(parsed.domain_max, [0.5; 3]);
+        assert_eq!(parsed.data, lut.data);
+
+        // A pure 1D LUT is written without a lattice
+        let mut curve = Lut3D::identity(2);
+        curve.shaper = Some(Lut1D::new(shaper.clone(), [0.0; 3], [1.0; 3]));
+        let (text, parsed) = cube_round_trip("curve_1d_round_trip.cube", &curve);
+        assert!(!text.contains("LUT_3D_SIZE"));
+        assert_eq!(parsed.shaper.as_ref().unwrap().data(), &shaper[..]);
+        assert_close(
+            parsed.sample([0.5; 3], Interpolation::Trilinear),
+            [0.6, 0.5, 0.4],
+        );
+    }
+
+    #[test]
+    fn reports_3dl_errors_with_position() {
+        let text = table_3dl("", 500).replacen("0 0 500\n", "0 0 5x0\n", 1);
+        let err = parse_3dl("bad_value.3dl", &text).err().unwrap();
+        assert!(matches!(
+            err,
+            LutError::InvalidValue { line: 3, column: 5, ref value } if value == "5x0"
+        ));
+
+        let text = table_3dl("", 500).replacen("0 0 500\n", "0 0 500 7\n", 1);
+        let err = parse_3dl("extra_value.3dl", &text).err().unwrap();
+        assert!(matches!(
+            err,
+            LutError::MalformedLine {
+                line: 3,
+                column: 9,
+                ..
+            }
+        ));
+
+        let text = table_3dl("", 500).replacen("0 0 500\n", "", 1);
+        let err = parse_3dl("short.3dl", &text).err().unwrap();
+        assert!(matches!(
+            err,
+            LutError::SizeMismatch {
+                expected: 27,
+                found: 26
+            }
+        ));
+    }
+
+    #[test]
+    fn strict_mode_rejects_what_lenient_mode_reports() {
+        let identity = "0 0 0\n1 0 0\n0 1 0\n1 1 0\n0 0 1\n1 0 1\n0 1 1\n1 1 1\n";
+        let cube = format!("LUT_3D_SIZE 2\n  VENDOR_KEY 7\n{}", identity);
+
+        let err = parse_cube("unknown_strict.cube", &cube, ParseMode::Strict)
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            LutError::UnknownKeyword { line: 2, column: 3, ref keyword } if keyword == "VENDOR_KEY"
+        ));
+
+        let (lut, diagnostics) =
+            parse_cube("unknown_lenient.cube", &cube, ParseMode::Lenient).unwrap();
+        assert_eq!(lut.data, Lut3D::identity(2).data);
+        assert_eq!(diagnostics.len(), 1);
+        assert!(matches!(
+            diagnostics[0],
+            LutError::UnknownKeyword {
+                line: 2,
+                column: 3,
+                ..
+            }
+        ));
+
+        // A malformed header line is skipped in lenient mode
+        let cube = format!("LUT_3D_SIZE 2\nDOMAIN_MIN 0 0\n{}", identity);
+        let err = parse_cube("header_strict.cube", &cube, ParseMode::Strict)
+            .err()
+            .unwrap();
+        assert!(matches!(
+            err,
+            LutError::MalformedLine {
+                line: 2,
+                column: 15,
+                ..
+            }
+        ));
+        let (lut, diagnostics) =
+            parse_cube("header_lenient.cube", &cube, ParseMode::Lenient).unwrap();
+        assert_eq!(lut.domain_min, [0.0; 3]);
+        assert!(matches!(
+            diagnostics[..],
+            [LutError::MalformedLine {
+                line: 2,
+                column: 15,
+                ..
+            }]
+        ));
+    }
+
+    #[test]
+    fn skipped_data_lines_fail_in_both_modes() {
+        let cube = "LUT_3D_SIZE 2\n0 0 0\n1 0 0\n0 1 0\n1 x 0\n0 0 1\n1 0 1\n0 1 1\n1 1 1\n";
+        for mode in [ParseMode::Strict, ParseMode::Lenient] {
+            let err = parse_cube("bad_data.cube", cube, mode).err().unwrap();
+            assert!(matches!(
+                err,
+                LutError::InvalidValue { line: 5, column: 3, ref value } if value == "x"
+            ));
+        }
+
+        let cube = "LUT_3D_SIZE 2\n0 0 0\n1 0 0\n0 1 0\n1 1\n0 0 1\n1 0 1\n0 1 1\n1 1 1\n";
+        for mode in [ParseMode::Strict, ParseMode::Lenient] {
+            let err = parse_cube("short_data.cube", cube, mode).err().unwrap();
+            assert!(matches!(
+                err,
+                LutError::MalformedLine {
+                    line: 5,
+                    column: 4,
+                    ..
+                }
+            ));
+        }
+
+        let err = parse_cube("no_size.cube", "0 0 0\n", ParseMode::Lenient)
+            .err()
+            .unwrap();
+        assert!(matches!(err, LutError::MissingSize));
+    }
+
+    fn from_u8(r: u8, g: u8, b: u8) -> [f32; 3] {
+        [r as f32 / 255.0, g as f32 / 255.0, b as f32 / 255.0]
+    }
+
+    #[test]
+    fn samples_inside_a_non_default_domain() {
+        for size in [2, 5] {
+            for mode in OutOfDomain::ALL {
+                let lut = linear_lut(size, mode);
+                assert_eq!(lut.domain_min(), DOMAIN_MIN);
+                assert_eq!(lut.domain_max(), DOMAIN_MAX);
+                for rgb in [
+                    [0.2, 0.1, 0.0],
+                    [0.5, 0.5, 0.5],
+                    [0.8, 0.9, 1.0],
+                    [0.35, 0.7, 0.1],
+                ] {
+                    assert_close(lut.sample(rgb, Interpolation::Trilinear), expected(rgb));
+                    assert_close(lut.sample(rgb, Interpolation::Tetrahedral), expected(rgb));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn clamps_outside_the_domain_by_default() {
+        let lut = linear_lut(5, OutOfDomain::default());
+        assert_close(
+            lut.sample([0.0, 0.0, 0.5], Interpolation::Trilinear),
+            [0.25, 0.25, 0.5],
+        );
+        assert_close(
+            lut.sample([1.0, 1.0, 0.5], Interpolation::Trilinear),
+            [0.75, 0.75, 0.5],
+        );
+        assert_eq!(
+            lut.apply_lut_trilinear(0, 0, 255),
+            to_u8([0.25, 0.25, 0.75])
+        );
+        assert_eq!(
+            lut.apply_lut_trilinear(255, 255, 0),
+            to_u8([0.75, 0.75, 0.25])
+        );
+        assert_eq!(lut.apply_lut(0, 255, 128), to_u8([0.25, 0.75, 0.5]));
+    }
+
+    #[test]
+    fn extrapolates_from_the_edge_cells() {
+        for size in [2, 5] {
+            let lut = linear_lut(size, OutOfDomain::Extrapolate);
+            for rgb in [
+                [0.0, 0.0, 0.5],
+                [1.0, 1.0, 0.5],
+                [0.0, 1.0, -0.2],
+                [1.1, -0.3, 1.4],
+            ] {
+                assert_close(lut.sample(rgb, Interpolation::Trilinear), expected(rgb));
+                assert_close(lut.sample(rgb, Interpolation::Tetrahedral), expected(rgb));
+            }
+            for (r, g, b) in [(0, 0, 255), (255, 255, 0), (10, 250, 128)] {
+                assert_eq!(
+                    lut.apply_lut_trilinear(r, g, b),
+                    to_u8(expected(from_u8(r, g, b)))
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn nearest_clamps_when_extrapolating() {
+        let lut = linear_lut(5, OutOfDomain::Extrapolate);
+        assert_close(
+            lut.sample([0.0, 1.0, 0.5], Interpolation::Nearest),
+            [0.25, 0.75, 0.5],
+        );
+        assert_eq!(lut.apply_lut(0, 255, 128), to_u8([0.25, 0.75, 0.5]));
+    }
+
+    #[test]
+    fn passes_through_colors_outside_the_domain() {
+        for size in [2, 5] {
+            let lut = linear_lut(size, OutOfDomain::Passthrough);
+            for rgb in [[0.0, 0.5, 0.5], [0.5, 0.95, 0.5], [0.5, 0.5, 1.2]] {
+                assert_eq!(lut.sample(rgb, Interpolation::Trilinear), rgb);
+                assert_eq!(lut.sample(rgb, Interpolation::Nearest), rgb);
+            }
+            assert_close(
+                lut.sample([0.5, 0.5, 0.5], Interpolation::Trilinear),
+                expected([0.5, 0.5, 0.5]),
+            );
+            assert_eq!(lut.apply_lut_trilinear(10, 128, 128), [10, 128, 128]);
+            assert_eq!(lut.apply_lut(255, 0, 0), [255, 0, 0]);
+            assert_eq!(
+                lut.apply_lut_trilinear(128, 128, 128),
+                to_u8(expected(from_u8(128, 128, 128)))
+            );
+        }
+    }
+
+    #[test]
+    fn buffers_match_per_pixel_results_in_every_mode() {
+        let pixels: Vec<[f32; 3]> = (0..37)
+            .map(|i| {
+                let t = i as f32 / 36.0;
+                [t * 1.4 - 0.2, 1.0 - t, (t * 3.0) % 1.1]
+            })
+            .collect();
+        for size in [2, 5] {
+            for mode in OutOfDomain::ALL {
+                let lut = linear_lut(size, mode);
+                for interpolation in Interpolation::ALL {
+                    for strength in [1.0, 0.5] {
+                        let options = ApplyOptions {
+                            interpolation,
+                            strength,
+                        };
+                        let mut buf: Vec<f32> = pixels.iter().flatten().copied().collect();
+                        lut.apply_to_buffer(PixelBuffer::Rgb32F(&mut buf), &options);
+                        for (rgb, out) in pixels.iter().zip(buf.chunks(3)) {
+                            let want = lut.apply(*rgb, &options);
+                            assert_close([out[0], out[1], out[2]], want);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn buffers_match_per_pixel_results_exactly() {
+        // A curved LUT, so every tetrahedron of a cell gives a different result
+        let lut = Lut3D::bake(5, |[r, g, b]| [r * g, g * g + 0.3 * b, (r + b * b) * 0.5]).unwrap();
+        let pixels: Vec<u8> = (0..1001u32).map(|i| (i * 97 % 256) as u8).collect();
+        for interpolation in Interpolation::ALL {
+            for strength in [1.0, 0.7] {
+                let options = ApplyOptions {
+                    interpolation,
+                    strength,
+                };
+                let mut buf = pixels[..999].to_vec();
+                lut.apply_to_buffer(PixelBuffer::Rgb8(&mut buf), &options);
+                for (px, out) in pixels.chunks(3).zip(buf.chunks(3)) {
+                    assert_eq!(out, lut.apply_with(px[0], px[1], px[2], &options));
+                }
+
+                let floats: Vec<f32> = pixels.iter().map(|&v| v as f32 / 200.0 - 0.1).collect();
+                let mut buf = floats[..999].to_vec();
+                lut.apply_to_buffer(PixelBuffer::Rgb32F(&mut buf), &options);
+                for (rgb, out) in floats.chunks(3).zip(buf.chunks(3)) {
+                    assert_eq!(out, lut.apply([rgb[0], rgb[1], rgb[2]], &options));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn handles_inputs_outside_a_shaper_range() {
+        let cube = "LUT_1D_SIZE 2\nLUT_1D_INPUT_RANGE 0.25 0.75\nLUT_3D_SIZE 2\n\
+                    0 0 0\n1 1 1\n\
+                    0 0 0\n1 0 0\n0 1 0\n1 1 0\n0 0 1\n1 0 1\n0 1 1\n1 1 1\n";
+        let path = write_file("shaper.cube", cube);
+        let mut lut = Lut3D::from_cube(path.to_str().unwrap()).unwrap();
+        let _ = fs::remove_file(path);
+        let rgb = [0.0, 0.5, 1.0];
+
+        assert_close(lut.sample(rgb, Interpolation::Trilinear), [0.0, 0.5, 1.0]);
+        lut.set_out_of_domain(OutOfDomain::Extrapolate);
+        assert_close(lut.sample(rgb, Interpolation::Trilinear), [-0.5, 0.5, 1.5]);
+        lut.set_out_of_domain(OutOfDomain::Passthrough);
+        assert_eq!(lut.sample(rgb, Interpolation::Trilinear), rgb);
+        assert_close(lut.sample([0.5; 3], Interpolation::Trilinear), [0.5; 3]);
+    }
+
+    #[test]
+    fn mode_is_part_of_the_content_hash() {
+        let hashes: Vec<u64> = OutOfDomain::ALL
+            .iter()
+            .map(|&mode| linear_lut(3, mode).content_hash())
+            .collect();
+        assert_ne!(hashes[0], hashes[1]);
+        assert_ne!(hashes[0], hashes[2]);
+        assert_ne!(hashes[1], hashes[2]);
+    }
+
+    #[test]
+    fn composing_with_an_identity_returns_the_original() {
+        let lut = Lut3D::bake(5, |[r, g, b]| {
+            [r * g, 0.7 * g * g + 0.3 * b, (r + b * b) * 0.5]
+        })
+        .unwrap();
+        let identity = Lut3D::identity(5);
+        // Nearest would snap the graded colors to the identity's lattice points
+        for interpolation in [Interpolation::Trilinear, Interpolation::Tetrahedral] {
+            for chain in [[&lut, &identity], [&identity, &lut]] {
+                let composite = Lut3D::compose(&chain, 5, interpolation).unwrap();
+                assert_eq!(composite.size, 5);
+                for (a, b) in composite.data.iter().zip(&lut.data) {
+                    assert_close(*a, *b);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn resampling_up_and_down_stays_within_interpolation_error() {
+        let lut = Lut3D::bake(17, |[r, g, b]| {
+            [r * g, 0.7 * g * g + 0.3 * b, (r + b * b) * 0.5]
+        })
+        .unwrap();
+        for interpolation in [Interpolation::Trilinear, Interpolation::Tetrahedral] {
+            let up = lut.resample(33, interpolation).unwrap();
+            assert_eq!(up.size, 33);
+            let down = up.resample(17, interpolation).unwrap();
+            assert_eq!(down.size, 17);
+            // Every 17-point lattice point is also a 33-point one
+            for (a, b) in down.data.iter().zip(&lut.data) {
+                assert_close(*a, *b);
+            }
+        }
+    }
+
+    #[test]
+    fn resampling_to_the_same_size_keeps_the_data() {
+        let mut lut = linear_lut(5, OutOfDomain::Extrapolate);
+        lut.set_title("Same size");
+        for interpolation in Interpolation::ALL {
+            let resampled = lut.resample(5, interpolation).unwrap();
+            assert_eq!(resampled.data, lut.data);
+            assert_eq!(resampled.domain_min, lut.domain_min);
+            assert_eq!(resampled.domain_max, lut.domain_max);
+            assert_eq!(resampled.title(), Some("Same size"));
+        }
+    }
+
+    #[test]
+    fn hald_round_trips() {
+        let lut = Lut3D::bake(9, |[r, g, b]| {
+            [r * g, 0.7 * g * g + 0.3 * b, (r + b * b) * 0.5]
+        })
+        .unwrap();
+        let path = temp_path("round_trip_hald.png");
+        lut.to_hald(path.to_str().unwrap(), 3, Interpolation::Trilinear)
+            .unwrap();
+        let parsed = Lut3D::from_hald(path.to_str().unwrap());
+        let _ = fs::remove_file(path);
+        let parsed = parsed.unwrap();
+        assert_eq!(parsed.size, 9);
+        // Within the 16-bit quantization of the image
+        for (a, b) in parsed.data.iter().zip(&lut.data) {
+            for c in 0..3 {
+                assert!((a[c] - b[c]).abs() <= 0.5 / 65535.0, "{:?} != {:?}", a, b);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_images_that_are_not_hald_sized() {
+        // Not square, and square but not a cube number of pixels wide
+        for (width, height) in [(27, 8), (8, 27), (20, 20)] {
+            let path = temp_path(&format!("not_hald_{}x{}.png", width, height));
+            image::RgbImage::new(width, height).save(&path).unwrap();
+            let result = Lut3D::from_hald(path.to_str().unwrap());
+            let _ = fs::remove_file(path);
+            assert!(matches!(result, Err(LutError::Invalid(_))));
+        }
+    }
+}
@@ -0,0 +1,371 @@
+use crate::lut3d::{Interpolation, Lut3D};
+use rayon::prelude::*;
+use std::fmt;
+
+/// Decrease between neighboring lattice points still treated as monotonic
+/// (rounding noise in LUT files).
+const MONOTONIC_TOLERANCE: f32 = 1e-4;
+/// Distance outside [0, 1] an output may have before it counts as out of range.
+const RANGE_TOLERANCE: f32 = 1e-4;
+/// Neutral-axis deviation reported as an issue (one 8-bit step).
+const NEUTRAL_TOLERANCE: f32 = 1.0 / 255.0;
+/// Number of grey levels checked along the neutral axis.
+const NEUTRAL_STEPS: usize = 256;
+/// How many times larger than both neighboring steps a step must be to count as a
+/// discontinuity.
+const DISCONTINUITY_RATIO: f32 = 4.0;
+/// Smallest step that can count as a discontinuity, so flat regions don't trigger it.
+const DISCONTINUITY_MIN_STEP: f32 = 0.05;
+/// Input grid points per axis sampled to measure gamut coverage.
+const COVERAGE_SAMPLES: usize = 64;
+/// Bins per axis the output cube is divided into to measure gamut coverage.
+const COVERAGE_BINS: usize = 16;
+
+/// Channel names used in report lines.
+const CHANNELS: [&str; 3] = ["red", "green", "blue"];
+
+/// Diagnostics of a LUT, see [`Lut3D::analyze`].
+///
+/// Slopes are measured over the lattice's normalized input, so an identity LUT has
+/// a slope of 1 everywhere.
+#[derive(Clone, Debug)]
+pub struct LutReport {
+    /// Lattice points per axis
+    pub size: usize,
+    /// Per output channel: steps along its own input axis (and in the shaper) where
+    /// the output decreases
+    pub reversals: [usize; 3],
+    /// Per output channel: the largest such decrease
+    pub max_reversal: [f32; 3],
+    /// Smallest output value per channel
+    pub output_min: [f32; 3],
+    /// Largest output value per channel
+    pub output_max: [f32; 3],
+    /// Lattice points with at least one output outside [0, 1], which 8-bit exports clamp
+    pub out_of_range: usize,
+    /// Largest spread between the output channels of a grey input
+    pub neutral_max_deviation: f32,
+    /// Grey input level with the largest spread
+    pub neutral_worst_input: f32,
+    /// Mean spread between the output channels of grey inputs
+    pub neutral_mean_deviation: f32,
+    /// Mean offset of each output channel from the channel average for grey inputs;
+    /// positive red and negative blue is a warm cast
+    pub neutral_cast: [f32; 3],
+    /// Largest change of any output channel per unit of normalized input
+    pub max_slope: f32,
+    /// Input color at the start of the steepest lattice step
+    pub max_slope_at: [f32; 3],
+    /// Lattice steps much larger than both of their neighbors along the same axis
+    pub discontinuities: usize,
+    /// Fraction of the output RGB cube reached by the LUT
+    pub gamut_coverage: f32,
+}
+
+/// Analyzes a LUT for problems that are easy to miss when applying it.
+///
+/// The lattice is checked for non-monotonic channels, outputs outside [0, 1],
+/// steep steps and jumps, and the shaper (if any) for reversals. The neutral axis
+/// and gamut coverage are measured by sampling the LUT with tetrahedral
+/// interpolation, which keeps greys on the lattice diagonal.
+///
+/// # Arguments
+/// * `lut` - The LUT to analyze
+///
+/// # Returns
+/// A `LutReport` with the measurements; see [`LutReport::issues`] for a verdict
+pub fn analyze(lut: &Lut3D) -> LutReport {
+    let size = lut.size();
+    let data = lut.data();
+    let strides = [1, size, size * size];
+    let index = |p: [usize; 3]| p[0] + p[1] * size + p[2] * size * size;
+    let steps = (size - 1) as f32;
+
+    // Output range
+    let mut output_min = [f32::INFINITY; 3];
+    let mut output_max = [f32::NEG_INFINITY; 3];
+    for v in data {
+        for c in 0..3 {
+            output_min[c] = output_min[c].min(v[c]);
+            output_max[c] = output_max[c].max(v[c]);
+        }
+    }
+    let out_of_range = data
+        .iter()
+        .filter(|v| {
+            v.iter()
+                .any(|x| !(-RANGE_TOLERANCE..=1.0 + RANGE_TOLERANCE).contains(x))
+        })
+        .count();
+
+    // Monotonicity of each output channel along its own input axis
+    let mut reversals = [0; 3];
+    let mut max_reversal = [0.0f32; 3];
+    let mut count_drop = |c: usize, drop: f32| {
+        if drop > MONOTONIC_TOLERANCE {
+            reversals[c] += 1;
+            max_reversal[c] = max_reversal[c].max(drop);
+        }
+    };
+    for (i, v) in data.iter().enumerate() {
+        let p = [i % size, (i / size) % size, i / (size * size)];
+        for c in 0..3 {
+            if p[c] + 1 < size {
+                count_drop(c, v[c] - data[i + strides[c]][c]);
+            }
+        }
+    }
+    if let Some(shaper) = lut.shaper() {
+        for w in shaper.data().windows(2) {
+            for (c, (a, b)) in w[0].iter().zip(&w[1]).enumerate() {
+                count_drop(c, a - b);
+            }
+        }
+    }
+
+    // Slopes and discontinuities along every axis and channel
+    let mut max_slope = 0.0f32;
+    let mut max_slope_at = [0.0; 3];
+    let mut discontinuities = 0;
+    for (i, v) in data.iter().enumerate() {
+        let p = [i % size, (i / size) % size, i / (size * size)];
+        for axis in 0..3 {
+            if p[axis] + 1 >= size {
+                continue;
+            }
+            let next = &data[i + strides[axis]];
+            for c in 0..3 {
+                let step = (next[c] - v[c]).abs();
+                if step * steps > max_slope {
+                    max_slope = step * steps;
+                    max_slope_at = p.map(|x| x as f32 / steps);
+                }
+                if step < DISCONTINUITY_MIN_STEP {
+                    continue;
+                }
+                let neighbor =
+                    |q: [usize; 3]| (data[index(q) + strides[axis]][c] - data[index(q)][c]).abs();
+                let before = (p[axis] > 0).then(|| {
+                    let mut q = p;
+                    q[axis] -= 1;
+                    neighbor(q)
+                });
+                let after = (p[axis] + 2 < size).then(|| {
+                    let mut q = p;
+                    q[axis] += 1;
+                    neighbor(q)
+                });
+                let jump = [before, after]
+                    .into_iter()
+                    .flatten()
+                    .all(|n| step > DISCONTINUITY_RATIO * n);
+                if jump && (before.is_some() || after.is_some()) {
+                    discontinuities += 1;
+                }
+            }
+        }
+    }
+
+    // Neutral axis
+    let mut neutral_max_deviation = 0.0f32;
+    let mut neutral_worst_input = 0.0;
+    let mut neutral_total = 0.0;
+    let mut neutral_cast = [0.0f32; 3];
+    for i in 0..NEUTRAL_STEPS {
+        let level = i as f32 / (NEUTRAL_STEPS - 1) as f32;
+        let out = lut.sample([level; 3], Interpolation::Tetrahedral);
+        let max = out[0].max(out[1]).max(out[2]);
+        let min = out[0].min(out[1]).min(out[2]);
+        let mean = (out[0] + out[1] + out[2]) / 3.0;
+        if max - min > neutral_max_deviation {
+            neutral_max_deviation = max - min;
+            neutral_worst_input = level;
+        }
+        neutral_total += max - min;
+        for c in 0..3 {
+            neutral_cast[c] += (out[c] - mean) / NEUTRAL_STEPS as f32;
+        }
+    }
+
+    // Gamut coverage: output bins reached by an even grid of inputs
+    let bins = COVERAGE_BINS;
+    let hit = (0..COVERAGE_SAMPLES.pow(3))
+        .into_par_iter()
+        .fold(
+            || vec![false; bins * bins * bins],
+            |mut hit, i| {
+                let n = COVERAGE_SAMPLES;
+                let f = (n - 1) as f32;
+                let rgb = [
+                    (i % n) as f32 / f,
+                    ((i / n) % n) as f32 / f,
+                    (i / (n * n)) as f32 / f,
+                ];
+                let out = lut.sample(rgb, Interpolation::Tetrahedral);
+                let [r, g, b] =
+                    out.map(|v| ((v.clamp(0.0, 1.0) * bins as f32) as usize).min(bins - 1));
+                hit[r + g * bins + b * bins * bins] = true;
+                hit
+            },
+        )
+        .reduce(
+            || vec![false; bins * bins * bins],
+            |a, b| a.iter().zip(&b).map(|(&a, &b)| a || b).collect(),
+        );
+    let gamut_coverage = hit.iter().filter(|&&h| h).count() as f32 / hit.len() as f32;
+
+    LutReport {
+        size,
+        reversals,
+        max_reversal,
+        output_min,
+        output_max,
+        out_of_range,
+        neutral_max_deviation,
+        neutral_worst_input,
+        neutral_mean_deviation: neutral_total / NEUTRAL_STEPS as f32,
+        neutral_cast,
+        max_slope,
+        max_slope_at,
+        discontinuities,
+        gamut_coverage,
+    }
+}
+
+impl LutReport {
+    /// Describes the findings worth checking before using the LUT.
+    ///
+    /// # Returns
+    /// One sentence per problem, or an empty list if the LUT looks sound
+    pub fn issues(&self) -> Vec<String> {
+        let mut issues = Vec::new();
+        for (c, name) in CHANNELS.iter().enumerate() {
+            if self.reversals[c] > 0 {
+                issues.push(format!(
+                    "The {} channel decreases in {} places (by up to {:.4}), which can invert gradients",
+                    name, self.reversals[c], self.max_reversal[c]
+                ));
+            }
+        }
+        if self.out_of_range > 0 {
+            issues.push(format!(
+                "{} lattice points have outputs outside [0, 1], which 8-bit exports clip",
+                self.out_of_range
+            ));
+        }
+        if self.neutral_max_deviation > NEUTRAL_TOLERANCE {
+            issues.push(format!(
+                "Greys are tinted by up to {:.4} (at input {:.3})",
+                self.neutral_max_deviation, self.neutral_worst_input
+            ));
+        }
+        if self.discontinuities > 0 {
+            issues.push(format!(
+                "{} lattice steps jump abruptly, which can cause banding or posterization",
+                self.discontinuities
+            ));
+        }
+        issues
+    }
+}
+
+impl fmt::Display for LutReport {
+    /// Formats the report as one line per check.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let monotonic: Vec<String> = (0..3)
+            .map(|c| match self.reversals[c] {
+                0 => format!("{} monotonic", CHANNELS[c]),
+                n => format!(
+                    "{} {} reversals (max drop {:.4})",
+                    CHANNELS[c], n, self.max_reversal[c]
+                ),
+            })
+            .collect();
+        writeln!(f, "Lattice {}³: {}", self.size, monotonic.join(", "))?;
+
+        let ranges: Vec<String> = (0..3)
+            .map(|c| {
+                format!(
+                    "{} [{:.4}, {:.4}]",
+                    CHANNELS[c], self.output_min[c], self.output_max[c]
+                )
+            })
+            .collect();
+        writeln!(
+            f,
+            "Output range: {}; {} lattice points outside [0, 1]",
+            ranges.join(", "),
+            self.out_of_range
+        )?;
+
+        writeln!(
+            f,
+            "Neutral axis: max deviation {:.4} at input {:.3}, mean {:.4}, cast R {:+.4} G {:+.4} B {:+.4}",
+            self.neutral_max_deviation,
+            self.neutral_worst_input,
+            self.neutral_mean_deviation,
+            self.neutral_cast[0],
+            self.neutral_cast[1],
+            self.neutral_cast[2]
+        )?;
+
+        let [r, g, b] = self.max_slope_at;
+        writeln!(
+            f,
+            "Max slope {:.2} at input ({:.3}, {:.3}, {:.3}); {} discontinuities",
+            self.max_slope, r, g, b, self.discontinuities
+        )?;
+
+        write!(
+            f,
+            "Gamut coverage: {:.1}% of the output RGB cube",
+            self.gamut_coverage * 100.0
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identity_lut_is_sound() {
+        let report = analyze(&Lut3D::identity(17));
+        assert_eq!(report.reversals, [0; 3]);
+        assert_eq!(report.out_of_range, 0);
+        assert_eq!(report.output_min, [0.0; 3]);
+        assert_eq!(report.output_max, [1.0; 3]);
+        assert!(report.neutral_max_deviation < 1e-6);
+        assert!(report.neutral_cast.iter().all(|v| v.abs() < 1e-6));
+        assert!((report.max_slope - 1.0).abs() < 1e-4);
+        assert_eq!(report.discontinuities, 0);
+        assert!(report.issues().is_empty(), "{:?}", report.issues());
+    }
+
+    #[test]
+    fn flags_reversals_and_out_of_range_outputs() {
+        let mut data = Lut3D::identity(3).data().to_vec();
+        // Red drops from 0.5 to 0.2 between the last two red inputs
+        data[2] = [0.2, 0.0, 0.0];
+        // White overshoots in green
+        data[26] = [1.0, 1.2, 1.0];
+        let report = analyze(&Lut3D::from_data(3, data).unwrap());
+
+        assert_eq!(report.reversals, [1, 0, 0]);
+        assert!((report.max_reversal[0] - 0.3).abs() < 1e-6);
+        assert_eq!(report.out_of_range, 1);
+        assert_eq!(report.output_max, [1.0, 1.2, 1.0]);
+        let issues = report.issues();
+        assert!(
+            issues
+                .iter()
+                .any(|i| i.starts_with("The red channel decreases in 1 places"))
+        );
+        assert!(
+            issues
+                .iter()
+                .any(|i| i.starts_with("1 lattice points have outputs outside"))
+        );
+        assert!(!issues.iter().any(|i| i.contains("green channel")));
+    }
+}
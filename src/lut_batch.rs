@@ -0,0 +1,263 @@
+use crate::lut3d::{self, ApplyOptions, Interpolation, Lut3D, OutOfDomain};
+use rayon::prelude::*;
+use wide::f32x8;
+
+/// Number of pixels interpolated together, one per lane of an [`f32x8`], so the
+/// interpolation method, shaper and out-of-domain mode are dispatched once per
+/// block rather than once per pixel.
+const LANES: usize = 8;
+
+/// Number of pixels handed to each worker thread at a time.
+const PIXELS_PER_TASK: usize = 4096;
+
+/// An interleaved pixel buffer to grade in place with [`Lut3D::apply_to_buffer`].
+pub enum PixelBuffer<'a> {
+    /// 8-bit RGB, as produced by `image::RgbImage`
+    Rgb8(&'a mut [u8]),
+    /// 8-bit RGBA; alpha is left unchanged
+    Rgba8(&'a mut [u8]),
+    /// 16-bit RGB, as produced by `image::Rgb16Image` (`ImageBuffer<Rgb<u16>, _>`)
+    Rgb16(&'a mut [u16]),
+    /// Floating-point RGB; values are not clamped, so they may leave [0, 1]
+    Rgb32F(&'a mut [f32]),
+}
+
+/// A block of up to [`LANES`] colors stored channel by channel, one vector per
+/// channel.
+type Block = [f32x8; 3];
+
+/// The lattice cells enclosing the lanes of a [`Block`].
+struct Cells {
+    /// Index of each lane's lower corner
+    base: [usize; LANES],
+    /// Offset of each lane from its lower corner, per channel
+    d: Block,
+}
+
+/// Applies a LUT to every pixel of an interleaved buffer.
+///
+/// Pixels are split across the rayon thread pool and interpolated [`LANES`] at a
+/// time in SIMD registers: the lattice corners of each block are gathered channel
+/// by channel and then blended with vector arithmetic. Results match [`Lut3D::apply`]
+/// exactly for each pixel; integer buffers are clamped to their range.
+///
+/// # Arguments
+/// * `lut` - The LUT to apply
+/// * `buffer` - The pixels to transform in place
+/// * `options` - Interpolation method and strength of the effect
+pub fn apply_to_buffer(lut: &Lut3D, buffer: PixelBuffer<'_>, options: &ApplyOptions) {
+    match buffer {
+        PixelBuffer::Rgb8(buf) => apply_interleaved(
+            lut,
+            buf,
+            3,
+            options,
+            |v| v as f32 / 255.0,
+            lut3d::unit_to_u8,
+        ),
+        PixelBuffer::Rgba8(buf) => apply_interleaved(
+            lut,
+            buf,
+            4,
+            options,
+            |v| v as f32 / 255.0,
+            lut3d::unit_to_u8,
+        ),
+        PixelBuffer::Rgb16(buf) => apply_interleaved(
+            lut,
+            buf,
+            3,
+            options,
+            |v| v as f32 / 65535.0,
+            |v| (v.clamp(0.0, 1.0) * 65535.0).round() as u16,
+        ),
+        PixelBuffer::Rgb32F(buf) => apply_interleaved(lut, buf, 3, options, |v| v, |v| v),
+    }
+}
+
+/// Grades an interleaved buffer whose first 3 channels are RGB.
+fn apply_interleaved<T: Copy + Send>(
+    lut: &Lut3D,
+    buf: &mut [T],
+    channels: usize,
+    options: &ApplyOptions,
+    load: impl Fn(T) -> f32 + Sync,
+    store: impl Fn(f32) -> T + Sync,
+) {
+    buf.par_chunks_mut(PIXELS_PER_TASK * channels)
+        .for_each(|task| {
+            for pixels in task.chunks_mut(LANES * channels) {
+                let count = pixels.len() / channels;
+                let mut lanes = [[0.0; LANES]; 3];
+                for (i, px) in pixels.chunks_exact(channels).enumerate() {
+                    for c in 0..3 {
+                        lanes[c][i] = load(px[c]);
+                    }
+                }
+                let graded = apply_block(lut, &lanes.map(f32x8::new), options).map(f32x8::to_array);
+                for (i, px) in pixels.chunks_exact_mut(channels).enumerate().take(count) {
+                    for c in 0..3 {
+                        px[c] = store(graded[c][i]);
+                    }
+                }
+            }
+        });
+}
+
+/// Applies shaper, lattice, and strength to a block of colors.
+fn apply_block(lut: &Lut3D, input: &Block, options: &ApplyOptions) -> Block {
+    let mode = lut.out_of_domain();
+    let mut n = *input;
+    if lut.shaper().is_some() {
+        let rgb = n.map(f32x8::to_array);
+        let shaped: [[f32; 3]; LANES] =
+            std::array::from_fn(|i| lut.shape([rgb[0][i], rgb[1][i], rgb[2][i]]));
+        n = std::array::from_fn(|c| f32x8::new(std::array::from_fn(|i| shaped[i][c])));
+    }
+
+    // Rescale from the domain to 0..1
+    let (min, max) = (lut.domain_min(), lut.domain_max());
+    for c in 0..3 {
+        n[c] = (n[c] - f32x8::splat(min[c])) / f32x8::splat(max[c] - min[c]);
+        if mode != OutOfDomain::Extrapolate {
+            n[c] = n[c].max(f32x8::ZERO).min(f32x8::ONE);
+        }
+    }
+
+    let mut graded = match options.interpolation {
+        Interpolation::Nearest if lut.size() > 2 => block_nearest(lut, &n),
+        Interpolation::Nearest | Interpolation::Trilinear => block_trilinear(lut, &n),
+        Interpolation::Tetrahedral => block_tetrahedral(lut, &n),
+    };
+
+    if mode == OutOfDomain::Passthrough {
+        let rgb = input.map(f32x8::to_array);
+        let mut out = graded.map(f32x8::to_array);
+        for i in 0..LANES {
+            if !lut.in_domain([rgb[0][i], rgb[1][i], rgb[2][i]]) {
+                for c in 0..3 {
+                    out[c][i] = rgb[c][i];
+                }
+            }
+        }
+        graded = out.map(f32x8::new);
+    }
+
+    if options.strength < 1.0 {
+        let t = f32x8::splat(options.strength.max(0.0));
+        for c in 0..3 {
+            graded[c] = input[c] + (graded[c] - input[c]) * t;
+        }
+    }
+    graded
+}
+
+/// Locates each lane's lattice cell.
+fn block_cells(lut: &Lut3D, n: &Block) -> Cells {
+    let size = lut.size();
+    let f = f32x8::splat((size - 1) as f32);
+    let last = f32x8::splat((size - 2) as f32);
+    let strides = [1, size, size * size];
+    let mut cells = Cells {
+        base: [0; LANES],
+        d: [f32x8::ZERO; 3],
+    };
+    for c in 0..3 {
+        let x = n[c] * f;
+        // Limiting the lower corner to the lattice picks the edge cell for
+        // extrapolated inputs
+        let i0 = x.floor().max(f32x8::ZERO).min(last);
+        cells.d[c] = x - i0;
+        for (base, i0) in cells.base.iter_mut().zip(i0.to_array()) {
+            *base += i0 as usize * strides[c];
+        }
+    }
+    cells
+}
+
+/// Gathers the lattice entry at `offsets` from each lane's lower corner into a block.
+fn block_corner(lut: &Lut3D, cells: &Cells, offsets: [usize; LANES]) -> Block {
+    let data = lut.data();
+    let mut corner = [[0.0; LANES]; 3];
+    for i in 0..LANES {
+        let v = data[cells.base[i] + offsets[i]];
+        for c in 0..3 {
+            corner[c][i] = v[c];
+        }
+    }
+    corner.map(f32x8::new)
+}
+
+/// Snaps each lane to its closest lattice point.
+fn block_nearest(lut: &Lut3D, n: &Block) -> Block {
+    let size = lut.size();
+    let f = (size - 1) as f32;
+    let data = lut.data();
+    let n = n.map(f32x8::to_array);
+    let mut out = [[0.0; LANES]; 3];
+    for i in 0..LANES {
+        let idx = |c: usize| (n[c][i] * f).round().clamp(0.0, f) as usize;
+        let v = data[idx(0) + idx(1) * size + idx(2) * size * size];
+        for c in 0..3 {
+            out[c][i] = v[c];
+        }
+    }
+    out.map(f32x8::new)
+}
+
+/// Trilinear interpolation of each lane, matching [`Lut3D::apply`].
+fn block_trilinear(lut: &Lut3D, n: &Block) -> Block {
+    let size = lut.size();
+    let cells = block_cells(lut, n);
+    let [dr, dg, db] = cells.d;
+    let c: [Block; 8] = std::array::from_fn(|bits| {
+        let offset = (bits & 1) + (bits >> 1 & 1) * size + (bits >> 2) * size * size;
+        block_corner(lut, &cells, [offset; LANES])
+    });
+
+    let lerp = |a: &Block, b: &Block, t: f32x8| -> Block {
+        std::array::from_fn(|ch| a[ch] * (f32x8::ONE - t) + b[ch] * t)
+    };
+    let c00 = lerp(&c[0b000], &c[0b001], dr);
+    let c01 = lerp(&c[0b100], &c[0b101], dr);
+    let c10 = lerp(&c[0b010], &c[0b011], dr);
+    let c11 = lerp(&c[0b110], &c[0b111], dr);
+    let c0 = lerp(&c00, &c10, dg);
+    let c1 = lerp(&c01, &c11, dg);
+    lerp(&c0, &c1, db)
+}
+
+/// Tetrahedral interpolation of each lane, matching [`Lut3D::apply`].
+fn block_tetrahedral(lut: &Lut3D, n: &Block) -> Block {
+    let size = lut.size();
+    let cells = block_cells(lut, n);
+    let [dr, dg, db] = cells.d;
+
+    // Pick each lane's tetrahedron with the same comparisons as the scalar path.
+    // Its corners step first along the largest offset, then the middle one, so the
+    // weights are the offsets in descending order
+    let (r, g, b) = (
+        f32x8::ONE,
+        f32x8::splat(size as f32),
+        f32x8::splat((size * size) as f32),
+    );
+    let (r_gt_g, r_gt_b) = (dr.simd_gt(dg), dr.simd_gt(db));
+    let (g_gt_b, b_gt_g, b_gt_r) = (dg.simd_gt(db), db.simd_gt(dg), db.simd_gt(dr));
+    let first = r_gt_g.select(r_gt_b.select(r, b), b_gt_g.select(b, g));
+    let second = r_gt_g.select(
+        g_gt_b.select(r + g, r + b),
+        (b_gt_g | b_gt_r).select(b + g, g + r),
+    );
+    let w_max = dr.max(dg).max(db);
+    let w_mid = dr.max(dg).min(db).max(dr.min(dg));
+    let w_min = dr.min(dg).min(db);
+
+    let offsets = |v: f32x8| v.to_array().map(|o| o as usize);
+    let c0 = block_corner(lut, &cells, [0; LANES]);
+    let c1 = block_corner(lut, &cells, offsets(first));
+    let c2 = block_corner(lut, &cells, offsets(second));
+    let c3 = block_corner(lut, &cells, [1 + size + size * size; LANES]);
+    std::array::from_fn(|ch| {
+        c0[ch] + w_max * (c1[ch] - c0[ch]) + w_mid * (c2[ch] - c1[ch]) + w_min * (c3[ch] - c2[ch])
+    })
+}
@@ -0,0 +1,546 @@
+use crate::lut_batch::PixelBuffer;
+use crate::lut_compact::CompactLut;
+use crate::lut3d::{ApplyOptions, Interpolation, Lut3D};
+use memmap2::Mmap;
+use rayon::prelude::*;
+use serde::Deserialize;
+use std::{
+    fs::{self, File},
+    io::{self, Read},
+    ops::Deref,
+    path::{Path, PathBuf},
+    sync::OnceLock,
+};
+
+/// Version of the precomputed table layout; bump whenever table contents change.
+pub const TABLE_FORMAT_VERSION: u32 = 2;
+
+/// Size in bytes of a full precomputed table (256³ RGB entries).
+pub const TABLE_LEN: usize = 256 * 256 * 256 * 3;
+
+/// File name prefix shared by all precomputed tables in the LUT directory.
+pub const TABLE_PREFIX: &str = "precomputed_";
+
+/// Identifies the file as an Opsin precomputed table.
+const MAGIC: &[u8; 8] = b"OPSINLUT";
+
+/// Kind of precomputed table generated for a processing job.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TableKind {
+    /// Every 8-bit input color precomputed (48MB, fastest lookup)
+    #[default]
+    Full,
+    /// A reduced-resolution float lattice interpolated on lookup (see [`CompactLut`])
+    Compact,
+}
+
+impl TableKind {
+    /// All table kinds, in the order they are offered in the GUI.
+    pub const ALL: [TableKind; 2] = [TableKind::Full, TableKind::Compact];
+
+    /// Short lowercase name used in configuration files and cache file names.
+    pub fn name(self) -> &'static str {
+        match self {
+            TableKind::Full => "full",
+            TableKind::Compact => "compact",
+        }
+    }
+}
+
+/// Header stored in front of every precomputed table.
+///
+/// A table is only reused when its header matches the LUT content and settings it
+/// is requested for, so editing a LUT in place or changing interpolation or
+/// strength always regenerates it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TableHeader {
+    /// Table layout version, see [`TABLE_FORMAT_VERSION`]
+    pub version: u32,
+    /// [`Lut3D::content_hash`] of the LUT the table was generated from
+    pub content_hash: u64,
+    /// Interpolation method the table was generated with
+    pub interpolation: Interpolation,
+    /// Strength the table was generated with
+    pub strength: f32,
+    /// Lattice size of a compact table, or 0 for a full table
+    pub lattice_size: u16,
+}
+
+impl TableHeader {
+    /// Size of the encoded header in bytes.
+    pub const LEN: usize = 28;
+
+    /// Creates the header describing a table generated from `lut` with `options`.
+    pub fn new(lut: &Lut3D, options: &ApplyOptions) -> Self {
+        TableHeader {
+            version: TABLE_FORMAT_VERSION,
+            content_hash: lut.content_hash(),
+            interpolation: options.interpolation,
+            strength: options.strength,
+            lattice_size: 0,
+        }
+    }
+
+    /// Creates the header describing a compact table of `size`³ points generated
+    /// from `lut` with `options`.
+    pub fn compact(lut: &Lut3D, options: &ApplyOptions, size: u16) -> Self {
+        TableHeader {
+            lattice_size: size,
+            ..Self::new(lut, options)
+        }
+    }
+
+    /// Size in bytes of the table data following the header.
+    pub fn table_len(&self) -> usize {
+        match self.lattice_size {
+            0 => TABLE_LEN,
+            size => CompactLut::byte_len(size as usize),
+        }
+    }
+
+    /// Encodes the header as little-endian bytes.
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let mut bytes = [0u8; Self::LEN];
+        bytes[0..8].copy_from_slice(MAGIC);
+        bytes[8..12].copy_from_slice(&self.version.to_le_bytes());
+        bytes[12..20].copy_from_slice(&self.content_hash.to_le_bytes());
+        bytes[20] = Interpolation::ALL
+            .iter()
+            .position(|&m| m == self.interpolation)
+            .unwrap_or(0) as u8;
+        bytes[22..24].copy_from_slice(&self.lattice_size.to_le_bytes());
+        bytes[24..28].copy_from_slice(&self.strength.to_le_bytes());
+        bytes
+    }
+
+    /// Decodes a header, returning `None` if the bytes aren't a table header.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < Self::LEN || &bytes[0..8] != MAGIC {
+            return None;
+        }
+        let u32_at = |i: usize| u32::from_le_bytes(bytes[i..i + 4].try_into().unwrap());
+        Some(TableHeader {
+            version: u32_at(8),
+            content_hash: u64::from_le_bytes(bytes[12..20].try_into().unwrap()),
+            interpolation: *Interpolation::ALL.get(bytes[20] as usize)?,
+            strength: f32::from_bits(u32_at(24)),
+            lattice_size: u16::from_le_bytes([bytes[22], bytes[23]]),
+        })
+    }
+
+    /// Reads the header at the start of a table file.
+    ///
+    /// # Returns
+    /// `Ok(None)` if the file is too short or doesn't start with a table header
+    pub fn read(path: &Path) -> io::Result<Option<Self>> {
+        let mut bytes = [0u8; Self::LEN];
+        let mut file = File::open(path)?;
+        match file.read_exact(&mut bytes) {
+            Ok(()) => Ok(Self::from_bytes(&bytes)),
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+/// The data of a precomputed table, either memory-mapped from its file or in memory.
+///
+/// Dereferences to the table bytes following the header. Mapped tables are
+/// read-only and paged in on demand, so opening even a 48MB table is near-instant
+/// and the pages are shared between jobs through the OS page cache.
+pub enum TableData {
+    /// The whole table file mapped read-only, header included
+    Mapped(Mmap),
+    /// The table bytes without header, generated or read into memory
+    Owned(Vec<u8>),
+}
+
+impl Deref for TableData {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        match self {
+            TableData::Mapped(map) => &map[TableHeader::LEN..],
+            TableData::Owned(bytes) => bytes,
+        }
+    }
+}
+
+/// Path of the file holding the table described by `header` for the cache file
+/// `path`.
+///
+/// The LUT's content hash is inserted before the extension
+/// (`precomputed_<name>.<hash>.bin`), so a regenerated table always goes to a new
+/// file and never replaces one that may still be mapped by [`read_table`].
+pub fn versioned_path(path: &Path, header: &TableHeader) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    path.with_file_name(format!("{}.{:016x}.bin", stem, header.content_hash))
+}
+
+/// Opens a precomputed table if the file exists and matches the expected header.
+///
+/// The table is looked up under [`versioned_path`]. The file size is checked before
+/// opening, so truncated or foreign files are never loaded. The file is
+/// memory-mapped; if mapping fails it is read instead.
+///
+/// # Returns
+/// `Ok(None)` if the file is missing, has the wrong size, or its header differs
+pub fn read_table(path: &Path, expected: &TableHeader) -> io::Result<Option<TableData>> {
+    let path = &versioned_path(path, expected);
+    let file_len = (TableHeader::LEN + expected.table_len()) as u64;
+    let len = match fs::metadata(path) {
+        Ok(meta) => meta.len(),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    if len != file_len {
+        return Ok(None);
+    }
+    if TableHeader::read(path)?.as_ref() != Some(expected) {
+        return Ok(None);
+    }
+
+    // SAFETY: tables are never modified in place; `write_table` only renames over a
+    // file whose header didn't match, which is never mapped, so the mapped contents
+    // stay valid for the lifetime of the mapping.
+    let file = File::open(path)?;
+    if let Ok(map) = unsafe { Mmap::map(&file) }
+        && map.len() as u64 == file_len
+        && TableHeader::from_bytes(&map).as_ref() == Some(expected)
+    {
+        return Ok(Some(TableData::Mapped(map)));
+    }
+
+    // Mapping failed or the file changed in between: fall back to reading it
+    let mut bytes = fs::read(path)?;
+    if bytes.len() as u64 != file_len || TableHeader::from_bytes(&bytes).as_ref() != Some(expected)
+    {
+        return Ok(None);
+    }
+    bytes.drain(..TableHeader::LEN);
+    Ok(Some(TableData::Owned(bytes)))
+}
+
+/// Writes a precomputed table preceded by its header, creating parent directories.
+///
+/// The table goes to [`versioned_path`], so tables of earlier LUT versions that are
+/// still mapped by [`read_table`] are never changed underneath their readers. Those
+/// older versions are then removed; where that fails because the file is still
+/// open (on Windows), it is left for [`purge_tables`].
+pub fn write_table(path: &Path, header: &TableHeader, table: &[u8]) -> io::Result<()> {
+    let versioned = versioned_path(path, header);
+    if let Some(parent) = versioned.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut bytes = Vec::with_capacity(TableHeader::LEN + table.len());
+    bytes.extend_from_slice(&header.to_bytes());
+    bytes.extend_from_slice(table);
+
+    let tmp_path = versioned.with_extension("bin.tmp");
+    fs::write(&tmp_path, bytes)?;
+    fs::rename(&tmp_path, &versioned).inspect_err(|_| {
+        let _ = fs::remove_file(&tmp_path);
+    })?;
+    for old in old_versions(path, &versioned) {
+        let _ = fs::remove_file(old);
+    }
+    Ok(())
+}
+
+/// Lists the other versions of the cache file `path`, including an unversioned
+/// file written before tables were versioned, excluding `current`.
+fn old_versions(path: &Path, current: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(stem)) = (path.parent(), path.file_stem()) else {
+        return Vec::new();
+    };
+    let dir = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    let stem = stem.to_string_lossy();
+    let Ok(entries) = fs::read_dir(dir) else {
+        return Vec::new();
+    };
+    entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|p| p.as_path() != current)
+        .filter(|p| {
+            let Some(name) = p.file_name().and_then(|n| n.to_str()) else {
+                return false;
+            };
+            let Some(version) = name
+                .strip_prefix(stem.as_ref())
+                .and_then(|rest| rest.strip_suffix(".bin"))
+            else {
+                return false;
+            };
+            version.is_empty()
+                || version.strip_prefix('.').is_some_and(|hash| {
+                    hash.len() == 16 && hash.chars().all(|c| c.is_ascii_hexdigit())
+                })
+        })
+        .collect()
+}
+
+/// Reads a precomputed table of the kind described by `expected`, see [`read_table`].
+pub fn read_color_table(path: &Path, expected: &TableHeader) -> io::Result<Option<ColorTable>> {
+    Ok(
+        read_table(path, expected)?.and_then(|bytes| match expected.lattice_size {
+            0 => Some(ColorTable::Full(bytes)),
+            size => CompactLut::from_bytes(size as usize, &bytes, expected.interpolation)
+                .map(ColorTable::Compact),
+        }),
+    )
+}
+
+/// Writes a precomputed table of either kind preceded by its header.
+pub fn write_color_table(path: &Path, header: &TableHeader, table: &ColorTable) -> io::Result<()> {
+    match table {
+        ColorTable::Full(table) => write_table(path, header, table),
+        ColorTable::Compact(table) => write_table(path, header, &table.to_bytes()),
+    }
+}
+
+/// A precomputed table of either kind, ready for lookups.
+pub enum ColorTable {
+    /// Every 8-bit input color precomputed, see [`Lut3D::apply_precomputed`]
+    Full(TableData),
+    /// A reduced-resolution lattice interpolated on lookup
+    Compact(CompactLut),
+}
+
+impl ColorTable {
+    /// Maps an 8-bit RGB color through the table.
+    pub fn apply(&self, r: u8, g: u8, b: u8) -> [u8; 3] {
+        match self {
+            ColorTable::Full(table) => Lut3D::apply_precomputed(table, r, g, b),
+            ColorTable::Compact(table) => table.apply(r, g, b),
+        }
+    }
+}
+
+/// A LUT together with its precomputed table, which may still be generating.
+///
+/// Until the table is set, colors are mapped by interpolating the LUT directly, so
+/// processing can start while the table is built on another thread.
+pub struct LazyTable {
+    lut: Lut3D,
+    options: ApplyOptions,
+    table: OnceLock<ColorTable>,
+}
+
+impl LazyTable {
+    /// Creates a lazy table for `lut` applied with `options`, without a table yet.
+    pub fn new(lut: Lut3D, options: ApplyOptions) -> Self {
+        LazyTable {
+            lut,
+            options,
+            table: OnceLock::new(),
+        }
+    }
+
+    /// The LUT the table is generated from.
+    pub fn lut(&self) -> &Lut3D {
+        &self.lut
+    }
+
+    /// The interpolation method and strength the table is generated with.
+    pub fn options(&self) -> &ApplyOptions {
+        &self.options
+    }
+
+    /// Stores the finished table; later calls are ignored.
+    pub fn set(&self, table: ColorTable) {
+        let _ = self.table.set(table);
+    }
+
+    /// The precomputed table, or `None` while it is still being generated.
+    pub fn table(&self) -> Option<&ColorTable> {
+        self.table.get()
+    }
+
+    /// Maps an 8-bit RGB color through the table if it is ready, or by direct
+    /// interpolation otherwise. A full table gives identical results to direct
+    /// interpolation; a compact table may differ slightly.
+    pub fn apply(&self, r: u8, g: u8, b: u8) -> [u8; 3] {
+        match self.table() {
+            Some(table) => table.apply(r, g, b),
+            None => self.lut.apply_with(r, g, b, &self.options),
+        }
+    }
+
+    /// Maps every pixel of an 8-bit RGB buffer in place, spread across all cores.
+    ///
+    /// Uses the table if it is ready and [`Lut3D::apply_to_buffer`] otherwise.
+    pub fn apply_to_rgb8(&self, buf: &mut [u8]) {
+        match self.table() {
+            Some(table) => buf.par_chunks_mut(3).for_each(|px| {
+                let rgb = table.apply(px[0], px[1], px[2]);
+                px.copy_from_slice(&rgb);
+            }),
+            None => self
+                .lut
+                .apply_to_buffer(PixelBuffer::Rgb8(buf), &self.options),
+        }
+    }
+}
+
+/// A precomputed table file found in the LUT directory.
+pub struct CacheEntry {
+    /// Path of the table file
+    pub path: PathBuf,
+    /// Size of the file in bytes
+    pub file_size: u64,
+    /// The decoded header, or `None` for files without one (e.g. older tables)
+    pub header: Option<TableHeader>,
+}
+
+impl CacheEntry {
+    /// Returns `true` if the table uses the current format and has the expected size.
+    ///
+    /// Whether it matches its LUT is only known once the LUT is loaded.
+    pub fn is_current(&self) -> bool {
+        self.header.is_some_and(|h| {
+            h.version == TABLE_FORMAT_VERSION
+                && self.file_size == (TableHeader::LEN + h.table_len()) as u64
+        })
+    }
+}
+
+/// Lists the precomputed tables (`precomputed_*.bin`) in a directory, sorted by name.
+pub fn list_tables(dir: &Path) -> io::Result<Vec<CacheEntry>> {
+    let mut entries = Vec::new();
+    for entry in fs::read_dir(dir)?.flatten() {
+        let path = entry.path();
+        let is_table = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with(TABLE_PREFIX) && n.ends_with(".bin"));
+        if !is_table || !path.is_file() {
+            continue;
+        }
+        let file_size = entry.metadata()?.len();
+        let header = TableHeader::read(&path).unwrap_or(None);
+        entries.push(CacheEntry {
+            path,
+            file_size,
+            header,
+        });
+    }
+    entries.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(entries)
+}
+
+/// Deletes every precomputed table in a directory, including older versions that
+/// [`write_table`] could not remove while they were mapped.
+///
+/// # Returns
+/// The number of deleted files
+pub fn purge_tables(dir: &Path) -> io::Result<usize> {
+    let entries = list_tables(dir)?;
+    for entry in &entries {
+        fs::remove_file(&entry.path)?;
+    }
+    Ok(entries.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A path in the temporary directory, unique to this test process.
+    fn temp_path(file_name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("opsin_{}_{}", std::process::id(), file_name))
+    }
+
+    #[test]
+    fn headers_round_trip() {
+        let lut = Lut3D::identity(3);
+        let options = ApplyOptions {
+            interpolation: Interpolation::Tetrahedral,
+            strength: 0.75,
+        };
+        for header in [
+            TableHeader::new(&lut, &options),
+            TableHeader::compact(&lut, &options, 65),
+        ] {
+            assert_eq!(TableHeader::from_bytes(&header.to_bytes()), Some(header));
+        }
+
+        let header = TableHeader::new(&lut, &options);
+
+        let mut bytes = header.to_bytes();
+        bytes[0] ^= 0xff;
+        assert_eq!(TableHeader::from_bytes(&bytes), None);
+        assert_eq!(
+            TableHeader::from_bytes(&header.to_bytes()[..TableHeader::LEN - 1]),
+            None
+        );
+    }
+
+    #[test]
+    fn tables_are_only_read_for_matching_headers() {
+        let path = temp_path("precomputed_cache_test.bin");
+        let lut = Lut3D::identity(3);
+        let options = ApplyOptions::default();
+        let header = TableHeader::compact(&lut, &options, 2);
+        let table: Vec<u8> = (0..header.table_len()).map(|i| i as u8).collect();
+        write_table(&path, &header, &table).unwrap();
+
+        let read = read_table(&path, &header).unwrap().unwrap();
+        assert_eq!(&read[..], &table[..]);
+
+        // A different LUT, interpolation, strength or lattice size needs a new table
+        let stale = [
+            TableHeader::compact(&Lut3D::identity(2), &options, 2),
+            TableHeader {
+                interpolation: Interpolation::Nearest,
+                ..header
+            },
+            TableHeader {
+                strength: 0.5,
+                ..header
+            },
+            TableHeader::compact(&lut, &options, 3),
+        ];
+        for expected in stale {
+            assert_ne!(expected, header);
+            assert!(read_table(&path, &expected).unwrap().is_none());
+        }
+
+        // Truncated and missing tables are never read
+        let versioned = versioned_path(&path, &header);
+        fs::write(&versioned, header.to_bytes()).unwrap();
+        assert!(read_table(&path, &header).unwrap().is_none());
+        let _ = fs::remove_file(&versioned);
+        assert!(read_table(&path, &header).unwrap().is_none());
+    }
+
+    #[test]
+    fn regenerated_tables_go_to_a_new_file() {
+        let path = temp_path("precomputed_version_test.bin");
+        let options = ApplyOptions::default();
+        let old = TableHeader::compact(&Lut3D::identity(2), &options, 2);
+        let new = TableHeader::compact(&Lut3D::identity(3), &options, 2);
+        let table = vec![0u8; old.table_len()];
+        fs::write(&path, b"unversioned").unwrap();
+        write_table(&path, &old, &table).unwrap();
+        let mapped = read_table(&path, &old).unwrap().unwrap();
+
+        // The new version gets its own file and replaces the old ones
+        write_table(&path, &new, &table).unwrap();
+        assert_ne!(versioned_path(&path, &old), versioned_path(&path, &new));
+        assert_eq!(&mapped[..], &table[..]);
+        assert!(read_table(&path, &new).unwrap().is_some());
+        assert!(!path.exists());
+        if cfg!(unix) {
+            assert!(!versioned_path(&path, &old).exists());
+        }
+        drop(mapped);
+        let _ = fs::remove_file(versioned_path(&path, &old));
+        let _ = fs::remove_file(versioned_path(&path, &new));
+    }
+}
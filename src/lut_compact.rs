@@ -0,0 +1,208 @@
+use crate::lut3d::{self, ApplyOptions, Interpolation, Lut3D};
+use rayon::prelude::*;
+use std::io::{self, Error};
+
+/// Largest lattice size accepted for a compact table.
+///
+/// A 129³ table takes 25MB, so a compact table is always smaller than the 48MB full
+/// table it replaces (a 256³ float lattice would take 201MB).
+pub const MAX_COMPACT_SIZE: usize = 129;
+
+/// A reduced-resolution alternative to the full 256³ precomputed table.
+///
+/// The LUT, its shaper, domain, and strength are baked into a float lattice over the
+/// 8-bit input range, so a 65³ table takes 3.1MB instead of 48MB. Lookups refine the
+/// lattice on the fly: tetrahedral jobs use tetrahedral interpolation, all others
+/// trilinear. Per-axis lattice positions are precomputed for every 8-bit input value,
+/// so a lookup needs no division or rounding.
+pub struct CompactLut {
+    /// The number of lattice points per axis
+    size: usize,
+    /// Baked output colors, red fastest, then green, then blue
+    data: Vec<[f32; 3]>,
+    /// Whether lookups use tetrahedral rather than trilinear interpolation
+    tetrahedral: bool,
+    /// For each 8-bit input value: the lattice index below it and the offset from it
+    axis: Vec<(usize, f32)>,
+}
+
+impl CompactLut {
+    /// Creates a compact table from baked lattice data.
+    ///
+    /// # Arguments
+    /// * `size` - The number of lattice points per axis
+    /// * `data` - Output colors for evenly spaced 8-bit inputs, red fastest
+    /// * `interpolation` - The job's interpolation method, which selects the lookup
+    ///
+    /// # Errors
+    /// Returns an error if `size` is outside 2..=[`MAX_COMPACT_SIZE`] or `data` has the wrong length
+    pub fn from_data(
+        size: usize,
+        data: Vec<[f32; 3]>,
+        interpolation: Interpolation,
+    ) -> Result<Self, Error> {
+        if !(2..=MAX_COMPACT_SIZE).contains(&size) {
+            return Err(Error::new(
+                io::ErrorKind::InvalidInput,
+                format!(
+                    "Compact table sizes must be between 2 and {}.",
+                    MAX_COMPACT_SIZE
+                ),
+            ));
+        }
+        if data.len() != size * size * size {
+            return Err(Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "Compact table data size mismatch. Expected {} entries, found {}",
+                    size * size * size,
+                    data.len()
+                ),
+            ));
+        }
+        let f = (size - 1) as f32 / 255.0;
+        let axis = (0..=255u32)
+            .map(|v| {
+                let x = v as f32 * f;
+                let i0 = (x.floor() as usize).min(size - 2);
+                (i0, x - i0 as f32)
+            })
+            .collect();
+        Ok(CompactLut {
+            size,
+            data,
+            tetrahedral: interpolation == Interpolation::Tetrahedral,
+            axis,
+        })
+    }
+
+    /// Decodes a compact table from the little-endian floats written by [`Self::to_bytes`].
+    ///
+    /// # Returns
+    /// `None` if the byte count doesn't match `size`
+    pub fn from_bytes(size: usize, bytes: &[u8], interpolation: Interpolation) -> Option<Self> {
+        if bytes.len() != Self::byte_len(size) {
+            return None;
+        }
+        let data = bytes
+            .chunks_exact(12)
+            .map(|px| {
+                std::array::from_fn(|c| {
+                    f32::from_le_bytes(px[c * 4..c * 4 + 4].try_into().unwrap())
+                })
+            })
+            .collect();
+        Self::from_data(size, data, interpolation).ok()
+    }
+
+    /// Encodes the lattice as little-endian floats, red fastest.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.data
+            .iter()
+            .flat_map(|v| v.iter().flat_map(|c| c.to_le_bytes()))
+            .collect()
+    }
+
+    /// Size in bytes of an encoded lattice with `size` points per axis.
+    pub fn byte_len(size: usize) -> usize {
+        size * size * size * 12
+    }
+
+    /// The number of lattice points per axis.
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    /// Maps an 8-bit RGB color through the compact table.
+    ///
+    /// # Arguments
+    /// * `r`, `g`, `b` - Input RGB values in the range [0, 255]
+    ///
+    /// # Returns
+    /// An array containing the transformed RGB values in the range [0, 255]
+    pub fn apply(&self, r: u8, g: u8, b: u8) -> [u8; 3] {
+        let (r0, dr) = self.axis[r as usize];
+        let (g0, dg) = self.axis[g as usize];
+        let (b0, db) = self.axis[b as usize];
+
+        // Corner bits: 0b001 = R, 0b010 = G, 0b100 = B
+        let (sr, sg, sb) = (1, self.size, self.size * self.size);
+        let base = r0 + g0 * sg + b0 * sb;
+        let corner = |bits: usize| {
+            let offset = (bits & 1) * sr + (bits >> 1 & 1) * sg + (bits >> 2) * sb;
+            self.data[base + offset]
+        };
+
+        let rgb = if self.tetrahedral {
+            let (first, second, w) = if dr > dg {
+                if dg > db {
+                    (0b001, 0b011, [dr, dg, db])
+                } else if dr > db {
+                    (0b001, 0b101, [dr, db, dg])
+                } else {
+                    (0b100, 0b101, [db, dr, dg])
+                }
+            } else if db > dg {
+                (0b100, 0b110, [db, dg, dr])
+            } else if db > dr {
+                (0b010, 0b110, [dg, db, dr])
+            } else {
+                (0b010, 0b011, [dg, dr, db])
+            };
+            let (c0, c1, c2, c3) = (corner(0b000), corner(first), corner(second), corner(0b111));
+            std::array::from_fn(|i| {
+                c0[i] + w[0] * (c1[i] - c0[i]) + w[1] * (c2[i] - c1[i]) + w[2] * (c3[i] - c2[i])
+            })
+        } else {
+            let lerp3 = |a: [f32; 3], b: [f32; 3], t: f32| -> [f32; 3] {
+                std::array::from_fn(|i| a[i] + (b[i] - a[i]) * t)
+            };
+            let c00 = lerp3(corner(0b000), corner(0b001), dr);
+            let c01 = lerp3(corner(0b100), corner(0b101), dr);
+            let c10 = lerp3(corner(0b010), corner(0b011), dr);
+            let c11 = lerp3(corner(0b110), corner(0b111), dr);
+            lerp3(lerp3(c00, c10, dg), lerp3(c01, c11, dg), db)
+        };
+        lut3d::to_u8(rgb)
+    }
+}
+
+/// Bakes a LUT into a compact table for 8-bit input.
+///
+/// Each lattice point holds [`Lut3D::apply`] of the corresponding 8-bit input
+/// color, so the shaper, domain, and strength are all included.
+///
+/// # Arguments
+/// * `lut` - The LUT to bake
+/// * `size` - The number of lattice points per axis (e.g. 65 or 129)
+/// * `options` - Interpolation method and strength of the effect
+///
+/// # Returns
+/// A `Result` containing the compact table
+///
+/// # Errors
+/// Returns an error if `size` is outside 2..=[`MAX_COMPACT_SIZE`]
+pub fn bake(lut: &Lut3D, size: usize, options: &ApplyOptions) -> Result<CompactLut, Error> {
+    if !(2..=MAX_COMPACT_SIZE).contains(&size) {
+        return Err(Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "Compact table sizes must be between 2 and {}.",
+                MAX_COMPACT_SIZE
+            ),
+        ));
+    }
+    let f = (size - 1) as f32;
+    let data = (0..size * size * size)
+        .into_par_iter()
+        .map(|i| {
+            let rgb = [
+                (i % size) as f32 / f,
+                (i / size % size) as f32 / f,
+                (i / (size * size)) as f32 / f,
+            ];
+            lut.apply(rgb, options)
+        })
+        .collect();
+    CompactLut::from_data(size, data, options.interpolation)
+}
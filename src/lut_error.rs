@@ -0,0 +1,146 @@
+use serde::Deserialize;
+use std::{fmt, io};
+
+/// How strictly LUT files are parsed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ParseMode {
+    /// Reject any malformed data line or unknown keyword
+    Strict,
+    /// Skip malformed lines and unknown keywords, reporting them as diagnostics
+    #[default]
+    Lenient,
+}
+
+/// An error encountered while loading a LUT file.
+///
+/// Line and column numbers are 1-based and point at the offending token.
+#[derive(Debug)]
+pub enum LutError {
+    /// The file could not be opened or read
+    Io(io::Error),
+    /// The image could not be decoded (Hald CLUTs)
+    Image(image::ImageError),
+    /// The XML document is malformed (CDL and CLF files)
+    Xml(roxmltree::Error),
+    /// The file extension doesn't match any supported LUT format
+    UnsupportedFormat(String),
+    /// A line starts with a keyword the parser doesn't know
+    UnknownKeyword {
+        line: usize,
+        column: usize,
+        keyword: String,
+    },
+    /// A token could not be parsed as the expected number
+    InvalidValue {
+        line: usize,
+        column: usize,
+        value: String,
+    },
+    /// A line has the wrong number of values
+    MalformedLine {
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    /// The file declares no lattice size
+    MissingSize,
+    /// The number of data entries doesn't match the declared sizes
+    SizeMismatch { expected: usize, found: usize },
+    /// The file is well-formed but describes a LUT that can't be represented
+    Invalid(String),
+}
+
+impl LutError {
+    /// The line the error refers to, if it is tied to a specific line.
+    pub fn line(&self) -> Option<usize> {
+        match self {
+            LutError::UnknownKeyword { line, .. }
+            | LutError::InvalidValue { line, .. }
+            | LutError::MalformedLine { line, .. } => Some(*line),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for LutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LutError::Io(e) => write!(f, "{}", e),
+            LutError::Image(e) => write!(f, "{}", e),
+            LutError::Xml(e) => write!(f, "{}", e),
+            LutError::UnsupportedFormat(path) => {
+                write!(f, "Unsupported LUT file extension: {}", path)
+            }
+            LutError::UnknownKeyword {
+                line,
+                column,
+                keyword,
+            } => write!(
+                f,
+                "line {}, column {}: unknown keyword {}",
+                line, column, keyword
+            ),
+            LutError::InvalidValue {
+                line,
+                column,
+                value,
+            } => write!(
+                f,
+                "line {}, column {}: invalid value {}",
+                line, column, value
+            ),
+            LutError::MalformedLine {
+                line,
+                column,
+                message,
+            } => write!(f, "line {}, column {}: {}", line, column, message),
+            LutError::MissingSize => write!(f, "LUT_3D_SIZE and LUT_1D_SIZE are missing."),
+            LutError::SizeMismatch { expected, found } => write!(
+                f,
+                "LUT data size mismatch. Expected {} entries, found {}",
+                expected, found
+            ),
+            LutError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for LutError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            LutError::Io(e) => Some(e),
+            LutError::Image(e) => Some(e),
+            LutError::Xml(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for LutError {
+    fn from(e: io::Error) -> Self {
+        LutError::Io(e)
+    }
+}
+
+impl From<image::ImageError> for LutError {
+    fn from(e: image::ImageError) -> Self {
+        LutError::Image(e)
+    }
+}
+
+impl From<roxmltree::Error> for LutError {
+    fn from(e: roxmltree::Error) -> Self {
+        LutError::Xml(e)
+    }
+}
+
+impl From<LutError> for io::Error {
+    fn from(e: LutError) -> Self {
+        match e {
+            LutError::Io(e) => e,
+            LutError::UnsupportedFormat(_) => io::Error::new(io::ErrorKind::Unsupported, e),
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
@@ -0,0 +1,459 @@
+use crate::lut3d::{Interpolation, Lut3D};
+use rayon::prelude::*;
+use std::collections::HashMap;
+use std::io::{self, Error};
+
+/// Largest lattice size accepted by [`Lut3D::fit`].
+pub const MAX_FIT_SIZE: usize = 65;
+/// Smoothness used when none is configured.
+pub const DEFAULT_SMOOTHNESS: f32 = 0.1;
+/// Quantization steps per channel used to merge samples with (nearly) equal inputs.
+const BIN_STEPS: f32 = 1023.0;
+/// Weight pulling lattice points without nearby samples towards the identity.
+const IDENTITY_WEIGHT: f64 = 1e-4;
+/// Maximum number of conjugate gradient iterations.
+const MAX_ITERATIONS: usize = 1000;
+/// Relative residual at which the conjugate gradient solver stops.
+const TOLERANCE: f64 = 1e-6;
+/// Lattice size up to which the fit is solved directly rather than refined from a
+/// coarser fit.
+const COARSEST_SIZE: usize = 9;
+
+/// The result of [`Lut3D::fit`]: the fitted LUT and how closely it reproduces the grade.
+pub struct FittedLut {
+    /// The fitted LUT, mapping the original colors to the graded ones
+    pub lut: Lut3D,
+    /// Number of pixel pairs the LUT was fitted to
+    pub samples: usize,
+    /// Mean distance between the fitted and the graded colors
+    pub mean_error: f32,
+    /// Distance between the fitted and the graded colors not exceeded by 95% of pixels
+    pub p95_error: f32,
+    /// Largest distance between the fitted and the graded colors
+    pub max_error: f32,
+}
+
+/// Accumulated pixel pairs sharing a quantized input color.
+#[derive(Clone, Copy, Default)]
+struct Bin {
+    before: [f64; 3],
+    after: [f64; 3],
+    count: u64,
+}
+
+/// Fits a LUT reproducing the grade between an original and a graded image.
+///
+/// The lattice is solved as a regularized least-squares problem: trilinear
+/// interpolation of the lattice should map every original color to its graded
+/// counterpart, while second differences along each axis are penalized so the
+/// LUT stays smooth and extends linearly into colors the images don't contain.
+/// Pixels with (nearly) equal original colors are merged before solving, so
+/// large images cost little more than small ones.
+///
+/// Both buffers hold interleaved sRGB-encoded float pixels of the same image;
+/// values outside [0, 1] are clamped.
+///
+/// # Arguments
+/// * `before` - Pixels of the original image
+/// * `after` - Pixels of the graded image, in the same order
+/// * `size` - The size of each dimension of the fitted lattice (e.g. 33)
+/// * `smoothness` - Weight of the smoothness penalty relative to the pixel fit;
+///   larger values suppress noise and local edits at the cost of accuracy
+///
+/// # Returns
+/// A `Result` containing the fitted LUT and its error statistics
+///
+/// # Errors
+/// Returns an error if `size` is outside 2..=65, the buffers differ in length or
+/// hold no pixels, or `smoothness` is negative
+pub fn fit(
+    before: &[f32],
+    after: &[f32],
+    size: usize,
+    smoothness: f32,
+) -> Result<FittedLut, Error> {
+    if !(2..=MAX_FIT_SIZE).contains(&size) {
+        return Err(Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Fitted LUT sizes must be between 2 and {}.", MAX_FIT_SIZE),
+        ));
+    }
+    if before.len() != after.len() || !before.len().is_multiple_of(3) {
+        return Err(Error::new(
+            io::ErrorKind::InvalidInput,
+            "The original and graded pixels don't match.",
+        ));
+    }
+    if before.is_empty() {
+        return Err(Error::new(
+            io::ErrorKind::InvalidInput,
+            "There are no pixels to fit.",
+        ));
+    }
+    if smoothness.is_nan() || smoothness < 0.0 {
+        return Err(Error::new(
+            io::ErrorKind::InvalidInput,
+            "The smoothness must not be negative.",
+        ));
+    }
+
+    let bins: Vec<Bin> = bin_pixels(before, after).into_values().collect();
+    let mut lut = Lut3D::from_data(size, solve_lattice(&bins, size, smoothness as f64))?;
+
+    // Errors are measured per pixel, so colors that were graded differently in
+    // different parts of the image count as often as they occur
+    let mut errors: Vec<f32> = before
+        .par_chunks_exact(3)
+        .zip(after.par_chunks_exact(3))
+        .map(|(b, a)| {
+            let fitted = lut.sample(clamp([b[0], b[1], b[2]]), Interpolation::Trilinear);
+            distance(fitted, clamp([a[0], a[1], a[2]]))
+        })
+        .collect();
+    let samples = errors.len();
+    let mean_error = errors.par_iter().map(|&e| e as f64).sum::<f64>() / samples as f64;
+    let max_error = errors.par_iter().copied().reduce(|| 0.0, f32::max);
+    let p95 = (samples - 1) * 95 / 100;
+    let (_, &mut p95_error, _) = errors.select_nth_unstable_by(p95, f32::total_cmp);
+
+    lut.set_title("Fitted LUT");
+    lut.add_comment(format!(
+        " Fitted to {} pixels ({} distinct colors) with smoothness {}",
+        samples,
+        bins.len(),
+        smoothness
+    ));
+    lut.add_comment(format!(
+        " Mean error {:.6}, 95th percentile {:.6}, max error {:.6}",
+        mean_error, p95_error, max_error
+    ));
+
+    Ok(FittedLut {
+        lut,
+        samples,
+        mean_error: mean_error as f32,
+        p95_error,
+        max_error,
+    })
+}
+
+/// Solves the fit at `size`, starting from the fit at about half the size.
+///
+/// The conjugate gradient solver is slow to settle the smooth, large-scale part of
+/// the lattice, which the coarser fit already provides.
+fn solve_lattice(bins: &[Bin], size: usize, smoothness: f64) -> Vec<[f32; 3]> {
+    let identity = Lut3D::identity(size);
+    let to_f64 =
+        |data: &[[f32; 3]]| -> Vec<[f64; 3]> { data.iter().map(|v| v.map(f64::from)).collect() };
+    let prior = to_f64(identity.data());
+    let initial = if size > COARSEST_SIZE {
+        let coarse_size = size / 2 + 1;
+        Lut3D::from_data(coarse_size, solve_lattice(bins, coarse_size, smoothness))
+            .and_then(|coarse| coarse.resample(size, Interpolation::Trilinear))
+            .map_or_else(|_| prior.clone(), |lut| to_f64(lut.data()))
+    } else {
+        prior.clone()
+    };
+    NormalEquations::new(size, bins, smoothness)
+        .solve(&prior, initial)
+        .into_iter()
+        .map(|v| v.map(|c| c as f32))
+        .collect()
+}
+
+/// Merges pixel pairs whose original colors fall into the same quantization step.
+///
+/// 8-bit images are binned losslessly; each bin keeps the sums of its original and
+/// graded colors, so the fit uses their means.
+fn bin_pixels(before: &[f32], after: &[f32]) -> HashMap<u32, Bin> {
+    let merge = |a: HashMap<u32, Bin>, b: HashMap<u32, Bin>| {
+        let (mut a, b) = if a.len() < b.len() { (b, a) } else { (a, b) };
+        for (key, bin) in b {
+            let entry = a.entry(key).or_default();
+            for c in 0..3 {
+                entry.before[c] += bin.before[c];
+                entry.after[c] += bin.after[c];
+            }
+            entry.count += bin.count;
+        }
+        a
+    };
+    before
+        .par_chunks_exact(3)
+        .zip(after.par_chunks_exact(3))
+        .fold(HashMap::new, |mut bins: HashMap<u32, Bin>, (b, a)| {
+            let (b, a) = (clamp([b[0], b[1], b[2]]), clamp([a[0], a[1], a[2]]));
+            let [r, g, bl] = b.map(|v| (v * BIN_STEPS).round() as u32);
+            let bin = bins.entry(r | (g << 10) | (bl << 20)).or_default();
+            for c in 0..3 {
+                bin.before[c] += b[c] as f64;
+                bin.after[c] += a[c] as f64;
+            }
+            bin.count += 1;
+            bins
+        })
+        .reduce(HashMap::new, merge)
+}
+
+/// The normal equations of the regularized lattice fit, shared by all three channels.
+struct NormalEquations {
+    /// Lattice points per axis
+    size: usize,
+    /// Data term: for each lattice point, its coupling to the 3×3×3 neighborhood
+    /// (index `dr + 3·dg + 9·db` for offsets of -1..=1 shifted by 1)
+    couplings: Vec<[f64; 27]>,
+    /// Right-hand side: weighted graded colors accumulated per lattice point
+    rhs: Vec<[f64; 3]>,
+    /// Weight of the second-difference penalty
+    smoothness: f64,
+}
+
+impl NormalEquations {
+    /// Accumulates the data term of the binned pixel pairs.
+    ///
+    /// The data term is normalized by the pixel count and scaled by the number of
+    /// lattice points, so `smoothness` means the same at every image and lattice size.
+    fn new(size: usize, bins: &[Bin], smoothness: f64) -> Self {
+        let points = size * size * size;
+        let mut couplings = vec![[0.0; 27]; points];
+        let mut rhs = vec![[0.0; 3]; points];
+        let mut total = 0.0;
+        let f = (size - 1) as f64;
+
+        for bin in bins {
+            let n = bin.count as f64;
+            let before = bin.before.map(|v| v / n);
+            let after = bin.after.map(|v| v / n);
+
+            // Lattice cell containing the color and the trilinear weights of its corners
+            let cell: [usize; 3] = before.map(|v| ((v * f) as usize).min(size - 2));
+            let t: [f64; 3] = std::array::from_fn(|c| before[c] * f - cell[c] as f64);
+            let corners: [(usize, [usize; 3], f64); 8] = std::array::from_fn(|k| {
+                let o = [k & 1, (k >> 1) & 1, (k >> 2) & 1];
+                let w = (0..3)
+                    .map(|c| if o[c] == 1 { t[c] } else { 1.0 - t[c] })
+                    .product::<f64>();
+                let index =
+                    (cell[0] + o[0]) + size * (cell[1] + o[1]) + size * size * (cell[2] + o[2]);
+                (index, o, w)
+            });
+
+            for &(i, oi, wi) in &corners {
+                for &(_, oj, wj) in &corners {
+                    let k = (oj[0] + 1 - oi[0]) + 3 * (oj[1] + 1 - oi[1]) + 9 * (oj[2] + 1 - oi[2]);
+                    couplings[i][k] += n * wi * wj;
+                }
+                for c in 0..3 {
+                    rhs[i][c] += n * wi * after[c];
+                }
+            }
+            total += n;
+        }
+
+        let scale = points as f64 / total;
+        couplings
+            .par_iter_mut()
+            .for_each(|row| row.iter_mut().for_each(|v| *v *= scale));
+        rhs.par_iter_mut()
+            .for_each(|row| row.iter_mut().for_each(|v| *v *= scale));
+        NormalEquations {
+            size,
+            couplings,
+            rhs,
+            smoothness,
+        }
+    }
+
+    /// Coordinates of a lattice point, red fastest.
+    fn coords(&self, i: usize) -> [usize; 3] {
+        let s = self.size;
+        [i % s, (i / s) % s, i / (s * s)]
+    }
+
+    /// Computes `A · v`, where `A` combines the data term, the second-difference
+    /// penalty, and the identity weight.
+    fn apply(&self, v: &[[f64; 3]]) -> Vec<[f64; 3]> {
+        let s = self.size;
+        let strides = [1, s, s * s];
+        (0..v.len())
+            .into_par_iter()
+            .map(|i| {
+                let p = self.coords(i);
+                let mut out = v[i].map(|x| x * IDENTITY_WEIGHT);
+
+                for (k, &w) in self.couplings[i].iter().enumerate() {
+                    if w != 0.0 {
+                        // Nonzero couplings only exist between points sharing a cell
+                        let offset = [k % 3, (k / 3) % 3, k / 9];
+                        let j = (0..3).fold(i, |j, c| j + offset[c] * strides[c] - strides[c]);
+                        for c in 0..3 {
+                            out[c] += w * v[j][c];
+                        }
+                    }
+                }
+
+                // Gather form of Dᵀ·D·v: every second difference centered at or next
+                // to this point contributes with the point's coefficient in it
+                for (axis, &stride) in strides.iter().enumerate() {
+                    let lo = p[axis].saturating_sub(1).max(1);
+                    let hi = (p[axis] + 1).min(s - 2);
+                    for center in lo..=hi {
+                        let m = i + center * stride - p[axis] * stride;
+                        let coef = if center == p[axis] { -2.0 } else { 1.0 };
+                        for c in 0..3 {
+                            let d = v[m - stride][c] - 2.0 * v[m][c] + v[m + stride][c];
+                            out[c] += self.smoothness * coef * d;
+                        }
+                    }
+                }
+                out
+            })
+            .collect()
+    }
+
+    /// Diagonal of `A`, used as a Jacobi preconditioner.
+    fn diagonal(&self) -> Vec<f64> {
+        let s = self.size;
+        (0..self.couplings.len())
+            .map(|i| {
+                let p = self.coords(i);
+                let mut d = self.couplings[i][13] + IDENTITY_WEIGHT;
+                for &x in &p {
+                    for center in x.saturating_sub(1).max(1)..=(x + 1).min(s - 2) {
+                        d += self.smoothness * if center == x { 4.0 } else { 1.0 };
+                    }
+                }
+                d
+            })
+            .collect()
+    }
+
+    /// Solves the equations for all three channels with preconditioned conjugate
+    /// gradients, starting from `initial`.
+    ///
+    /// The identity weight pulls lattice points towards `prior`.
+    fn solve(&self, prior: &[[f64; 3]], initial: Vec<[f64; 3]>) -> Vec<[f64; 3]> {
+        let b: Vec<[f64; 3]> = self
+            .rhs
+            .iter()
+            .zip(prior)
+            .map(|(r, x)| std::array::from_fn(|c| r[c] + IDENTITY_WEIGHT * x[c]))
+            .collect();
+        let inv_diag: Vec<f64> = self.diagonal().into_iter().map(|d| 1.0 / d).collect();
+        let precondition = |r: &[[f64; 3]]| -> Vec<[f64; 3]> {
+            r.iter()
+                .zip(&inv_diag)
+                .map(|(r, &d)| r.map(|v| v * d))
+                .collect()
+        };
+        let dot = |a: &[[f64; 3]], b: &[[f64; 3]]| -> [f64; 3] {
+            a.iter().zip(b).fold([0.0; 3], |acc, (x, y)| {
+                std::array::from_fn(|c| acc[c] + x[c] * y[c])
+            })
+        };
+
+        let mut x = initial;
+        let ax = self.apply(&x);
+        let mut r: Vec<[f64; 3]> = b
+            .iter()
+            .zip(&ax)
+            .map(|(b, a)| std::array::from_fn(|c| b[c] - a[c]))
+            .collect();
+        let mut z = precondition(&r);
+        let mut p = z.clone();
+        let mut rz = dot(&r, &z);
+        let b_norm = dot(&b, &b).map(|v| v.sqrt().max(f64::MIN_POSITIVE));
+
+        for _ in 0..MAX_ITERATIONS {
+            let r_norm = dot(&r, &r).map(f64::sqrt);
+            if (0..3).all(|c| r_norm[c] <= TOLERANCE * b_norm[c]) {
+                break;
+            }
+            let ap = self.apply(&p);
+            let pap = dot(&p, &ap);
+            let alpha: [f64; 3] =
+                std::array::from_fn(|c| if pap[c] > 0.0 { rz[c] / pap[c] } else { 0.0 });
+            for (((x, r), p), ap) in x.iter_mut().zip(&mut r).zip(&p).zip(&ap) {
+                for c in 0..3 {
+                    x[c] += alpha[c] * p[c];
+                    r[c] -= alpha[c] * ap[c];
+                }
+            }
+            z = precondition(&r);
+            let rz_next = dot(&r, &z);
+            let beta: [f64; 3] =
+                std::array::from_fn(|c| if rz[c] > 0.0 { rz_next[c] / rz[c] } else { 0.0 });
+            for (p, z) in p.iter_mut().zip(&z) {
+                for c in 0..3 {
+                    p[c] = z[c] + beta[c] * p[c];
+                }
+            }
+            rz = rz_next;
+        }
+        x
+    }
+}
+
+/// Clamps an RGB color to [0, 1].
+fn clamp(rgb: [f32; 3]) -> [f32; 3] {
+    rgb.map(|v| v.clamp(0.0, 1.0))
+}
+
+/// Euclidean distance between two RGB colors.
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A 24³ grid of 8-bit colors (every 11th code value) as an interleaved buffer.
+    fn grid_pixels() -> Vec<f32> {
+        (0..24 * 24 * 24)
+            .flat_map(|i| [i % 24, i / 24 % 24, i / 576].map(|v| v as f32 * 11.0 / 255.0))
+            .collect()
+    }
+
+    #[test]
+    fn reproduces_a_known_grade() {
+        let before = grid_pixels();
+        let grade = Lut3D::bake(9, |[r, g, b]| {
+            [r.powf(0.8), 0.1 + 0.8 * g, (0.8 * b + 0.2 * r).powf(1.2)]
+        })
+        .unwrap();
+        let after: Vec<f32> = before
+            .chunks_exact(3)
+            .flat_map(|px| grade.sample([px[0], px[1], px[2]], Interpolation::Trilinear))
+            .collect();
+
+        let fitted = fit(&before, &after, 17, 0.001).unwrap();
+        assert_eq!(fitted.samples, before.len() / 3);
+        assert!(fitted.max_error < 1e-3, "max error {}", fitted.max_error);
+        assert!(fitted.mean_error <= fitted.p95_error && fitted.p95_error <= fitted.max_error);
+        for rgb in [[0.5, 0.5, 0.5], [0.9, 0.1, 0.3], [0.2, 0.7, 0.95]] {
+            let (actual, expected) = (
+                fitted.lut.sample(rgb, Interpolation::Trilinear),
+                grade.sample(rgb, Interpolation::Trilinear),
+            );
+            assert!(
+                distance(actual, expected) < 1e-3,
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        let before = grid_pixels();
+        assert!(fit(&before, &before[3..], 17, 0.1).is_err());
+        assert!(fit(&before[1..], &before[1..], 17, 0.1).is_err());
+        assert!(fit(&[], &[], 17, 0.1).is_err());
+        assert!(fit(&before, &before, 17, -0.1).is_err());
+        assert!(fit(&before, &before, 17, f32::NAN).is_err());
+        assert!(fit(&before, &before, 1, 0.1).is_err());
+        assert!(fit(&before, &before, MAX_FIT_SIZE + 1, 0.1).is_err());
+    }
+}
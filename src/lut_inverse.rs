@@ -0,0 +1,256 @@
+use crate::lut3d::{Interpolation, Lut3D};
+use rayon::prelude::*;
+use std::io::Error;
+
+/// Number of lattice points per axis of the coarse forward sampling used to seed the solver.
+const SEED_SIZE: usize = 9;
+/// Maximum number of Newton iterations per inverse lattice point.
+const MAX_ITERATIONS: usize = 32;
+/// Residual below which a lattice point is considered solved.
+const TOLERANCE: f32 = 1e-5;
+/// Step used for the finite-difference Jacobian, relative to the domain width.
+const JACOBIAN_STEP: f32 = 1e-3;
+/// Lattice size of the grid used to measure the round-trip error.
+const ROUND_TRIP_SIZE: usize = 17;
+
+/// The result of [`invert`]: the inverse LUT and how well it undoes the original.
+pub struct InverseLut {
+    /// The approximate inverse, mapping the original LUT's outputs back to its inputs
+    pub lut: Lut3D,
+    /// Largest distance between `forward(inverse(y))` and `y` over the inverse lattice
+    pub max_error: f32,
+    /// Mean distance between `forward(inverse(y))` and `y` over the inverse lattice
+    pub mean_error: f32,
+    /// Largest distance between `inverse(forward(x))` and `x` over a test grid of inputs
+    pub round_trip_error: f32,
+}
+
+/// Computes an approximate inverse of a LUT.
+///
+/// Each point of the inverse lattice is a target output color `y`. The forward LUT
+/// is first sampled on a coarse grid and the scattered sample whose output is
+/// closest to `y` seeds a damped Newton solve (with a finite-difference Jacobian)
+/// for the input `x` with `forward(x) = y`, constrained to the forward domain.
+/// Targets the forward LUT can't reach are mapped to the closest reachable input,
+/// which shows up in the reported errors.
+///
+/// # Arguments
+/// * `lut` - The forward LUT
+/// * `size` - The size of each dimension of the inverse lattice (e.g. 33)
+/// * `interpolation` - Interpolation used to evaluate the forward LUT
+///
+/// # Returns
+/// A `Result` containing the inverse LUT and its error statistics
+///
+/// # Errors
+/// Returns an error if `size` is smaller than 2
+pub fn invert(lut: &Lut3D, size: usize, interpolation: Interpolation) -> Result<InverseLut, Error> {
+    let (min, max) = (lut.domain_min(), lut.domain_max());
+    let forward = |x: [f32; 3]| lut.sample(x, interpolation);
+
+    // Scattered forward samples (input, output) used to seed the solver
+    let seeds: Vec<([f32; 3], [f32; 3])> = lattice_points(SEED_SIZE)
+        .map(|t| {
+            let x = std::array::from_fn(|c| min[c] + t[c] * (max[c] - min[c]));
+            (x, forward(x))
+        })
+        .collect();
+
+    let mut inverse = Lut3D::bake(size, |y| {
+        let seed = seeds
+            .iter()
+            .min_by(|a, b| distance(a.1, y).total_cmp(&distance(b.1, y)))
+            .map(|s| s.0)
+            .unwrap_or(y);
+        solve(&forward, y, seed, min, max).0
+    })?;
+
+    // Residual of each solved lattice point
+    let errors: Vec<f32> = inverse
+        .data()
+        .par_iter()
+        .zip(lattice_points(size).collect::<Vec<_>>())
+        .map(|(&x, y)| distance(forward(x), y))
+        .collect();
+    let max_error = errors.iter().copied().fold(0.0, f32::max);
+    let mean_error = errors.iter().sum::<f32>() / errors.len() as f32;
+    if let Some(title) = lut.title() {
+        inverse.set_title(format!("{} (inverse)", title));
+    }
+    inverse.add_comment(format!(
+        " Numerical inverse, max error {:.6}, mean error {:.6}",
+        max_error, mean_error
+    ));
+
+    // Check how well the inverse undoes the forward LUT across its domain
+    let round_trip_error = lattice_points(ROUND_TRIP_SIZE)
+        .map(|t| {
+            let x = std::array::from_fn(|c| min[c] + t[c] * (max[c] - min[c]));
+            distance(inverse.sample(forward(x), interpolation), x)
+        })
+        .fold(0.0, f32::max);
+
+    Ok(InverseLut {
+        lut: inverse,
+        max_error,
+        mean_error,
+        round_trip_error,
+    })
+}
+
+/// Iterates the points of an evenly spaced `size³` lattice over [0, 1]³,
+/// with red varying fastest (the .cube data order).
+fn lattice_points(size: usize) -> impl Iterator<Item = [f32; 3]> {
+    let f = (size - 1) as f32;
+    (0..size * size * size).map(move |i| {
+        [
+            (i % size) as f32 / f,
+            ((i / size) % size) as f32 / f,
+            (i / (size * size)) as f32 / f,
+        ]
+    })
+}
+
+/// Euclidean distance between two RGB colors.
+fn distance(a: [f32; 3], b: [f32; 3]) -> f32 {
+    ((a[0] - b[0]).powi(2) + (a[1] - b[1]).powi(2) + (a[2] - b[2]).powi(2)).sqrt()
+}
+
+/// Solves `forward(x) = y` for `x` within `[min, max]` with a damped Newton method.
+///
+/// Returns the best input found and its residual distance.
+fn solve(
+    forward: &impl Fn([f32; 3]) -> [f32; 3],
+    y: [f32; 3],
+    seed: [f32; 3],
+    min: [f32; 3],
+    max: [f32; 3],
+) -> ([f32; 3], f32) {
+    let clamp = |x: [f32; 3]| -> [f32; 3] { std::array::from_fn(|c| x[c].clamp(min[c], max[c])) };
+    let mut x = clamp(seed);
+    let mut fx = forward(x);
+    let mut err = distance(fx, y);
+
+    for _ in 0..MAX_ITERATIONS {
+        if err < TOLERANCE {
+            break;
+        }
+
+        // Finite-difference Jacobian, one column per input channel
+        let mut jac = [[0.0f32; 3]; 3];
+        for c in 0..3 {
+            let h = JACOBIAN_STEP * (max[c] - min[c]);
+            let mut lo = x;
+            let mut hi = x;
+            lo[c] = (x[c] - h).max(min[c]);
+            hi[c] = (x[c] + h).min(max[c]);
+            let (flo, fhi) = (forward(lo), forward(hi));
+            let span = hi[c] - lo[c];
+            for (row, jr) in jac.iter_mut().enumerate() {
+                jr[c] = (fhi[row] - flo[row]) / span;
+            }
+        }
+
+        let residual = [y[0] - fx[0], y[1] - fx[1], y[2] - fx[2]];
+        let step = solve3(jac, residual).unwrap_or(residual);
+
+        // Halve the step until the residual decreases
+        let mut t = 1.0;
+        let mut improved = false;
+        while t > 1e-3 {
+            let candidate = clamp(std::array::from_fn(|c| x[c] + step[c] * t));
+            let fc = forward(candidate);
+            let e = distance(fc, y);
+            if e < err {
+                (x, fx, err) = (candidate, fc, e);
+                improved = true;
+                break;
+            }
+            t *= 0.5;
+        }
+        if !improved {
+            break;
+        }
+    }
+    (x, err)
+}
+
+/// Solves the 3×3 linear system `m · x = v` with Cramer's rule.
+///
+/// Returns `None` if the matrix is (nearly) singular.
+fn solve3(m: [[f32; 3]; 3], v: [f32; 3]) -> Option<[f32; 3]> {
+    let det = |m: [[f32; 3]; 3]| {
+        m[0][0] * (m[1][1] * m[2][2] - m[1][2] * m[2][1])
+            - m[0][1] * (m[1][0] * m[2][2] - m[1][2] * m[2][0])
+            + m[0][2] * (m[1][0] * m[2][1] - m[1][1] * m[2][0])
+    };
+    let d = det(m);
+    if d.abs() < 1e-12 {
+        return None;
+    }
+    Some(std::array::from_fn(|c| {
+        let mut mc = m;
+        for (row, mr) in mc.iter_mut().enumerate() {
+            mr[c] = v[row];
+        }
+        det(mc) / d
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// An invertible LUT with a different increasing curve on each channel.
+    fn curve_lut(size: usize) -> Lut3D {
+        Lut3D::bake(size, |[r, g, b]| {
+            [
+                0.6 * r + 0.4 * r * r,
+                0.8 * g + 0.2 * g * g * g,
+                b.powf(1.25) * 0.5 + b * 0.5,
+            ]
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn inverse_undoes_a_per_channel_curve() {
+        let lut = curve_lut(17);
+        let inverse = invert(&lut, 17, Interpolation::Trilinear).unwrap();
+        assert!(inverse.max_error < 1e-4);
+        assert!(inverse.round_trip_error < 5e-3);
+
+        // forward(inverse(x)) ≈ x between the inverse lattice points too
+        for x in lattice_points(12) {
+            let y = lut.sample(
+                inverse.lut.sample(x, Interpolation::Trilinear),
+                Interpolation::Trilinear,
+            );
+            assert!(distance(y, x) < 5e-3, "{:?} -> {:?}", x, y);
+        }
+    }
+
+    #[test]
+    fn reported_errors_match_measured_errors() {
+        // A contrast curve that can't reach the darkest and brightest targets
+        let lut = Lut3D::bake(9, |rgb| rgb.map(|v| 0.1 + 0.8 * v)).unwrap();
+        let inverse = invert(&lut, 9, Interpolation::Trilinear).unwrap();
+        let errors: Vec<f32> = inverse
+            .lut
+            .data()
+            .iter()
+            .zip(lattice_points(9))
+            .map(|(&x, y)| distance(lut.sample(x, Interpolation::Trilinear), y))
+            .collect();
+        let max_error = errors.iter().copied().fold(0.0, f32::max);
+        let mean_error = errors.iter().sum::<f32>() / errors.len() as f32;
+        assert!(max_error > 0.1);
+        assert!((inverse.max_error - max_error).abs() < 1e-6);
+        assert!((inverse.mean_error - mean_error).abs() < 1e-6);
+    }
+
+    #[test]
+    fn rejects_degenerate_sizes() {
+        assert!(invert(&curve_lut(3), 1, Interpolation::Trilinear).is_err());
+    }
+}
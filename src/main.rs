@@ -1,16 +1,30 @@
 mod file_handler;
-mod lut3d;
 mod metadata_handler;
 
-use crate::lut3d::Lut3D;
 use eframe::{egui, App, NativeOptions};
 use egui::IconData;
+use opsin::adjustments::Adjustments;
+use opsin::cdl::{self, Cdl, CdlPlacement};
+use opsin::color_space::ColorSpace;
+use opsin::dither::Dither;
+use opsin::icc::OutputProfile;
+use opsin::lut_cache::{
+    self, CacheEntry, ColorTable, LazyTable, TableData, TableHeader, TableKind,
+};
+use opsin::lut_compact::MAX_COMPACT_SIZE;
+use opsin::lut_error::ParseMode;
+use opsin::lut_fit::{DEFAULT_SMOOTHNESS, MAX_FIT_SIZE};
+use opsin::lut3d::{self, ApplyOptions, Interpolation, Lut3D, OutOfDomain};
 use serde::Deserialize;
 use std::{
-    fs,
+    collections::HashMap,
+    fs, io,
     path::{Path, PathBuf},
-    sync::{mpsc, Arc, Mutex},
-    thread,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc, Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
 };
 
 /// Configuration structure for the application, loaded from `config.toml`.
@@ -39,6 +53,77 @@ struct InputPaths {
 struct LutConfig {
     /// The filename of the currently selected LUT file
     selected: String,
+    /// Interpolation method used when generating the precomputed table
+    #[serde(default)]
+    interpolation: Interpolation,
+    /// How colors outside a LUT's input domain are handled
+    #[serde(default)]
+    out_of_domain: OutOfDomain,
+    /// Strength of the LUT effect in percent (0 = original, 100 = fully graded)
+    #[serde(default = "default_strength")]
+    strength: u32,
+    /// Ordered LUT stack applied at startup; when empty, only `selected` is used
+    #[serde(default)]
+    stack: Vec<String>,
+    /// Lattice size used when baking a stack of several LUTs into one
+    #[serde(default = "default_composite_size")]
+    composite_size: usize,
+    /// Whether malformed LUT files are rejected or loaded with warnings
+    #[serde(default)]
+    parse_mode: ParseMode,
+    /// Kind of precomputed table built for processing jobs
+    #[serde(default)]
+    table: TableKind,
+    /// Lattice size of compact precomputed tables
+    #[serde(default = "default_compact_size")]
+    compact_size: usize,
+    /// How graded colors are quantized when exporting 8-bit images
+    #[serde(default)]
+    dither: Dither,
+    /// Color profile exported JPEG and PNG images are converted to and tagged with
+    #[serde(default)]
+    output_profile: OutputProfile,
+    /// Optional ASC CDL file in the LUT directory baked together with the LUT stack
+    #[serde(default)]
+    cdl: Option<String>,
+    /// `id` of the correction to use from a CDL file holding several
+    #[serde(default)]
+    cdl_id: Option<String>,
+    /// Whether the CDL is applied before or after the LUT stack
+    #[serde(default)]
+    cdl_placement: CdlPlacement,
+    /// Color spaces declared per LUT filename; undeclared LUTs are sRGB to sRGB
+    #[serde(default)]
+    color_spaces: HashMap<String, LutSpaces>,
+    /// Initial adjustments of the LUT generator, including its curves
+    #[serde(default)]
+    generate: Adjustments,
+}
+
+/// The color spaces a LUT expects as input and produces as output.
+#[derive(Clone, Copy, Default, PartialEq, Deserialize)]
+struct LutSpaces {
+    /// Color space the LUT's input is encoded in
+    #[serde(default)]
+    input: ColorSpace,
+    /// Color space the LUT's output is encoded in
+    #[serde(default)]
+    output: ColorSpace,
+}
+
+/// Default LUT strength, applying the full effect.
+fn default_strength() -> u32 {
+    100
+}
+
+/// Default lattice size for composite LUTs, matching common 33-point cubes.
+fn default_composite_size() -> usize {
+    33
+}
+
+/// Default lattice size for compact tables, a 3.1MB table with small errors.
+fn default_compact_size() -> usize {
+    65
 }
 
 /// Reads the application configuration from the `config.toml` file.
@@ -76,7 +161,7 @@ fn load_icon(path: &str) -> IconData {
 }
 
 /// Scans a directory for available LUT files and returns their names.
-/// Only files with the `.cube` extension are considered valid LUTs.
+/// Only files with an extension listed in [`lut3d::LUT_EXTENSIONS`] are considered valid LUTs.
 /// 
 /// # Arguments
 /// * `lut_dir` - Path to the directory containing LUT files
@@ -84,17 +169,26 @@ fn load_icon(path: &str) -> IconData {
 /// # Returns
 /// A sorted vector of LUT filenames (without path, including extension)
 fn list_luts(lut_dir: &Path) -> Vec<String> {
+    list_files(lut_dir, &lut3d::LUT_EXTENSIONS)
+}
+
+/// Scans a directory for ASC CDL files (`.cdl`, `.cc`, `.ccc`) and returns their names.
+fn list_cdls(lut_dir: &Path) -> Vec<String> {
+    list_files(lut_dir, &cdl::CDL_EXTENSIONS)
+}
+
+/// Returns the sorted names of the files in a directory with one of `extensions`.
+fn list_files(lut_dir: &Path, extensions: &[&str]) -> Vec<String> {
     let mut entries = Vec::new();
     if let Ok(read_dir) = fs::read_dir(lut_dir) {
         for entry in read_dir.flatten() {
             let path = entry.path();
-            // Check for .cube file extension (case-insensitive)
-            if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
-                if ext.eq_ignore_ascii_case("cube") {
-                    if let Some(name) = path.file_name().and_then(|n| n.to_str()) {
-                        entries.push(name.to_string());
-                    }
-                }
+            // Check for a supported file extension (case-insensitive)
+            if let Some(ext) = path.extension().and_then(|e| e.to_str())
+                && extensions.iter().any(|e| ext.eq_ignore_ascii_case(e))
+                && let Some(name) = path.file_name().and_then(|n| n.to_str())
+            {
+                entries.push(name.to_string());
             }
         }
     }
@@ -102,6 +196,223 @@ fn list_luts(lut_dir: &Path) -> Vec<String> {
     entries
 }
 
+/// Loads every LUT of a stack and composes them into a single LUT.
+///
+/// A single-entry stack is returned as loaded, without baking.
+///
+/// # Arguments
+/// * `lut_files` - Paths of the LUTs to apply, in order
+/// * `lut_spaces` - Declared color spaces of each LUT; LUTs that don't work in sRGB
+///   are baked with conversions from and back to sRGB, the images' working space
+/// * `composite_size` - Lattice size of LUTs baked from a stack or with color conversions
+/// * `interpolation` - Interpolation used to sample each LUT while baking
+/// * `out_of_domain` - How each LUT handles colors outside its input domain
+/// * `parse_mode` - Whether malformed LUT files are rejected or loaded with warnings
+/// * `log` - Callback receiving progress, parse warnings, and error messages
+///
+/// # Returns
+/// The LUT to apply, or `None` if any LUT failed to load (errors are logged)
+fn load_lut_stack(
+    lut_files: &[PathBuf],
+    lut_spaces: &[LutSpaces],
+    composite_size: usize,
+    interpolation: Interpolation,
+    out_of_domain: OutOfDomain,
+    parse_mode: ParseMode,
+    log: &dyn Fn(&str),
+) -> Option<Lut3D> {
+    let mut luts = Vec::with_capacity(lut_files.len());
+    for (lut_file, spaces) in lut_files.iter().zip(lut_spaces) {
+        log(&format!("Loading LUT from {}", lut_file.display()));
+        match Lut3D::load_with(lut_file.to_str().unwrap_or_default(), parse_mode) {
+            Ok((mut lut, diagnostics)) => {
+                lut.set_out_of_domain(out_of_domain);
+                for diagnostic in diagnostics {
+                    log(&format!("Warning in {}: {}", lut_file.display(), diagnostic));
+                }
+                log(&format!("Loaded LUT: {}", lut_file.display()));
+                if *spaces == LutSpaces::default() {
+                    luts.push(lut);
+                    continue;
+                }
+
+                // Convert sRGB images into the LUT's input space and its output back
+                match lut.with_color_spaces(
+                    spaces.input,
+                    spaces.output,
+                    ColorSpace::Srgb,
+                    composite_size,
+                    interpolation,
+                ) {
+                    Ok(managed) => {
+                        log(&format!(
+                            "Applying {} with {} input and {} output",
+                            lut_file.display(),
+                            spaces.input.name(),
+                            spaces.output.name()
+                        ));
+                        luts.push(managed);
+                    }
+                    Err(e) => {
+                        log(&format!(
+                            "Error converting {} color spaces: {}",
+                            lut_file.display(),
+                            e
+                        ));
+                        return None;
+                    }
+                }
+            }
+            Err(e) => {
+                log(&format!("Error reading LUT file {}: {}", lut_file.display(), e));
+                return None;
+            }
+        }
+    }
+
+    if luts.len() == 1 {
+        return luts.pop();
+    }
+    let refs: Vec<&Lut3D> = luts.iter().collect();
+    match Lut3D::compose(&refs, composite_size, interpolation) {
+        Ok(mut lut) => {
+            lut.set_out_of_domain(out_of_domain);
+            log(&format!(
+                "Composed {} LUTs into a {}-point LUT",
+                refs.len(),
+                composite_size
+            ));
+            Some(lut)
+        }
+        Err(e) => {
+            log(&format!("Error composing LUT stack: {}", e));
+            None
+        }
+    }
+}
+
+/// Loads a CDL correction and bakes it before or after a LUT.
+///
+/// # Arguments
+/// * `lut` - The LUT (or baked LUT stack) to combine with the CDL
+/// * `cdl_file` - Path of the `.cdl`, `.cc`, or `.ccc` file
+/// * `cdl_id` - The correction to use, or `None` for the file's first one
+/// * `placement` - Whether the CDL is applied before or after the LUT
+/// * `size` - Lattice size of the baked LUT
+/// * `interpolation` - Interpolation used to sample the LUT while baking
+/// * `log` - Callback receiving progress and error messages
+///
+/// # Returns
+/// The baked LUT, or `None` if the CDL failed to load (errors are logged)
+fn bake_cdl(
+    lut: &Lut3D,
+    cdl_file: &Path,
+    cdl_id: Option<&str>,
+    placement: CdlPlacement,
+    size: usize,
+    interpolation: Interpolation,
+    log: &dyn Fn(&str),
+) -> Option<Lut3D> {
+    let result = Cdl::load(cdl_file.to_str().unwrap_or_default(), cdl_id)
+        .map_err(io::Error::from)
+        .and_then(|cdl| lut.with_cdl(&cdl, placement, size, interpolation));
+    match result {
+        Ok(baked) => {
+            log(&format!(
+                "Baked CDL {} {} the LUT at size {}",
+                cdl_file.display(),
+                placement.name(),
+                size
+            ));
+            Some(baked)
+        }
+        Err(e) => {
+            log(&format!("Error applying CDL {}: {}", cdl_file.display(), e));
+            None
+        }
+    }
+}
+
+/// Loads the precomputed table for a LUT, or starts generating it in the background.
+///
+/// While the table is generating, the returned [`LazyTable`] falls back to direct
+/// interpolation, so processing can start right away. The finished table is saved
+/// to `bin_path` for future jobs.
+///
+/// # Arguments
+/// * `lut` - The LUT to precompute
+/// * `options` - Interpolation method and strength baked into the table
+/// * `kind` - Whether to build a full table or a compact lattice
+/// * `compact_size` - Lattice size of a compact table
+/// * `bin_path` - Cache file holding the table
+/// * `log_arc` - Thread-safe log receiving generation progress and errors
+///
+/// # Returns
+/// The lazy table and, if generation was started, the handle of its thread
+fn start_table(
+    lut: Lut3D,
+    options: ApplyOptions,
+    kind: TableKind,
+    compact_size: usize,
+    bin_path: PathBuf,
+    log_arc: Arc<Mutex<Vec<String>>>,
+) -> (Arc<LazyTable>, Option<JoinHandle<()>>) {
+    let header = match kind {
+        TableKind::Full => TableHeader::new(&lut, &options),
+        TableKind::Compact => TableHeader::compact(&lut, &options, compact_size as u16),
+    };
+    let lazy = Arc::new(LazyTable::new(lut, options));
+    let local_log = move |msg: String| {
+        if let Ok(mut log_vec) = log_arc.lock() {
+            log_vec.push(msg);
+        }
+    };
+
+    match lut_cache::read_color_table(&bin_path, &header) {
+        Ok(Some(table)) => {
+            local_log(format!("Loaded precomputed table {}", bin_path.display()));
+            lazy.set(table);
+            return (lazy, None);
+        }
+        Ok(None) => {}
+        Err(e) => local_log(format!("Error reading {}: {}", bin_path.display(), e)),
+    }
+
+    let generator = lazy.clone();
+    let handle = thread::spawn(move || {
+        local_log(format!("Generating precomputed table {}", bin_path.display()));
+        let (lut, options) = (generator.lut(), generator.options());
+        let table = match kind {
+            TableKind::Full => {
+                let table = lut.generate_map(options, &|percent| {
+                    local_log(format!("Precomputed table {}% done", percent))
+                });
+                ColorTable::Full(TableData::Owned(table))
+            }
+            TableKind::Compact => match lut.bake_compact(compact_size, options) {
+                Ok(table) => ColorTable::Compact(table),
+                Err(e) => {
+                    local_log(format!("Error baking compact table: {}", e));
+                    return;
+                }
+            },
+        };
+        if let Err(e) = lut_cache::write_color_table(&bin_path, &header, &table) {
+            local_log(format!("Error saving {}: {}", bin_path.display(), e));
+        }
+        generator.set(table);
+        local_log("Precomputed table ready.".to_string());
+    });
+    (lazy, Some(handle))
+}
+
+/// Reordering requested from the LUT stack list in the GUI.
+enum StackAction {
+    MoveUp,
+    MoveDown,
+    Remove,
+}
+
 /// Main application structure for the Opsin color grading tool.
 /// Manages the GUI state, file paths, processing status, and threading.
 struct OpsinApp {
@@ -115,10 +426,58 @@ struct OpsinApp {
     lut_dir: PathBuf,
     /// List of discovered LUT filenames
     available_luts: Vec<String>,
-    /// Currently selected LUT filename
+    /// List of discovered ASC CDL filenames
+    available_cdls: Vec<String>,
+    /// CDL file baked together with the LUT stack, if any
+    cdl: Option<String>,
+    /// `id` of the correction to use from the CDL file; empty selects the first one
+    cdl_id: String,
+    /// Whether the CDL is applied before or after the LUT stack
+    cdl_placement: CdlPlacement,
+    /// Currently selected LUT filename in the dropdown
     current_lut: String,
+    /// Ordered LUT filenames applied during processing (first entry applied first)
+    lut_stack: Vec<String>,
+    /// Lattice size used when baking the LUT stack into a single LUT
+    composite_size: usize,
+    /// Lattice size of LUTs created by the resample, invert, and fit tools
+    tool_lut_size: usize,
+    /// Original image the fit tool compares against, relative to the image directory
+    fit_before: String,
+    /// Graded version of `fit_before`, relative to the image directory
+    fit_after: String,
+    /// Weight of the smoothness penalty of the fit tool
+    fit_smoothness: f32,
+    /// Adjustments the LUT generator evaluates
+    adjustments: Adjustments,
+    /// Filename, without extension, of LUTs created by the generator
+    generated_name: String,
+    /// Whether malformed LUT files are rejected or loaded with warnings
+    parse_mode: ParseMode,
+    /// Kind of precomputed table built for the next processing job
+    table_kind: TableKind,
+    /// Lattice size of compact precomputed tables
+    compact_size: usize,
+    /// How graded colors are quantized when exporting 8-bit images
+    dither: Dither,
+    /// Color profile embedded in exported JPEG and PNG images
+    output_profile: OutputProfile,
+    /// Declared color spaces per LUT filename
+    lut_spaces: HashMap<String, LutSpaces>,
+    /// Precomputed tables found in the LUT directory, shown in the cache view
+    cache_entries: Vec<CacheEntry>,
+    /// Interpolation method used for the next processing job
+    interpolation: Interpolation,
+    /// Out-of-domain handling used for the next processing job
+    out_of_domain: OutOfDomain,
+    /// Strength of the LUT effect in percent used for the next processing job
+    strength: u32,
     /// Thread-safe log for status messages displayed in the GUI
     status_log: Arc<Mutex<Vec<String>>>,
+    /// Set by background tools after saving a LUT, so the LUT list gets refreshed
+    luts_changed: Arc<AtomicBool>,
+    /// Channel receiver for the filename of a LUT being generated, selected once saved
+    generated_lut_receiver: Option<mpsc::Receiver<String>>,
     /// Flag indicating whether file processing is currently active
     is_processing: bool,
     /// Channel receiver for completion signals from the processing thread
@@ -136,29 +495,444 @@ impl OpsinApp {
         let fixed_lut_dir = PathBuf::from("assets/luts");
         
         // Ensure the LUT directory exists
-        if !fixed_lut_dir.exists() {
-            if let Err(e) = fs::create_dir_all(&fixed_lut_dir) {
-                eprintln!(
-                    "Warning: Failed to create LUT directory at {}: {}",
-                    fixed_lut_dir.display(),
-                    e
-                );
-            }
+        if !fixed_lut_dir.exists()
+            && let Err(e) = fs::create_dir_all(&fixed_lut_dir)
+        {
+            eprintln!(
+                "Warning: Failed to create LUT directory at {}: {}",
+                fixed_lut_dir.display(),
+                e
+            );
         }
         
         let luts = list_luts(&fixed_lut_dir);
+        let cdls = list_cdls(&fixed_lut_dir);
+        let cache_entries = lut_cache::list_tables(&fixed_lut_dir).unwrap_or_default();
+        let lut_stack = if cfg.lut.stack.is_empty() {
+            vec![cfg.lut.selected.clone()]
+        } else {
+            cfg.lut.stack
+        };
         OpsinApp {
             image_input_dir: cfg.input.image_dir,
             video_input_dir: cfg.input.video_dir,
             output_dir: cfg.input.output,
             lut_dir: fixed_lut_dir,
             available_luts: luts,
+            available_cdls: cdls,
+            cdl: cfg.lut.cdl,
+            cdl_id: cfg.lut.cdl_id.unwrap_or_default(),
+            cdl_placement: cfg.lut.cdl_placement,
             current_lut: cfg.lut.selected,
+            lut_stack,
+            composite_size: cfg.lut.composite_size,
+            tool_lut_size: 33,
+            fit_before: String::new(),
+            fit_after: String::new(),
+            fit_smoothness: DEFAULT_SMOOTHNESS,
+            adjustments: cfg.lut.generate,
+            generated_name: "generated".to_string(),
+            parse_mode: cfg.lut.parse_mode,
+            table_kind: cfg.lut.table,
+            compact_size: cfg.lut.compact_size.clamp(2, MAX_COMPACT_SIZE),
+            dither: cfg.lut.dither,
+            output_profile: cfg.lut.output_profile,
+            lut_spaces: cfg.lut.color_spaces,
+            cache_entries,
+            interpolation: cfg.lut.interpolation,
+            out_of_domain: cfg.lut.out_of_domain,
+            strength: cfg.lut.strength.min(100),
             status_log: Arc::new(Mutex::new(Vec::new())),
+            luts_changed: Arc::new(AtomicBool::new(false)),
+            generated_lut_receiver: None,
             is_processing: false,
             processing_completion_receiver: None,
         }
     }
+
+    /// Exports the currently selected LUT as a Hald CLUT PNG into the LUT directory on
+    /// a background thread, reporting the result in the status log.
+    fn export_hald(&self) {
+        let lut_file = self.lut_dir.join(&self.current_lut);
+        let stem = Path::new(&self.current_lut)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let hald_file = self.lut_dir.join(format!("{}_hald.png", stem));
+        let interpolation = self.interpolation;
+        let log_arc = self.status_log.clone();
+        let luts_changed = self.luts_changed.clone();
+
+        thread::spawn(move || {
+            // Level 8 gives a 512x512 image holding a 64-point lattice
+            let result = Lut3D::load(lut_file.to_str().unwrap_or_default())
+                .map_err(io::Error::from)
+                .and_then(|lut| {
+                    lut.to_hald(hald_file.to_str().unwrap_or_default(), 8, interpolation)
+                });
+            let msg = match result {
+                Ok(()) => {
+                    luts_changed.store(true, Ordering::Relaxed);
+                    format!("Exported Hald CLUT to {}", hald_file.display())
+                }
+                Err(e) => format!("Error exporting {} as Hald CLUT: {}", lut_file.display(), e),
+            };
+            if let Ok(mut log_vec) = log_arc.lock() {
+                log_vec.push(msg);
+            }
+        });
+    }
+
+    /// Shows the precomputed tables in the LUT directory with their size and settings,
+    /// and buttons to delete single tables or purge all of them.
+    fn show_cache_view(&mut self, ui: &mut egui::Ui) {
+        let mut to_delete = None;
+        let mut purge = false;
+        if self.cache_entries.is_empty() {
+            ui.label("No precomputed tables.");
+        }
+        for (i, entry) in self.cache_entries.iter().enumerate() {
+            ui.horizontal(|ui| {
+                let name = entry
+                    .path
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let details = match entry.header {
+                    Some(header) if entry.is_current() => {
+                        let kind = match header.lattice_size {
+                            0 => "full".to_string(),
+                            size => format!("compact {}³", size),
+                        };
+                        format!(
+                            "{}, {}, {:.0}% strength",
+                            kind,
+                            header.interpolation.name(),
+                            header.strength * 100.0
+                        )
+                    }
+                    _ => "outdated format, will be regenerated".to_string(),
+                };
+                ui.label(format!(
+                    "{} ({:.1} MB, {})",
+                    name,
+                    entry.file_size as f64 / (1024.0 * 1024.0),
+                    details
+                ));
+                if ui.small_button("Delete").clicked() {
+                    to_delete = Some(i);
+                }
+            });
+        }
+        ui.horizontal(|ui| {
+            if ui.button("Refresh").clicked() {
+                self.cache_entries = lut_cache::list_tables(&self.lut_dir).unwrap_or_default();
+            }
+            if ui.button("Purge all").clicked() {
+                purge = true;
+            }
+        });
+
+        let msg = if let Some(i) = to_delete {
+            let path = &self.cache_entries[i].path;
+            Some(match fs::remove_file(path) {
+                Ok(()) => format!("Deleted precomputed table {}", path.display()),
+                Err(e) => format!("Error deleting {}: {}", path.display(), e),
+            })
+        } else if purge {
+            Some(match lut_cache::purge_tables(&self.lut_dir) {
+                Ok(count) => format!("Deleted {} precomputed tables", count),
+                Err(e) => format!("Error purging precomputed tables: {}", e),
+            })
+        } else {
+            None
+        };
+        if let Some(msg) = msg {
+            if let Ok(mut log_vec) = self.status_log.lock() {
+                log_vec.push(msg);
+            }
+            self.cache_entries = lut_cache::list_tables(&self.lut_dir).unwrap_or_default();
+        }
+    }
+
+    /// Resamples the currently selected LUT to the tool lattice size on a background
+    /// thread and saves it as `<name>_<size>.cube` in the LUT directory.
+    fn resample_current_lut(&self) {
+        let lut_file = self.lut_dir.join(&self.current_lut);
+        let stem = Path::new(&self.current_lut)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let size = self.tool_lut_size;
+        let resampled_file = self.lut_dir.join(format!("{}_{}.cube", stem, size));
+        let interpolation = self.interpolation;
+        let log_arc = self.status_log.clone();
+        let luts_changed = self.luts_changed.clone();
+
+        thread::spawn(move || {
+            let result = Lut3D::load(lut_file.to_str().unwrap_or_default())
+                .map_err(io::Error::from)
+                .and_then(|lut| lut.resample(size, interpolation))
+                .and_then(|lut| lut.to_cube(resampled_file.to_str().unwrap_or_default()));
+            let msg = match result {
+                Ok(()) => {
+                    luts_changed.store(true, Ordering::Relaxed);
+                    format!("Saved resampled LUT to {}", resampled_file.display())
+                }
+                Err(e) => format!("Error resampling {}: {}", lut_file.display(), e),
+            };
+            if let Ok(mut log_vec) = log_arc.lock() {
+                log_vec.push(msg);
+            }
+        });
+    }
+
+    /// Bakes the selected CDL and the currently selected LUT at the tool lattice size on
+    /// a background thread and saves the result as `<lut>_<cdl>.cube` in the LUT directory.
+    fn bake_cdl_current_lut(&self) {
+        let Some(cdl_name) = &self.cdl else {
+            return;
+        };
+        let lut_file = self.lut_dir.join(&self.current_lut);
+        let cdl_file = self.lut_dir.join(cdl_name);
+        let stem = |name: &str| {
+            Path::new(name)
+                .file_stem()
+                .map(|s| s.to_string_lossy().into_owned())
+                .unwrap_or_default()
+        };
+        let baked_file = self
+            .lut_dir
+            .join(format!("{}_{}.cube", stem(&self.current_lut), stem(cdl_name)));
+        let cdl_id = Some(self.cdl_id.clone()).filter(|id| !id.is_empty());
+        let placement = self.cdl_placement;
+        let size = self.tool_lut_size;
+        let interpolation = self.interpolation;
+        let log_arc = self.status_log.clone();
+        let luts_changed = self.luts_changed.clone();
+
+        thread::spawn(move || {
+            let log = |msg: &str| {
+                if let Ok(mut log_vec) = log_arc.lock() {
+                    log_vec.push(msg.to_string());
+                }
+            };
+
+            match Lut3D::load(lut_file.to_str().unwrap_or_default()) {
+                Ok(lut) => {
+                    if let Some(baked) = bake_cdl(
+                        &lut,
+                        &cdl_file,
+                        cdl_id.as_deref(),
+                        placement,
+                        size,
+                        interpolation,
+                        &log,
+                    ) {
+                        match baked.to_cube(baked_file.to_str().unwrap_or_default()) {
+                            Ok(()) => {
+                                luts_changed.store(true, Ordering::Relaxed);
+                                log(&format!("Saved baked LUT to {}", baked_file.display()))
+                            }
+                            Err(e) => log(&format!("Error saving {}: {}", baked_file.display(), e)),
+                        }
+                    }
+                }
+                Err(e) => log(&format!(
+                    "Error reading LUT file {}: {}",
+                    lut_file.display(),
+                    e
+                )),
+            }
+        });
+    }
+
+    /// Analyzes the currently selected LUT on a background thread and logs the report
+    /// and any issues found.
+    fn analyze_current_lut(&self) {
+        let lut_file = self.lut_dir.join(&self.current_lut);
+        let lut_name = self.current_lut.clone();
+        let log_arc = self.status_log.clone();
+
+        thread::spawn(move || {
+            let mut lines = vec![format!("Analysis of {}:", lut_name)];
+            match Lut3D::load(lut_file.to_str().unwrap_or_default()) {
+                Ok(lut) => {
+                    let report = lut.analyze();
+                    lines.extend(report.to_string().lines().map(|l| format!("  {}", l)));
+                    let issues = report.issues();
+                    if issues.is_empty() {
+                        lines.push("  No issues found".to_string());
+                    }
+                    lines.extend(issues.into_iter().map(|i| format!("  Warning: {}", i)));
+                }
+                Err(e) => lines.push(format!("  Error reading LUT file: {}", e)),
+            }
+            // Pushed together so the report isn't interleaved with other messages
+            if let Ok(mut log_vec) = log_arc.lock() {
+                log_vec.extend(lines);
+            }
+        });
+    }
+
+    /// Computes the inverse of the currently selected LUT at the tool lattice size on a
+    /// background thread and saves it as `<name>_inverse.cube` in the LUT directory,
+    /// logging the error bounds.
+    fn invert_current_lut(&self) {
+        let lut_file = self.lut_dir.join(&self.current_lut);
+        let stem = Path::new(&self.current_lut)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let inverse_file = self.lut_dir.join(format!("{}_inverse.cube", stem));
+        let interpolation = self.interpolation;
+        let size = self.tool_lut_size;
+        let log_arc = self.status_log.clone();
+        let luts_changed = self.luts_changed.clone();
+
+        thread::spawn(move || {
+            let local_log = |msg: String| {
+                if let Ok(mut log_vec) = log_arc.lock() {
+                    log_vec.push(msg);
+                }
+            };
+
+            local_log(format!("Inverting LUT {}", lut_file.display()));
+            let result = Lut3D::load(lut_file.to_str().unwrap_or_default())
+                .map_err(io::Error::from)
+                .and_then(|lut| lut.invert(size, interpolation))
+                .and_then(|inverse| {
+                    inverse
+                        .lut
+                        .to_cube(inverse_file.to_str().unwrap_or_default())
+                        .map(|()| inverse)
+                });
+            match result {
+                Ok(inverse) => {
+                    luts_changed.store(true, Ordering::Relaxed);
+                    local_log(format!(
+                        "Saved inverse LUT to {} (max error {:.4}, mean error {:.4}, round-trip error {:.4})",
+                        inverse_file.display(),
+                        inverse.max_error,
+                        inverse.mean_error,
+                        inverse.round_trip_error
+                    ))
+                }
+                Err(e) => local_log(format!("Error inverting {}: {}", lut_file.display(), e)),
+            }
+        });
+    }
+
+    /// Generates a LUT from the adjustments at the tool lattice size on a background
+    /// thread, saves it as `<name>.cube` in the LUT directory, and selects it.
+    ///
+    /// Names that are empty or contain path separators are rejected, so the LUT
+    /// can't be written outside the LUT directory.
+    fn generate_lut(&mut self) {
+        let stem = self.generated_name.trim();
+        if stem.is_empty() || stem.contains(['/', '\\']) {
+            if let Ok(mut log_vec) = self.status_log.lock() {
+                log_vec.push(format!(
+                    "Error generating LUT: invalid name '{}', use a plain file name",
+                    stem
+                ));
+            }
+            return;
+        }
+        let name = format!("{}.cube", stem);
+        let lut_file = self.lut_dir.join(&name);
+        let adjustments = self.adjustments.clone();
+        let size = self.tool_lut_size;
+        let log_arc = self.status_log.clone();
+        let (sender, receiver) = mpsc::channel();
+        self.generated_lut_receiver = Some(receiver);
+
+        thread::spawn(move || {
+            let result = Lut3D::from_adjustments(&adjustments, size)
+                .and_then(|lut| lut.to_cube(lut_file.to_str().unwrap_or_default()));
+            let msg = match result {
+                Ok(()) => {
+                    let _ = sender.send(name);
+                    format!("Saved generated LUT to {}", lut_file.display())
+                }
+                Err(e) => format!("Error generating LUT: {}", e),
+            };
+            if let Ok(mut log_vec) = log_arc.lock() {
+                log_vec.push(msg);
+            }
+        });
+    }
+
+    /// Fits a LUT to the original and graded images at the tool lattice size on a
+    /// background thread and saves it as `<graded>_fit.cube` in the LUT directory,
+    /// logging the fit error.
+    fn fit_lut(&self) {
+        let before_file = self.image_input_dir.join(&self.fit_before);
+        let after_file = self.image_input_dir.join(&self.fit_after);
+        let stem = Path::new(&self.fit_after)
+            .file_stem()
+            .map(|s| s.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let fit_file = self.lut_dir.join(format!("{}_fit.cube", stem));
+        let size = self.tool_lut_size;
+        let smoothness = self.fit_smoothness;
+        let log_arc = self.status_log.clone();
+        let luts_changed = self.luts_changed.clone();
+
+        thread::spawn(move || {
+            let local_log = |msg: String| {
+                if let Ok(mut log_vec) = log_arc.lock() {
+                    log_vec.push(msg);
+                }
+            };
+
+            local_log(format!(
+                "Fitting LUT to {} and {}",
+                before_file.display(),
+                after_file.display()
+            ));
+            let load = |path: &Path| {
+                file_handler::load_srgb(path)
+                    .map_err(|e| io::Error::other(format!("{}: {}", path.display(), e)))
+            };
+            let result = load(&before_file)
+                .and_then(|before| Ok((before, load(&after_file)?)))
+                .and_then(|(before, after)| {
+                    if before.dimensions() != after.dimensions() {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidInput,
+                            format!(
+                                "The images differ in size ({}x{} and {}x{})",
+                                before.width(),
+                                before.height(),
+                                after.width(),
+                                after.height()
+                            ),
+                        ));
+                    }
+                    Lut3D::fit(&before, &after, size, smoothness)
+                })
+                .and_then(|fit| {
+                    fit.lut
+                        .to_cube(fit_file.to_str().unwrap_or_default())
+                        .map(|()| fit)
+                });
+            match result {
+                Ok(fit) => {
+                    luts_changed.store(true, Ordering::Relaxed);
+                    local_log(format!(
+                        "Saved fitted LUT to {} ({} pixels, mean error {:.4}, 95th percentile {:.4}, max error {:.4})",
+                        fit_file.display(),
+                        fit.samples,
+                        fit.mean_error,
+                        fit.p95_error,
+                        fit.max_error
+                    ))
+                }
+                Err(e) => local_log(format!("Error fitting LUT: {}", e)),
+            }
+        });
+    }
 }
 
 impl Default for OpsinApp {
@@ -177,16 +951,37 @@ impl App for OpsinApp {
     /// * `_frame` - Frame information (unused in this implementation)
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
         // Check if background processing has completed
-        if self.is_processing {
-            if let Some(receiver) = &self.processing_completion_receiver {
-                if matches!(
-                    receiver.try_recv(),
-                    Ok(()) | Err(mpsc::TryRecvError::Disconnected)
-                ) {
-                    // Processing thread has finished
-                    self.is_processing = false;
-                    self.processing_completion_receiver = None;
+        if self.is_processing
+            && let Some(receiver) = &self.processing_completion_receiver
+            && matches!(
+                receiver.try_recv(),
+                Ok(()) | Err(mpsc::TryRecvError::Disconnected)
+            )
+        {
+            // Processing thread has finished
+            self.is_processing = false;
+            self.processing_completion_receiver = None;
+            // The job may have generated a new precomputed table
+            self.cache_entries = lut_cache::list_tables(&self.lut_dir).unwrap_or_default();
+        }
+
+        // Pick up LUTs saved by background tools
+        if self.luts_changed.swap(false, Ordering::Relaxed) {
+            self.available_luts = list_luts(&self.lut_dir);
+        }
+
+        // Select a generated LUT once it is saved
+        if let Some(receiver) = &self.generated_lut_receiver {
+            match receiver.try_recv() {
+                Ok(name) => {
+                    self.available_luts = list_luts(&self.lut_dir);
+                    if self.available_luts.contains(&name) {
+                        self.current_lut = name;
+                    }
+                    self.generated_lut_receiver = None;
                 }
+                Err(mpsc::TryRecvError::Disconnected) => self.generated_lut_receiver = None,
+                Err(mpsc::TryRecvError::Empty) => {}
             }
         }
 
@@ -203,11 +998,293 @@ impl App for OpsinApp {
                             ui.selectable_value(&mut self.current_lut, lut.clone(), lut);
                         }
                     });
+                if ui.button("Add to stack").clicked() && !self.current_lut.is_empty() {
+                    self.lut_stack.push(self.current_lut.clone());
+                }
+                if ui.button("Export Hald CLUT").clicked() {
+                    self.export_hald();
+                }
+                if ui.button("Refresh").clicked() {
+                    self.available_luts = list_luts(&self.lut_dir);
+                }
+            });
+
+            // Color spaces of the selected LUT
+            ui.horizontal(|ui| {
+                let current = self
+                    .lut_spaces
+                    .get(&self.current_lut)
+                    .copied()
+                    .unwrap_or_default();
+                let mut spaces = current;
+                ui.label("Input space:");
+                egui::ComboBox::from_id_salt("input_space")
+                    .selected_text(spaces.input.name())
+                    .show_ui(ui, |ui| {
+                        for space in ColorSpace::ALL {
+                            ui.selectable_value(&mut spaces.input, space, space.name());
+                        }
+                    });
+                ui.label("Output space:");
+                egui::ComboBox::from_id_salt("output_space")
+                    .selected_text(spaces.output.name())
+                    .show_ui(ui, |ui| {
+                        for space in ColorSpace::ALL {
+                            ui.selectable_value(&mut spaces.output, space, space.name());
+                        }
+                    });
+                if spaces != current {
+                    self.lut_spaces.insert(self.current_lut.clone(), spaces);
+                }
+            });
+
+            // Tools creating new LUTs from the selected one
+            ui.horizontal(|ui| {
+                ui.label("Lattice size:");
+                ui.add(egui::DragValue::new(&mut self.tool_lut_size).range(2..=129));
+                if ui.button("Resample").clicked() {
+                    self.resample_current_lut();
+                }
+                if ui.button("Invert").clicked() {
+                    self.invert_current_lut();
+                }
+                if ui.button("Analyze").clicked() {
+                    self.analyze_current_lut();
+                }
+                if ui
+                    .add_enabled(self.cdl.is_some(), egui::Button::new("Bake CDL"))
+                    .clicked()
+                {
+                    self.bake_cdl_current_lut();
+                }
+            });
+
+            // Fit a LUT to an original and a graded image
+            ui.horizontal(|ui| {
+                ui.label("Fit:");
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.fit_before)
+                        .hint_text("original image")
+                        .desired_width(140.0),
+                );
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.fit_after)
+                        .hint_text("graded image")
+                        .desired_width(140.0),
+                );
+                ui.label("Smoothness:");
+                ui.add(
+                    egui::DragValue::new(&mut self.fit_smoothness)
+                        .range(0.0..=10.0)
+                        .speed(0.01),
+                );
+                let ready = !self.fit_before.is_empty()
+                    && !self.fit_after.is_empty()
+                    && self.tool_lut_size <= MAX_FIT_SIZE;
+                if ui
+                    .add_enabled(ready, egui::Button::new("Fit LUT"))
+                    .on_disabled_hover_text(format!(
+                        "Needs both images and a lattice size of at most {}",
+                        MAX_FIT_SIZE
+                    ))
+                    .clicked()
+                {
+                    self.fit_lut();
+                }
+            });
+
+            // Generate a LUT from parametric adjustments
+            egui::CollapsingHeader::new("Generate LUT").show(ui, |ui| {
+                let adj = &mut self.adjustments;
+                ui.horizontal(|ui| {
+                    ui.label("Exposure:");
+                    ui.add(egui::Slider::new(&mut adj.exposure, -4.0..=4.0).suffix(" EV"));
+                    ui.label("Contrast:");
+                    ui.add(egui::Slider::new(&mut adj.contrast, 0.25..=4.0).logarithmic(true));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Temperature:");
+                    ui.add(egui::Slider::new(&mut adj.temperature, -100.0..=100.0));
+                    ui.label("Tint:");
+                    ui.add(egui::Slider::new(&mut adj.tint, -100.0..=100.0));
+                });
+                ui.horizontal(|ui| {
+                    ui.label("Saturation:");
+                    ui.add(egui::Slider::new(&mut adj.saturation, 0.0..=2.0));
+                });
+                let curves = [
+                    ("hue vs hue", &adj.hue_vs_hue),
+                    ("tone", &adj.curve),
+                    ("red", &adj.red_curve),
+                    ("green", &adj.green_curve),
+                    ("blue", &adj.blue_curve),
+                ];
+                let curves: Vec<String> = curves
+                    .iter()
+                    .filter(|(_, points)| !points.is_empty())
+                    .map(|(name, points)| format!("{} ({} points)", name, points.len()))
+                    .collect();
+                if !curves.is_empty() {
+                    ui.label(format!("Curves from config: {}", curves.join(", ")));
+                }
+                ui.horizontal(|ui| {
+                    ui.label("Name:");
+                    ui.add(
+                        egui::TextEdit::singleline(&mut self.generated_name).desired_width(140.0),
+                    );
+                    if ui
+                        .add_enabled(
+                            !self.generated_name.trim().is_empty(),
+                            egui::Button::new("Generate"),
+                        )
+                        .clicked()
+                    {
+                        self.generate_lut();
+                    }
+                    if ui.button("Reset").clicked() {
+                        self.adjustments = Adjustments::default();
+                    }
+                });
+            });
+
+            // ASC CDL graded together with the LUT stack
+            ui.horizontal(|ui| {
+                ui.label("CDL:");
+                egui::ComboBox::from_label("CDL")
+                    .selected_text(self.cdl.as_deref().unwrap_or("None"))
+                    .show_ui(ui, |ui| {
+                        ui.selectable_value(&mut self.cdl, None, "None");
+                        for cdl in &self.available_cdls {
+                            ui.selectable_value(&mut self.cdl, Some(cdl.clone()), cdl);
+                        }
+                    });
+                ui.label("ID:");
+                ui.add(
+                    egui::TextEdit::singleline(&mut self.cdl_id)
+                        .hint_text("first")
+                        .desired_width(80.0),
+                );
+                egui::ComboBox::from_id_salt("cdl_placement")
+                    .selected_text(format!("{} LUT", self.cdl_placement.name()))
+                    .show_ui(ui, |ui| {
+                        for placement in CdlPlacement::ALL {
+                            let label = format!("{} LUT", placement.name());
+                            ui.selectable_value(&mut self.cdl_placement, placement, label);
+                        }
+                    });
+                if ui.button("Refresh").clicked() {
+                    self.available_cdls = list_cdls(&self.lut_dir);
+                }
+            });
+
+            // Ordered LUT stack, applied top to bottom
+            ui.label("LUT stack:");
+            let mut stack_action = None;
+            for (i, lut) in self.lut_stack.iter().enumerate() {
+                ui.horizontal(|ui| {
+                    ui.label(format!("{}. {}", i + 1, lut));
+                    if ui.small_button("Up").clicked() && i > 0 {
+                        stack_action = Some((i, StackAction::MoveUp));
+                    }
+                    if ui.small_button("Down").clicked() && i + 1 < self.lut_stack.len() {
+                        stack_action = Some((i, StackAction::MoveDown));
+                    }
+                    if ui.small_button("Remove").clicked() {
+                        stack_action = Some((i, StackAction::Remove));
+                    }
+                });
+            }
+            match stack_action {
+                Some((i, StackAction::MoveUp)) => self.lut_stack.swap(i - 1, i),
+                Some((i, StackAction::MoveDown)) => self.lut_stack.swap(i, i + 1),
+                Some((i, StackAction::Remove)) => {
+                    self.lut_stack.remove(i);
+                }
+                None => {}
+            }
+
+            // Interpolation method dropdown
+            ui.horizontal(|ui| {
+                ui.label("Interpolation:");
+                egui::ComboBox::from_label("Interpolation")
+                    .selected_text(self.interpolation.name())
+                    .show_ui(ui, |ui| {
+                        for mode in Interpolation::ALL {
+                            ui.selectable_value(&mut self.interpolation, mode, mode.name());
+                        }
+                    });
+            });
+
+            // Out-of-domain handling dropdown
+            ui.horizontal(|ui| {
+                ui.label("Out of domain:");
+                egui::ComboBox::from_label("Out of domain")
+                    .selected_text(self.out_of_domain.name())
+                    .show_ui(ui, |ui| {
+                        for mode in OutOfDomain::ALL {
+                            ui.selectable_value(&mut self.out_of_domain, mode, mode.name());
+                        }
+                    });
+            });
+
+            // LUT strength slider
+            ui.horizontal(|ui| {
+                ui.label("Strength:");
+                ui.add(egui::Slider::new(&mut self.strength, 0..=100).suffix("%"));
+            });
+
+            // Quantization of 8-bit exports
+            ui.horizontal(|ui| {
+                ui.label("Dither:");
+                egui::ComboBox::from_label("Dither")
+                    .selected_text(self.dither.name())
+                    .show_ui(ui, |ui| {
+                        for mode in Dither::ALL {
+                            ui.selectable_value(&mut self.dither, mode, mode.name());
+                        }
+                    });
+            });
+
+            // Color profile of exported images
+            ui.horizontal(|ui| {
+                ui.label("Output profile:");
+                egui::ComboBox::from_label("Output profile")
+                    .selected_text(self.output_profile.name())
+                    .show_ui(ui, |ui| {
+                        for profile in OutputProfile::ALL {
+                            ui.selectable_value(&mut self.output_profile, profile, profile.name());
+                        }
+                    });
+            });
+
+            // Precomputed table kind used for processing
+            ui.horizontal(|ui| {
+                ui.label("Table:");
+                egui::ComboBox::from_label("Table")
+                    .selected_text(self.table_kind.name())
+                    .show_ui(ui, |ui| {
+                        for kind in TableKind::ALL {
+                            ui.selectable_value(&mut self.table_kind, kind, kind.name());
+                        }
+                    });
+                if self.table_kind == TableKind::Compact {
+                    ui.label("Lattice size:");
+                    ui.add(
+                        egui::DragValue::new(&mut self.compact_size).range(2..=MAX_COMPACT_SIZE),
+                    );
+                }
+            });
+
+            // Precomputed table cache management
+            egui::CollapsingHeader::new("Precomputed tables").show(ui, |ui| {
+                self.show_cache_view(ui);
             });
 
             // Processing control button
             if self.is_processing {
                 ui.label("Processing... please wait.");
+            } else if self.lut_stack.is_empty() {
+                ui.label("Add at least one LUT to the stack to start processing.");
             } else if ui.button("Start Processing").clicked() {
                 self.is_processing = true;
                 
@@ -215,8 +1292,63 @@ impl App for OpsinApp {
                 let image_dir = self.image_input_dir.clone();
                 let video_dir = self.video_input_dir.clone();
                 let output_dir = self.output_dir.clone();
-                let lut_file = self.lut_dir.join(&self.current_lut);
-                let bin_name = format!("precomputed_{}.bin", &self.current_lut);
+                let lut_files: Vec<PathBuf> =
+                    self.lut_stack.iter().map(|l| self.lut_dir.join(l)).collect();
+                let lut_spaces: Vec<LutSpaces> = self
+                    .lut_stack
+                    .iter()
+                    .map(|l| self.lut_spaces.get(l).copied().unwrap_or_default())
+                    .collect();
+                let composite_size = self.composite_size;
+                let parse_mode = self.parse_mode;
+                let interpolation = self.interpolation;
+                let out_of_domain = self.out_of_domain;
+                let options = ApplyOptions {
+                    interpolation,
+                    strength: self.strength as f32 / 100.0,
+                };
+                let dither = self.dither;
+                let output_profile = self.output_profile;
+                let table_kind = self.table_kind;
+                let compact_size = self.compact_size;
+                let cdl_job = self.cdl.as_ref().map(|name| {
+                    let id = Some(self.cdl_id.clone()).filter(|id| !id.is_empty());
+                    (self.lut_dir.join(name), id, self.cdl_placement)
+                });
+                // Tables differ per LUT stack (including the CDL), interpolation
+                // method, out-of-domain handling, strength and table kind, so key
+                // the cache by all of them
+                let mut kind_suffix = match table_kind {
+                    TableKind::Full => String::new(),
+                    TableKind::Compact => format!("_compact{}", compact_size),
+                };
+                if out_of_domain != OutOfDomain::Clamp {
+                    kind_suffix.push_str(&format!("_{}", out_of_domain.name()));
+                }
+                let mut baked_stack = self.lut_stack.clone();
+                match (&self.cdl, self.cdl_placement) {
+                    (Some(cdl), CdlPlacement::Before) => baked_stack.insert(0, cdl.clone()),
+                    (Some(cdl), CdlPlacement::After) => baked_stack.push(cdl.clone()),
+                    (None, _) => {}
+                }
+                let bin_name = if baked_stack.len() == 1 {
+                    format!(
+                        "precomputed_{}_{}_{}{}.bin",
+                        &baked_stack[0],
+                        interpolation.name(),
+                        self.strength,
+                        kind_suffix
+                    )
+                } else {
+                    format!(
+                        "precomputed_{}_{}_{}_{}{}.bin",
+                        baked_stack.join("+"),
+                        composite_size,
+                        interpolation.name(),
+                        self.strength,
+                        kind_suffix
+                    )
+                };
                 let bin_path = self.lut_dir.join(bin_name);
                 let log_arc = self.status_log.clone();
 
@@ -233,34 +1365,55 @@ impl App for OpsinApp {
                         }
                     };
 
-                    // Load and process the selected LUT
-                    local_log(&format!("Loading LUT from {}", lut_file.display()));
-                    if let Ok(lut3d) = Lut3D::from_cube(lut_file.to_str().unwrap_or_default()) {
-                        local_log(&format!("Loaded LUT: {}", lut_file.display()));
-                        
-                        // Generate or load precomputed LUT mapping table
-                        match lut3d.load_or_generate_map(bin_path.to_str().unwrap_or_default()) {
-                            Ok(table) => {
-                                // Process images using the LUT
-                                local_log("Starting image processing...");
-                                file_handler::process_images(
-                                    &image_dir,
-                                    &output_dir,
-                                    &table,
-                                    log_arc.clone(),
-                                );
-                                local_log("Image processing complete.");
-                            }
-                            Err(e) => {
-                                local_log(&format!(
-                                    "Error loading LUT map {}: {}",
-                                    bin_path.display(),
-                                    e
-                                ));
-                            }
-                        }
-                    } else {
-                        local_log(&format!("Error reading LUT file {}", lut_file.display()));
+                    // Load the LUT stack and bake it, with the CDL, into a single LUT
+                    // if needed
+                    let mut table_generator = None;
+                    let lut3d = load_lut_stack(
+                        &lut_files,
+                        &lut_spaces,
+                        composite_size,
+                        interpolation,
+                        out_of_domain,
+                        parse_mode,
+                        &local_log,
+                    )
+                    .and_then(|lut| match &cdl_job {
+                        Some((cdl_file, cdl_id, placement)) => bake_cdl(
+                            &lut,
+                            cdl_file,
+                            cdl_id.as_deref(),
+                            *placement,
+                            composite_size,
+                            interpolation,
+                            &local_log,
+                        ),
+                        None => Some(lut),
+                    });
+                    if let Some(lut3d) = lut3d {
+                        // Load the precomputed LUT mapping table, or build it while
+                        // the first images are processed by direct interpolation
+                        let (table, generator) =
+                            start_table(
+                                lut3d,
+                                options,
+                                table_kind,
+                                compact_size,
+                                bin_path,
+                                log_arc.clone(),
+                            );
+
+                        // Process images using the LUT
+                        local_log("Starting image processing...");
+                        file_handler::process_images(
+                            &image_dir,
+                            &output_dir,
+                            &table,
+                            dither,
+                            output_profile,
+                            log_arc.clone(),
+                        );
+                        local_log("Image processing complete.");
+                        table_generator = generator;
                     }
 
                     // Process videos (note: video processing doesn't use LUT in current implementation)
@@ -268,6 +1421,12 @@ impl App for OpsinApp {
                     file_handler::process_videos(&video_dir, &output_dir, log_arc.clone());
                     local_log("Video processing complete.");
 
+                    // Let a table still being generated finish and save
+                    if let Some(handle) = table_generator {
+                        local_log("Waiting for the precomputed table...");
+                        let _ = handle.join();
+                    }
+
                     // Signal completion to the main thread
                     let _ = sender.send(());
                 });
@@ -38,27 +38,21 @@ pub fn copy_metadata(src: &Path, dst: &Path) -> io::Result<()> {
     // Read metadata from the source file
     // This extracts all available EXIF data including camera settings, timestamps, GPS data, etc.
     let src_metadata = Metadata::new_from_path(src).map_err(|e| {
-        io::Error::new(
-            io::ErrorKind::Other,
-            format!(
-                "Failed to read metadata from source file {}: {}",
-                src.display(),
-                e
-            ),
-        )
+        io::Error::other(format!(
+            "Failed to read metadata from source file {}: {}",
+            src.display(),
+            e
+        ))
     })?;
 
     // Write the extracted metadata to the destination file
     // This embeds the EXIF data into the processed image, preserving original context
     src_metadata.write_to_file(dst).map_err(|e| {
-        io::Error::new(
-            io::ErrorKind::Other,
-            format!(
-                "Failed to write metadata to destination file {}: {}",
-                dst.display(),
-                e
-            ),
-        )
+        io::Error::other(format!(
+            "Failed to write metadata to destination file {}: {}",
+            dst.display(),
+            e
+        ))
     })?;
 
     Ok(())